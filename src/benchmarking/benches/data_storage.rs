@@ -0,0 +1,154 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use rand::seq::SliceRandom;
+
+// The ecs builds with bazel, so pull its sources in directly rather than
+// copying them, to keep these numbers honest as the storages change.
+#[allow(dead_code)]
+mod entity {
+    include!("../../ecs/src/entity/entity.rs");
+}
+
+#[allow(dead_code)]
+#[macro_use]
+mod data_storage {
+    use super::entity;
+
+    include!("../../ecs/src/data_storage/data_storage.rs");
+}
+
+use data_storage::*;
+use entity::{Entity, EntityAllocator};
+
+const ITEM_COUNT: usize = 10000;
+
+#[derive(Debug)]
+struct Position {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub vx: f64,
+    pub vy: f64,
+    pub vz: f64,
+    pub ax: f64,
+    pub ay: f64,
+    pub az: f64,
+}
+
+impl Position {
+    fn new() -> Self {
+        Self {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+            vx: 0.0,
+            vy: 0.0,
+            vz: 0.0,
+            ax: 0.0,
+            ay: 0.0,
+            az: 0.0,
+        }
+    }
+}
+
+soa! {
+    struct SoaPosition => SoaPositionColumns, SoaPositionRef, SoaPositionMut {
+        x: f64,
+        y: f64,
+        z: f64,
+        vx: f64,
+        vy: f64,
+        vz: f64,
+        ax: f64,
+        ay: f64,
+        az: f64,
+    }
+}
+
+impl SoaPosition {
+    fn new() -> Self {
+        Self {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+            vx: 0.0,
+            vy: 0.0,
+            vz: 0.0,
+            ax: 0.0,
+            ay: 0.0,
+            az: 0.0,
+        }
+    }
+}
+
+/// Fill a storage the way a game would, with some entities despawned along
+/// the way so the sparse storages aren't perfectly in order.
+fn filled<T, S: DataStorage<T>>(new_data: impl Fn() -> T) -> (S, Vec<Entity>) {
+    let mut allocator = EntityAllocator::new(0);
+    let mut storage = S::default();
+    let mut entities = Vec::new();
+
+    for i in 0..ITEM_COUNT * 2 {
+        let entity = allocator.allocate();
+        storage.insert(entity, new_data());
+
+        if i % 2 == 0 {
+            storage.remove(entity);
+            allocator.free(entity);
+        } else {
+            entities.push(entity);
+        }
+    }
+
+    entities.shuffle(&mut rand::thread_rng());
+
+    (storage, entities)
+}
+
+fn storage_accesses<T, S>(
+    c: &mut Criterion,
+    name: &str,
+    new_data: impl Fn() -> T,
+    mut update: impl for<'a> FnMut(S::Mut<'a>),
+) where S: DataStorage<T> {
+    let (mut storage, entities) = filled::<T, S>(new_data);
+    let mut next = entities.iter().cycle();
+
+    c.bench_function(&format!("{} get", name), |b| {
+        b.iter(|| storage.get(black_box(*next.next().unwrap())).is_some())
+    });
+
+    c.bench_function(&format!("{} iter mut update", name), |b| {
+        b.iter(|| {
+            for (_, data) in storage.iter_mut() {
+                update(data);
+            }
+        });
+    });
+}
+
+// A closure isn't inferred as general over the Mut lifetime.
+fn update_soa_position(position: SoaPositionMut) {
+    *position.x += 1.0;
+}
+
+fn storages(c: &mut Criterion) {
+    storage_accesses::<_, BTreeMapStorage<_>>(c, "btree map storage", Position::new, |position| position.x += 1.0);
+    storage_accesses::<_, HashMapStorage<_>>(c, "hash map storage", Position::new, |position| position.x += 1.0);
+    storage_accesses::<_, SparseSetStorage<_>>(c, "sparse set storage", Position::new, |position| position.x += 1.0);
+    storage_accesses::<_, SoaStorage<_>>(c, "soa storage", SoaPosition::new, update_soa_position);
+
+    // The case SoA is for, touching one field without pulling the rest into cache.
+    let (mut storage, _) = filled::<_, SoaStorage<_>>(SoaPosition::new);
+
+    c.bench_function("soa storage column update", |b| {
+        b.iter(|| {
+            for x in storage.columns_mut().x.iter_mut() {
+                *x += 1.0;
+            }
+        });
+    });
+}
+
+criterion_group!(storage_benches, storages);
+
+criterion_main!(storage_benches);
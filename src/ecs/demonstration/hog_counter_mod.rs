@@ -0,0 +1,69 @@
+//! An example mod. Build it, then copy the shared object into the
+//! demonstration's mods directory to have it loaded at startup.
+use connors_ecs::*;
+use movement::{Mover, MovementUpdater};
+
+pub struct HogCount {
+    most_hogs: usize,
+}
+
+impl DataTrait for HogCount {
+    type Storage = BTreeMapStorage<Self>;
+}
+
+pub struct HogCountUpdater {
+    movers: DataSet<Mover>,
+    hog_count: DataSingleton<HogCount>,
+}
+
+impl UpdaterTrait for HogCountUpdater {
+    fn register(nucleus: Nucleus) -> Result<(), EcsError> {
+        nucleus.add_data_singleton(HogCount {
+            most_hogs: 0,
+        })?;
+
+        Ok(())
+    }
+
+    fn new(nucleus: Nucleus) -> Result<Self, EcsError> {
+        Ok(Self {
+            movers: nucleus.get_data_set::<Mover>()?,
+            hog_count: nucleus.get_data_singleton::<HogCount>()?,
+        })
+    }
+
+    fn after() -> Vec<UpdaterId> {
+        vec![UpdaterId::of::<MovementUpdater>()]
+    }
+
+    fn rate() -> UpdateRate {
+        UpdateRate::Hz(1.0)
+    }
+
+    fn update(&self) {
+        let Ok(hogs) = self.movers.get().map(|movers| movers.len()) else {
+            return;
+        };
+        let Ok(mut hog_count) = self.hog_count.get() else {
+            return;
+        };
+
+        hog_count.most_hogs = hog_count.most_hogs.max(hogs);
+
+        println!("hog counter mod: {} hogs, most {}", hogs, hog_count.most_hogs);
+    }
+}
+
+fn load(context: &mut ModContext) -> Result<(), EcsError> {
+    context.add_updater::<HogCountUpdater>("main")
+}
+
+fn unload(nucleus: &Nucleus) {
+    if let Ok(hog_count) = nucleus.get_data_singleton::<HogCount>() {
+        if let Ok(hog_count) = hog_count.get() {
+            println!("hog counter mod unloaded, most hogs {}", hog_count.most_hogs);
+        }
+    }
+}
+
+declare_mod!("hog_counter", load, unload);
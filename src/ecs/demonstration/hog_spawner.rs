@@ -1,47 +1,77 @@
 use connors_ecs::*;
 use movement::Mover;
+use status_effects::{StatusEffects, StatusEffectsEnum};
 
 pub struct HogSpawnData {
-    next_hog_id: usize,
+    hogs_spawned: usize,
 }
 
-impl DataTrait for HogSpawnData {}
+impl DataTrait for HogSpawnData {
+    type Storage = BTreeMapStorage<Self>;
+}
 
 pub struct HogSpawnUpdater {
+    nucleus: Nucleus,
     movers: DataSet<Mover>,
+    status_effects: DataSet<StatusEffects>,
     hog_spawn: DataSingleton<HogSpawnData>,
 }
 
 impl UpdaterTrait for HogSpawnUpdater {
-    fn register(nucleus: Nucleus) {
+    fn register(nucleus: Nucleus) -> Result<(), EcsError> {
         nucleus.add_data_singleton(HogSpawnData {
-            next_hog_id: 0,
-        });
+            hogs_spawned: 0,
+        })?;
+
+        Ok(())
     }
 
-    fn new(nucleus: Nucleus) -> Self {
-        Self {
-            movers: nucleus.get_data_set::<Mover>(),
-            hog_spawn: nucleus.get_data_singleton::<HogSpawnData>(),
-        }
+    fn new(nucleus: Nucleus) -> Result<Self, EcsError> {
+        Ok(Self {
+            nucleus: nucleus.clone(),
+            movers: nucleus.get_data_set::<Mover>()?,
+            status_effects: nucleus.get_data_set::<StatusEffects>()?,
+            hog_spawn: nucleus.get_data_singleton::<HogSpawnData>()?,
+        })
     }
 
-    fn update(&self) {
-        let mut movers = self.movers.get();
-        let mut hog_spawn = self.hog_spawn.get();
+    fn rate() -> UpdateRate {
+        UpdateRate::Hz(10.0)
+    }
 
-        if movers.len() > 500 {
+    fn update(&self) {
+        if !self.movers.get().is_ok_and(|movers| movers.len() <= 500) {
             return;
         }
 
-        let next_hog_id = hog_spawn.next_hog_id;
-        hog_spawn.next_hog_id += 1;
+        // Spawned before taking the movers, since despawn locks the Nucleus
+        // then every set.
+        let Ok(hog) = self.nucleus.spawn() else {
+            return;
+        };
+
+        let (Ok(mut movers), Ok(mut hog_spawn)) = (self.movers.get(), self.hog_spawn.get()) else {
+            return;
+        };
+
+        hog_spawn.hogs_spawned += 1;
 
         let mut new_hog_mover = Mover::new();
         new_hog_mover.ax = 0.1;
         new_hog_mover.ay = 0.0;
         new_hog_mover.az = -0.1;
 
-        movers.insert(next_hog_id, new_hog_mover);
+        movers.insert(hog, new_hog_mover);
+        drop(movers);
+
+        // Every other hog spawns frozen.
+        if hog_spawn.hogs_spawned % 2 == 0 {
+            let mut status_effects = StatusEffects::new();
+            status_effects.apply(StatusEffectsEnum::Frost, 100);
+
+            if let Ok(mut all_status_effects) = self.status_effects.get() {
+                all_status_effects.insert(hog, status_effects);
+            }
+        }
     }
 }
\ No newline at end of file
@@ -1,35 +1,71 @@
 use movement::*;
 use hog_spawner::HogSpawnUpdater;
 use user_input::UserInputUpdater;
+use status_effects::StatusEffectUpdater;
 use connors_ecs::*;
+use std::{
+    path::Path,
+    time::Duration,
+};
 
-fn main() {
+/// Shared objects here are loaded as mods.
+const MODS_DIRECTORY: &str = "mods";
+
+fn main() -> Result<(), EcsError> {
     let mut nucleus = Nucleus::new("demo");
-    let mut main = Runner::new("main", nucleus.clone());
+
+    // For the access report printed on exit.
+    nucleus.set_access_tracking(true)?;
+
+    let mut main = Runner::new("main", nucleus.clone())?.with_timestep(Duration::from_millis(20));
+
+
+
+    main.register_updater::<MovementUpdater>()?;
+    main.register_updater::<HogSpawnUpdater>()?;
+    main.register_updater::<UserInputUpdater>()?;
+    main.register_updater::<StatusEffectUpdater>()?;
+
+    Runner::spawn(&nucleus, "input", |input| {
+        input.set_timestep(Some(Duration::from_millis(10)));
+        input.add_updater::<UserInputUpdater>()
+    })?;
 
 
 
-    main.register_updater::<MovementUpdater>();
-    main.register_updater::<HogSpawnUpdater>();
-    main.register_updater::<UserInputUpdater>();
 
-    let nucleus_clone = nucleus.clone();
+    main.add_updater::<MovementUpdater>()?;
+    main.add_updater::<HogSpawnUpdater>()?;
+    main.add_updater::<StatusEffectUpdater>()?;
 
-    std::thread::spawn(move || {
-        let mut input = Runner::new("input", nucleus_clone);
+    // Mods like hog_counter_mod add their updaters to main at its first tick.
+    let mut mods = ModLoader::new(nucleus.clone());
 
-        input.add_updater::<UserInputUpdater>();
+    if Path::new(MODS_DIRECTORY).is_dir() {
+        for name in unsafe { mods.load_dir(MODS_DIRECTORY)? } {
+            println!("loaded mod {}", name);
+        }
+    }
 
-        input.run();
-    });
 
 
+    if let Err(error) = main.run() {
+        eprintln!("{}", error);
+    }
 
+    for error in nucleus.shutdown() {
+        eprintln!("{}", error);
+    }
 
-    main.add_updater::<MovementUpdater>();
-    main.add_updater::<HogSpawnUpdater>();
+    // Runners drop mod updaters at the start of a tick, and main won't tick again.
+    drop(main);
 
+    // Nothing here holds onto the mods' data or types.
+    for error in unsafe { mods.unload_all(Duration::from_secs(1)) } {
+        eprintln!("{}", error);
+    }
 
+    print!("{}", nucleus.access_report()?);
 
-    main.run();
+    Ok(())
 }
\ No newline at end of file
@@ -1,5 +1,5 @@
 use connors_ecs::*;
-use std::time::{Duration, Instant};
+use std::time::Instant;
 use user_input::RawUserInput;
 
 #[derive(Debug)]
@@ -31,13 +31,18 @@ impl Mover {
     }
 }
 
-impl DataTrait for Mover {}
+impl DataTrait for Mover {
+    // Every mover is updated every tick, so keep them packed.
+    type Storage = SparseSetStorage<Self>;
+}
 
 pub struct MovementSystem {
     update_count: u32,
 }
 
-impl DataTrait for MovementSystem {}
+impl DataTrait for MovementSystem {
+    type Storage = BTreeMapStorage<Self>;
+}
 
 pub struct MovementUpdater {
     raw_user_input: DataSingleton<RawUserInput>,
@@ -46,30 +51,35 @@ pub struct MovementUpdater {
 }
 
 impl UpdaterTrait for MovementUpdater {
-    fn register(nucleus: Nucleus) {
-        nucleus.add_data_singleton(MovementSystem { update_count: 0 });
-        nucleus.add_data_set::<Mover>();
+    fn register(nucleus: Nucleus) -> Result<(), EcsError> {
+        nucleus.add_data_singleton(MovementSystem { update_count: 0 })?;
+        nucleus.add_data_set::<Mover>()?;
+
+        Ok(())
     }
 
-    fn new(nucleus: Nucleus) -> Self {
-        Self {
-            raw_user_input: nucleus.get_data_singleton::<RawUserInput>(),
-            system: nucleus.get_data_singleton::<MovementSystem>(),
-            movers: nucleus.get_data_set::<Mover>(),
-        }
+    fn new(nucleus: Nucleus) -> Result<Self, EcsError> {
+        Ok(Self {
+            raw_user_input: nucleus.get_data_singleton::<RawUserInput>()?,
+            system: nucleus.get_data_singleton::<MovementSystem>()?,
+            movers: nucleus.get_data_set::<Mover>()?,
+        })
     }
 
     fn update(&self) {
         let now = Instant::now();
-        let mut system = self.system.get();
-        let mut movers = self.movers.get();
-        let raw_user_input = self.raw_user_input.get();
+        // Another updater panicked holding one of these, skip the tick.
+        let (Ok(mut system), Ok(mut movers), Ok(raw_user_input)) =
+            (self.system.get(), self.movers.get(), self.raw_user_input.get())
+        else {
+            return;
+        };
 
         println!("raw input count: {:?}", raw_user_input);
 
         drop(raw_user_input);
 
-        for mover in movers.values_mut() {
+        for (_, mover) in movers.iter_mut() {
             mover.x += mover.vx * 0.1;
             mover.y += mover.vy * 0.1;
             mover.z += mover.vz * 0.1;
@@ -84,8 +94,6 @@ impl UpdaterTrait for MovementUpdater {
         system.update_count += 1;
 
         println!("run {} took {:?}", system.update_count, now.elapsed());
-
-        std::thread::sleep(Duration::from_millis(20));
     }
 }
 
@@ -1,7 +1,7 @@
 use connors_ecs::*;
-use std::time::{Duration, Instant};
-use user_input::RawUserInput;
+use movement::{Mover, MovementUpdater};
 
+#[derive(Copy, Clone, Debug)]
 pub enum StatusEffectsEnum {
     Fire,
     Poison,
@@ -9,68 +9,68 @@ pub enum StatusEffectsEnum {
     Acid,
 }
 
+/// Ticks left on each effect, indexed by [StatusEffectsEnum].
 #[derive(Debug)]
 pub struct StatusEffects {
-    status_effects: [4; StatusEffectsEnum]
+    status_effects: [u32; 4],
 }
 
 impl StatusEffects {
     pub fn new() -> Self {
         Self {
-            status_effects: [],
+            status_effects: [0; 4],
         }
     }
-}
-
-impl DataTrait for StatusEffects {}
 
-pub struct StatusEffectUpdater {
-    status_effects: DataSet<StatusEffects>,
-    movers: DataSet<Mover>,
-}
+    pub fn apply(&mut self, effect: StatusEffectsEnum, ticks: u32) {
+        let remaining = &mut self.status_effects[effect as usize];
 
-impl UpdaterTrait for MovementUpdater {
-    fn register(nucleus: Nucleus) {
-        nucleus.add_data_set(MovementSystem { update_count: 0 });
+        *remaining = (*remaining).max(ticks);
     }
 
-    fn new(nucleus: Nucleus) -> Self {
-        Self {
-            status_effects: nucleus.get_data_set::<StatusEffects>(),
-            movers: nucleus.get_data_set::<Mover>(),
-        }
+    pub fn has(&self, effect: StatusEffectsEnum) -> bool {
+        self.status_effects[effect as usize] > 0
     }
+}
 
-    fn update(&self) {
-        let now = Instant::now();
-        let mut system = self.system.get();
-        let mut movers = self.movers.get();
-        let raw_user_input = self.raw_user_input.get();
-
-        println!("raw input count: {:?}", raw_user_input);
-
-        drop(raw_user_input);
+impl DataTrait for StatusEffects {
+    type Storage = BTreeMapStorage<Self>;
+}
 
-        for mover in movers.values_mut() {
-            mover.x += mover.vx * 0.1;
-            mover.y += mover.vy * 0.1;
-            mover.z += mover.vz * 0.1;
+pub struct StatusEffectUpdater {
+    status_effect_movers: Query<(Write<StatusEffects>, Write<Mover>)>,
+}
 
-            mover.vx += mover.ax * 0.1;
-            mover.vy += mover.ay * 0.1;
-            mover.vz += mover.az * 0.1;
+impl UpdaterTrait for StatusEffectUpdater {
+    fn register(nucleus: Nucleus) -> Result<(), EcsError> {
+        nucleus.add_data_set::<StatusEffects>()?;
 
-            // println!("{:?}", mover);
-        }
+        Ok(())
+    }
 
-        system.update_count += 1;
+    fn new(nucleus: Nucleus) -> Result<Self, EcsError> {
+        Ok(Self {
+            status_effect_movers: nucleus.query()?,
+        })
+    }
 
-        println!("run {} took {:?}", system.update_count, now.elapsed());
+    /// Frost has to slow movers down before they move.
+    fn before() -> Vec<UpdaterId> {
+        vec![UpdaterId::of::<MovementUpdater>()]
+    }
 
-        std::thread::sleep(Duration::from_millis(20));
+    fn update(&self) {
+        // A poisoned set skips the tick, like any other failed get in an updater.
+        let _ = self.status_effect_movers.for_each(|_, (status_effects, mover)| {
+            if status_effects.has(StatusEffectsEnum::Frost) {
+                mover.ax *= 0.5;
+                mover.ay *= 0.5;
+                mover.az *= 0.5;
+            }
+
+            for remaining in status_effects.status_effects.iter_mut() {
+                *remaining = remaining.saturating_sub(1);
+            }
+        });
     }
 }
-
-
-
-
@@ -1,5 +1,4 @@
 use connors_ecs::*;
-use std::time::Duration;
 
 #[derive(Debug)]
 pub struct RawUserInput {
@@ -7,27 +6,30 @@ pub struct RawUserInput {
     input_count: u32,
 }
 
-impl DataTrait for RawUserInput {}
+impl DataTrait for RawUserInput {
+    type Storage = BTreeMapStorage<Self>;
+}
 
 pub struct UserInputUpdater {
     raw_input: DataSingleton<RawUserInput>,
 }
 
 impl UpdaterTrait for UserInputUpdater {
-    fn register(nucleus: Nucleus) {
+    fn register(nucleus: Nucleus) -> Result<(), EcsError> {
         nucleus.add_data_singleton(RawUserInput {
             input_count: 0,
-        });
+        })?;
+
+        Ok(())
     }
 
-    fn new(nucleus: Nucleus) -> Self {
-        Self {
-            raw_input: nucleus.get_data_singleton::<RawUserInput>()
-        }
+    fn new(nucleus: Nucleus) -> Result<Self, EcsError> {
+        Ok(Self {
+            raw_input: nucleus.get_data_singleton::<RawUserInput>()?
+        })
     }
 
     fn update(&self) {
         // println!("getting user input");
-        std::thread::sleep(Duration::from_millis(10));
     }
 }
\ No newline at end of file
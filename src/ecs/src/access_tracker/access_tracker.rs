@@ -0,0 +1,281 @@
+use ecs_error::EcsError;
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard, TryLockError,
+    },
+    time::{Duration, Instant},
+};
+
+/// The updater running on this thread, so gets can be counted against it.
+#[derive(Clone)]
+struct AccessContext {
+    runner: Arc<str>,
+    tick: u64,
+    updater: &'static str,
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<AccessContext>> = const { RefCell::new(None) };
+}
+
+/// Puts the previous context back, even if the updater panics.
+struct RestoreContext(Option<AccessContext>);
+
+impl Drop for RestoreContext {
+    fn drop(&mut self) {
+        let previous = self.0.take();
+
+        CURRENT.with(|current| *current.borrow_mut() = previous);
+    }
+}
+
+/// Count the gets made on this thread while f runs against the updater in
+/// the runner's tick. Used by Runner::update.
+pub fn with_access_context<R>(runner: &Arc<str>, tick: u64, updater: &'static str, f: impl FnOnce() -> R) -> R {
+    let context = AccessContext {
+        runner: runner.clone(),
+        tick,
+        updater,
+    };
+
+    let _restore = RestoreContext(CURRENT.with(|current| current.borrow_mut().replace(context)));
+
+    f()
+}
+
+/// Running totals for one updater's gets on one container.
+#[derive(Default)]
+struct AccessCounts {
+    gets: u64,
+    contended: u64,
+    ticks: u64,
+    max_gets_per_tick: u64,
+    wait: Duration,
+    max_wait: Duration,
+    last_tick: Option<u64>,
+    gets_this_tick: u64,
+}
+
+/// Keyed by runner and updater, None for gets made outside of any updater.
+type AccessKey = Option<(Arc<str>, &'static str)>;
+
+/// Counts the gets on a data container, by the runner tick and updater they
+/// were made in, and how long each waited for the lock.
+///
+/// Shared by every clone of the container, and by the Nucleus for
+/// [AccessTracker::report].
+///
+/// Off until [AccessTracker::set_enabled], since counting locks the counts
+/// on every get, which every updater getting the container would contend on.
+#[derive(Clone)]
+pub struct AccessTracker {
+    data: &'static str,
+    enabled: Arc<AtomicBool>,
+    counts: Arc<Mutex<HashMap<AccessKey, AccessCounts>>>,
+}
+
+impl AccessTracker {
+    pub fn new(data: &'static str) -> Self {
+        Self {
+            data,
+            enabled: Arc::new(AtomicBool::new(false)),
+            counts: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Start or stop counting gets, for every clone of the container. The
+    /// counts so far are kept, see [AccessTracker::reset].
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Lock the mutex, recording the get and how long it waited if counting
+    /// is enabled.
+    pub fn lock<'a, T>(&self, mutex: &'a Mutex<T>) -> Result<MutexGuard<'a, T>, EcsError> {
+        if !self.is_enabled() {
+            return mutex.lock().map_err(|_| EcsError::Poisoned { data: self.data });
+        }
+
+        // Only time the gets that have to wait, most don't.
+        match mutex.try_lock() {
+            Ok(guard) => {
+                self.record(None);
+
+                return Ok(guard);
+            }
+            Err(TryLockError::Poisoned(_)) => return Err(EcsError::Poisoned { data: self.data }),
+            Err(TryLockError::WouldBlock) => {}
+        }
+
+        let start = Instant::now();
+        let guard = mutex.lock().map_err(|_| EcsError::Poisoned { data: self.data })?;
+
+        self.record(Some(start.elapsed()));
+
+        Ok(guard)
+    }
+
+    /// wait is None for gets that didn't have to wait.
+    fn record(&self, wait: Option<Duration>) {
+        let context = CURRENT.with(|current| current.borrow().clone());
+
+        // A poisoned count isn't worth failing a get over.
+        let Ok(mut counts) = self.counts.lock() else {
+            return;
+        };
+
+        let key = context.as_ref().map(|context| (context.runner.clone(), context.updater));
+        let counts = counts.entry(key).or_default();
+
+        counts.gets += 1;
+
+        if let Some(wait) = wait {
+            counts.contended += 1;
+            counts.wait += wait;
+            counts.max_wait = counts.max_wait.max(wait);
+        }
+
+        if let Some(context) = context {
+            if counts.last_tick != Some(context.tick) {
+                counts.last_tick = Some(context.tick);
+                counts.ticks += 1;
+                counts.gets_this_tick = 0;
+            }
+
+            counts.gets_this_tick += 1;
+            counts.max_gets_per_tick = counts.max_gets_per_tick.max(counts.gets_this_tick);
+        }
+    }
+
+    /// The type name of the container's data.
+    pub fn data_name(&self) -> &'static str {
+        self.data
+    }
+
+    /// The counts since the container was made or last reset.
+    pub fn report(&self) -> DataAccessReport {
+        let counts = self.counts.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        let mut updaters: Vec<UpdaterAccess> = counts.iter()
+            .map(|(key, counts)| UpdaterAccess {
+                runner: key.as_ref().map(|(runner, _)| runner.to_string()),
+                updater: key.as_ref().map(|(_, updater)| *updater),
+                gets: counts.gets,
+                contended: counts.contended,
+                ticks: counts.ticks,
+                max_gets_per_tick: counts.max_gets_per_tick,
+                wait: counts.wait,
+                max_wait: counts.max_wait,
+            })
+            .collect();
+
+        updaters.sort_by(|a, b| b.wait.cmp(&a.wait).then(b.gets.cmp(&a.gets)));
+
+        DataAccessReport {
+            data: self.data,
+            updaters,
+        }
+    }
+
+    pub fn reset(&self) {
+        self.counts.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clear();
+    }
+}
+
+/// One updater's gets on a container.
+#[derive(Clone, Debug)]
+pub struct UpdaterAccess {
+    /// None for gets made outside of any updater, like during setup.
+    pub runner: Option<String>,
+    pub updater: Option<&'static str>,
+    pub gets: u64,
+    /// Gets that had to wait for the lock.
+    pub contended: u64,
+    /// Ticks with at least one get.
+    pub ticks: u64,
+    pub max_gets_per_tick: u64,
+    /// Total time spent waiting for the lock.
+    pub wait: Duration,
+    pub max_wait: Duration,
+}
+
+impl UpdaterAccess {
+    pub fn gets_per_tick(&self) -> f64 {
+        if self.ticks == 0 {
+            return 0.0;
+        }
+
+        self.gets as f64 / self.ticks as f64
+    }
+}
+
+/// Every updater's gets on one container, the most waited on first.
+#[derive(Clone, Debug)]
+pub struct DataAccessReport {
+    pub data: &'static str,
+    pub updaters: Vec<UpdaterAccess>,
+}
+
+impl DataAccessReport {
+    pub fn gets(&self) -> u64 {
+        self.updaters.iter().map(|access| access.gets).sum()
+    }
+
+    pub fn wait(&self) -> Duration {
+        self.updaters.iter().map(|access| access.wait).sum()
+    }
+}
+
+/// The gets on every container in a Nucleus, the most waited on first, so
+/// contention hotspots come out on top.
+#[derive(Clone, Debug)]
+pub struct AccessReport {
+    pub datas: Vec<DataAccessReport>,
+}
+
+impl AccessReport {
+    pub fn new(mut datas: Vec<DataAccessReport>) -> Self {
+        datas.sort_by(|a, b| b.wait().cmp(&a.wait()).then(b.gets().cmp(&a.gets())));
+
+        Self {
+            datas,
+        }
+    }
+}
+
+impl fmt::Display for AccessReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for data in self.datas.iter().filter(|data| data.gets() > 0) {
+            writeln!(f, "{}: {} gets, waited {:?}", data.data, data.gets(), data.wait())?;
+
+            for access in data.updaters.iter() {
+                match (&access.runner, access.updater) {
+                    (Some(runner), Some(updater)) => write!(f, "    {} / {}", runner, updater)?,
+                    _ => write!(f, "    outside updaters")?,
+                }
+
+                writeln!(
+                    f,
+                    ": {} gets over {} ticks ({:.1} per tick, max {}), {} waited {:?} (max {:?})",
+                    access.gets,
+                    access.ticks,
+                    access.gets_per_tick(),
+                    access.max_gets_per_tick,
+                    access.contended,
+                    access.wait,
+                    access.max_wait,
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,119 @@
+use access_tracker::*;
+use ecs_error::EcsError;
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+#[test]
+fn test_access_counts() {
+    let tracker = AccessTracker::new("Data");
+
+    tracker.set_enabled(true);
+    let mutex = Mutex::new(0);
+    let runner: Arc<str> = Arc::from("main");
+
+    drop(tracker.lock(&mutex));
+
+    for tick in 0..3 {
+        with_access_context(&runner, tick, "Updater", || {
+            for _ in 0..=tick {
+                *tracker.lock(&mutex).unwrap() += 1;
+            }
+        });
+    }
+
+    let report = tracker.report();
+
+    assert_eq!(report.data, "Data");
+    assert_eq!(report.gets(), 7);
+
+    let updater = report.updaters.iter().find(|access| access.updater == Some("Updater")).unwrap();
+
+    assert_eq!(updater.runner.as_deref(), Some("main"));
+    assert_eq!(updater.gets, 6);
+    assert_eq!(updater.ticks, 3);
+    assert_eq!(updater.max_gets_per_tick, 3);
+    assert_eq!(updater.gets_per_tick(), 2.0);
+    assert_eq!(updater.contended, 0);
+
+    // Gets outside any updater are counted on their own.
+    let outside = report.updaters.iter().find(|access| access.updater.is_none()).unwrap();
+
+    assert_eq!(outside.gets, 1);
+    assert_eq!(outside.ticks, 0);
+
+    tracker.reset();
+
+    assert_eq!(tracker.report().gets(), 0);
+}
+
+#[test]
+fn test_access_wait() {
+    let tracker = AccessTracker::new("Data");
+
+    tracker.set_enabled(true);
+    let mutex = Arc::new(Mutex::new(0));
+    let (locked, wait_for_lock) = std::sync::mpsc::channel();
+
+    let thread_mutex = mutex.clone();
+    let thread = std::thread::spawn(move || {
+        let _guard = thread_mutex.lock().unwrap();
+
+        locked.send(()).unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+    });
+
+    wait_for_lock.recv().unwrap();
+    drop(tracker.lock(&mutex));
+    thread.join().unwrap();
+
+    let report = tracker.report();
+    let access = &report.updaters[0];
+
+    assert_eq!(access.contended, 1);
+    assert!(access.wait >= Duration::from_millis(10));
+    assert_eq!(access.max_wait, access.wait);
+
+    let report = AccessReport::new(vec![report]);
+
+    assert!(report.to_string().starts_with("Data: 1 gets"));
+}
+
+#[test]
+fn test_access_poisoned() {
+    let tracker = AccessTracker::new("Data");
+
+    tracker.set_enabled(true);
+    let mutex = Arc::new(Mutex::new(0));
+
+    let thread_mutex = mutex.clone();
+
+    std::thread::spawn(move || {
+        let _guard = thread_mutex.lock().unwrap();
+
+        panic!("poison the mutex");
+    }).join().unwrap_err();
+
+    assert!(matches!(tracker.lock(&mutex), Err(EcsError::Poisoned { data: "Data" })));
+    assert_eq!(tracker.report().gets(), 0);
+}
+
+#[test]
+fn test_access_disabled() {
+    let tracker = AccessTracker::new("Data");
+    let mutex = Mutex::new(0);
+
+    drop(tracker.lock(&mutex));
+
+    assert!(!tracker.is_enabled());
+    assert_eq!(tracker.report().gets(), 0);
+
+    // Clones share the switch.
+    tracker.clone().set_enabled(true);
+    drop(tracker.lock(&mutex));
+    tracker.set_enabled(false);
+    drop(tracker.lock(&mutex));
+
+    assert_eq!(tracker.report().gets(), 1);
+}
@@ -1,38 +1,106 @@
 use data_trait::DataTrait;
+use data_storage::DataStorage;
+use entity::Entity;
 use std::{
     sync::{Arc, Mutex, MutexGuard},
     any::Any,
-    collections::BTreeMap,
 };
 use as_any::AsAny;
+use access_tracker::AccessTracker;
+use ecs_error::EcsError;
 
-/// TODO(AddBTreeMapSettings)
-/// TODO(TrackGetsPerUpdate)
+/// Data for many entities, kept in T's DataTrait::Storage.
+///
 /// TODO(AddHashMapCacheForCommonEntities)
 pub struct DataSet<T: DataTrait> {
-    /// TODO(RealEntityId)
-    datas: Arc<Mutex<BTreeMap<usize, T>>>,
+    datas: Arc<Mutex<T::Storage>>,
+    tracker: AccessTracker,
 }
 
 impl<T> Clone for DataSet<T> where T: DataTrait {
     fn clone(&self) -> Self {
         Self {
-            datas: self.datas.clone()
+            datas: self.datas.clone(),
+            tracker: self.tracker.clone(),
         }
     }
 }
 
+impl<T> Default for DataSet<T> where T: DataTrait {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<T> DataSet<T> where T: DataTrait {
     pub fn new() -> Self {
         Self {
-            datas: Arc::new(Mutex::new(BTreeMap::new())),
+            datas: Arc::new(Mutex::new(T::Storage::default())),
+            tracker: AccessTracker::new(std::any::type_name::<T>()),
         }
     }
 
-    /// TODO(MakeFallible)
+    /// Fails if another thread panicked while holding the set.
+    ///
     /// TODO(MakeSingularlyMutable?)
-    pub fn get(&self) -> MutexGuard<BTreeMap<usize, T>> {
-        self.datas.lock().unwrap()
+    pub fn get(&self) -> Result<MutexGuard<T::Storage>, EcsError> {
+        self.tracker.lock(&self.datas)
+    }
+
+    /// Counts the set's gets, see [AccessTracker].
+    pub fn access_tracker(&self) -> &AccessTracker {
+        &self.tracker
+    }
+
+    /// Identifies the set's lock. Whenever several sets are held at once they
+    /// are locked in order of this key, so two threads never wait on each other.
+    pub fn lock_key(&self) -> usize {
+        Arc::as_ptr(&self.datas) as *const () as usize
+    }
+}
+
+/// The parts of a [DataSet] the Nucleus needs without knowing its data type.
+pub trait DataSetTrait: AsAny {
+    /// Drop the entity's data, if it has any in this set.
+    fn remove_entity(&self, entity: Entity) -> Result<(), EcsError>;
+
+    /// See [DataSet::lock_key].
+    fn lock_key(&self) -> usize;
+
+    /// The type name of the set's data.
+    fn data_name(&self) -> &'static str;
+
+    /// Lock the set, returning a check for whether an entity has data in it.
+    /// The set stays locked until the check is dropped.
+    fn lock_contains(&self) -> Result<Box<dyn Fn(Entity) -> bool + '_>, EcsError>;
+
+    /// Another handle to the same set.
+    fn clone_box(&self) -> Box<dyn DataSetTrait>;
+}
+
+impl<T> DataSetTrait for DataSet<T> where T: DataTrait {
+    fn remove_entity(&self, entity: Entity) -> Result<(), EcsError> {
+        self.get()?.remove(entity);
+
+        Ok(())
+    }
+
+    fn lock_key(&self) -> usize {
+        DataSet::lock_key(self)
+    }
+
+    fn data_name(&self) -> &'static str {
+        std::any::type_name::<T>()
+    }
+
+    fn lock_contains(&self) -> Result<Box<dyn Fn(Entity) -> bool + '_>, EcsError> {
+        let datas = self.get()?;
+
+        Ok(Box::new(move |entity| datas.contains(entity)))
+    }
+
+    fn clone_box(&self) -> Box<dyn DataSetTrait> {
+        Box::new(self.clone())
     }
 }
 
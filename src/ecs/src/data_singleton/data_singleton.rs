@@ -4,30 +4,38 @@ use std::{
     any::Any,
 };
 use as_any::AsAny;
+use access_tracker::AccessTracker;
+use ecs_error::EcsError;
 
 pub struct DataSingleton<T> where T: DataTrait {
-    /// TODO(TrackGetsPerUpdate)
-    /// +
     data: Arc<Mutex<T>>,
+    tracker: AccessTracker,
 }
 
 impl<T> DataSingleton<T> where T: DataTrait {
     pub fn new(data: T) -> Self {
         Self {
             data: Arc::new(Mutex::new(data)),
+            tracker: AccessTracker::new(std::any::type_name::<T>()),
         }
     }
 
-    /// TODO(MakeFallible)
-    pub fn get(&self) -> MutexGuard<T> {
-        self.data.lock().unwrap()
+    /// Fails if another thread panicked while holding the singleton.
+    pub fn get(&self) -> Result<MutexGuard<T>, EcsError> {
+        self.tracker.lock(&self.data)
+    }
+
+    /// Counts the singleton's gets, see [AccessTracker].
+    pub fn access_tracker(&self) -> &AccessTracker {
+        &self.tracker
     }
 }
 
 impl<T> Clone for DataSingleton<T> where T: DataTrait {
     fn clone(&self) -> Self {
         Self {
-            data: self.data.clone()
+            data: self.data.clone(),
+            tracker: self.tracker.clone(),
         }
     }
 }
@@ -0,0 +1,519 @@
+use entity::Entity;
+use std::collections::{BTreeMap, HashMap};
+
+/// How a DataSet keeps its data. Each data type picks one through
+/// DataTrait::Storage, trading iteration speed against lookups and churn.
+/// - [BTreeMapStorage]: sorted by entity, the default choice.
+/// - [HashMapStorage]: fastest random lookups, unordered iteration.
+/// - [SparseSetStorage]: data packed in a Vec, fastest iteration, removal moves the last entity.
+/// - [SoaStorage]: like the sparse set, but with each field in its own Vec. See [soa].
+///
+/// Ref and Mut are what gets handed out for an entity's data, plain references
+/// for everything but SoA.
+pub trait DataStorage<T>: Default + 'static {
+    type Ref<'a> where Self: 'a;
+    type Mut<'a> where Self: 'a;
+
+    /// Returns the entity's previous data. The sparse storages only have room
+    /// for one generation per index, so they ignore data for an entity older
+    /// than the one holding its index.
+    fn insert(&mut self, entity: Entity, data: T) -> Option<T>;
+    fn remove(&mut self, entity: Entity) -> Option<T>;
+    fn contains(&self, entity: Entity) -> bool;
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn get(&self, entity: Entity) -> Option<Self::Ref<'_>>;
+    fn get_mut(&mut self, entity: Entity) -> Option<Self::Mut<'_>>;
+    fn entities(&self) -> impl Iterator<Item = Entity> + '_;
+    fn iter(&self) -> impl Iterator<Item = (Entity, Self::Ref<'_>)>;
+    fn iter_mut(&mut self) -> impl Iterator<Item = (Entity, Self::Mut<'_>)>;
+}
+
+pub struct BTreeMapStorage<T> {
+    datas: BTreeMap<Entity, T>,
+}
+
+impl<T> Default for BTreeMapStorage<T> {
+    fn default() -> Self {
+        Self {
+            datas: BTreeMap::new(),
+        }
+    }
+}
+
+impl<T> DataStorage<T> for BTreeMapStorage<T> where T: 'static {
+    type Ref<'a> = &'a T;
+    type Mut<'a> = &'a mut T;
+
+    fn insert(&mut self, entity: Entity, data: T) -> Option<T> {
+        self.datas.insert(entity, data)
+    }
+
+    fn remove(&mut self, entity: Entity) -> Option<T> {
+        self.datas.remove(&entity)
+    }
+
+    fn contains(&self, entity: Entity) -> bool {
+        self.datas.contains_key(&entity)
+    }
+
+    fn len(&self) -> usize {
+        self.datas.len()
+    }
+
+    fn get(&self, entity: Entity) -> Option<&T> {
+        self.datas.get(&entity)
+    }
+
+    fn get_mut(&mut self, entity: Entity) -> Option<&mut T> {
+        self.datas.get_mut(&entity)
+    }
+
+    fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.datas.keys().copied()
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (Entity, &T)> {
+        self.datas.iter().map(|(entity, data)| (*entity, data))
+    }
+
+    fn iter_mut(&mut self) -> impl Iterator<Item = (Entity, &mut T)> {
+        self.datas.iter_mut().map(|(entity, data)| (*entity, data))
+    }
+}
+
+pub struct HashMapStorage<T> {
+    datas: HashMap<Entity, T>,
+}
+
+impl<T> Default for HashMapStorage<T> {
+    fn default() -> Self {
+        Self {
+            datas: HashMap::new(),
+        }
+    }
+}
+
+impl<T> DataStorage<T> for HashMapStorage<T> where T: 'static {
+    type Ref<'a> = &'a T;
+    type Mut<'a> = &'a mut T;
+
+    fn insert(&mut self, entity: Entity, data: T) -> Option<T> {
+        self.datas.insert(entity, data)
+    }
+
+    fn remove(&mut self, entity: Entity) -> Option<T> {
+        self.datas.remove(&entity)
+    }
+
+    fn contains(&self, entity: Entity) -> bool {
+        self.datas.contains_key(&entity)
+    }
+
+    fn len(&self) -> usize {
+        self.datas.len()
+    }
+
+    fn get(&self, entity: Entity) -> Option<&T> {
+        self.datas.get(&entity)
+    }
+
+    fn get_mut(&mut self, entity: Entity) -> Option<&mut T> {
+        self.datas.get_mut(&entity)
+    }
+
+    fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.datas.keys().copied()
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (Entity, &T)> {
+        self.datas.iter().map(|(entity, data)| (*entity, data))
+    }
+
+    fn iter_mut(&mut self) -> impl Iterator<Item = (Entity, &mut T)> {
+        self.datas.iter_mut().map(|(entity, data)| (*entity, data))
+    }
+}
+
+const EMPTY_SLOT: u32 = u32::MAX;
+
+/// Maps entities to their slot in densely packed arrays. The sparse side has
+/// a page per thread mask, indexed by the entity's index, so lookups are two
+/// array reads and a generation check.
+#[derive(Default)]
+pub struct SparseIndex {
+    pages: Vec<Vec<u32>>,
+    /// The entity in each slot.
+    entities: Vec<Entity>,
+}
+
+impl SparseIndex {
+    /// The slot holding the entity's data.
+    pub fn slot(&self, entity: Entity) -> Option<usize> {
+        self.occupied(entity).filter(|slot| self.entities[*slot] == entity)
+    }
+
+    /// The slot holding data for the entity's index, which may be from an
+    /// older generation that was never removed.
+    fn occupied(&self, entity: Entity) -> Option<usize> {
+        let slot = *self.pages.get(entity.thread_mask() as usize)?.get(entity.index() as usize)?;
+
+        if slot == EMPTY_SLOT {
+            None
+        } else {
+            Some(slot as usize)
+        }
+    }
+
+    /// Whether the entity is from an older generation than the one in the slot.
+    /// Generations wrap, so anything up to half the range behind counts as older.
+    fn older_than_occupant(&self, entity: Entity, slot: usize) -> bool {
+        let occupant = self.entities[slot].generation();
+
+        occupant.wrapping_sub(entity.generation()) as i16 > 0
+    }
+
+    fn set_slot(&mut self, entity: Entity, slot: u32) {
+        let thread_mask = entity.thread_mask() as usize;
+        let index = entity.index() as usize;
+
+        if self.pages.len() <= thread_mask {
+            self.pages.resize_with(thread_mask + 1, Vec::new);
+        }
+
+        let page = &mut self.pages[thread_mask];
+
+        if page.len() <= index {
+            page.resize(index + 1, EMPTY_SLOT);
+        }
+
+        page[index] = slot;
+    }
+
+    /// Give the entity the slot after the last one.
+    fn push(&mut self, entity: Entity) -> usize {
+        let slot = self.entities.len();
+
+        self.set_slot(entity, slot as u32);
+        self.entities.push(entity);
+
+        slot
+    }
+
+    /// Free the entity's slot by moving the last entity into it, the same way
+    /// the dense arrays have to be updated.
+    fn swap_remove(&mut self, entity: Entity) -> Option<usize> {
+        let slot = self.slot(entity)?;
+
+        self.set_slot(entity, EMPTY_SLOT);
+        self.entities.swap_remove(slot);
+
+        if let Some(moved) = self.entities.get(slot).copied() {
+            self.set_slot(moved, slot as u32);
+        }
+
+        Some(slot)
+    }
+
+    /// Entities in slot order.
+    pub fn entities(&self) -> &[Entity] {
+        &self.entities
+    }
+}
+
+pub struct SparseSetStorage<T> {
+    index: SparseIndex,
+    datas: Vec<T>,
+}
+
+impl<T> Default for SparseSetStorage<T> {
+    fn default() -> Self {
+        Self {
+            index: SparseIndex::default(),
+            datas: Vec::new(),
+        }
+    }
+}
+
+impl<T> SparseSetStorage<T> {
+    /// Data in slot order, lined up with [SparseIndex::entities].
+    pub fn datas(&self) -> &[T] {
+        &self.datas
+    }
+
+    pub fn datas_mut(&mut self) -> &mut [T] {
+        &mut self.datas
+    }
+
+    pub fn index(&self) -> &SparseIndex {
+        &self.index
+    }
+}
+
+impl<T> DataStorage<T> for SparseSetStorage<T> where T: 'static {
+    type Ref<'a> = &'a T;
+    type Mut<'a> = &'a mut T;
+
+    fn insert(&mut self, entity: Entity, data: T) -> Option<T> {
+        match self.index.occupied(entity) {
+            // A stale entity must not overwrite whatever got its index next.
+            Some(slot) if self.index.older_than_occupant(entity, slot) => None,
+            Some(slot) => {
+                // Replacing a despawned entity's data drops it rather than
+                // handing it back as this entity's.
+                let same_entity = self.index.entities[slot] == entity;
+                self.index.entities[slot] = entity;
+                let previous = std::mem::replace(&mut self.datas[slot], data);
+
+                same_entity.then_some(previous)
+            }
+            None => {
+                self.index.push(entity);
+                self.datas.push(data);
+
+                None
+            }
+        }
+    }
+
+    fn remove(&mut self, entity: Entity) -> Option<T> {
+        let slot = self.index.swap_remove(entity)?;
+
+        Some(self.datas.swap_remove(slot))
+    }
+
+    fn contains(&self, entity: Entity) -> bool {
+        self.index.slot(entity).is_some()
+    }
+
+    fn len(&self) -> usize {
+        self.datas.len()
+    }
+
+    fn get(&self, entity: Entity) -> Option<&T> {
+        Some(&self.datas[self.index.slot(entity)?])
+    }
+
+    fn get_mut(&mut self, entity: Entity) -> Option<&mut T> {
+        let slot = self.index.slot(entity)?;
+
+        Some(&mut self.datas[slot])
+    }
+
+    fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.index.entities().iter().copied()
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (Entity, &T)> {
+        self.index.entities().iter().copied().zip(self.datas.iter())
+    }
+
+    fn iter_mut(&mut self) -> impl Iterator<Item = (Entity, &mut T)> {
+        self.index.entities.iter().copied().zip(self.datas.iter_mut())
+    }
+}
+
+/// A data type that can be split into one Vec per field for [SoaStorage].
+/// Implemented by the [soa] macro.
+pub trait SoaTrait: Sized + 'static {
+    /// A Vec for each field, all the same length.
+    type Columns: Default;
+    /// A reference to each field of one entity's data.
+    type Ref<'a>;
+    type Mut<'a>;
+
+    fn push(columns: &mut Self::Columns, data: Self);
+    fn replace(columns: &mut Self::Columns, slot: usize, data: Self) -> Self;
+    fn swap_remove(columns: &mut Self::Columns, slot: usize) -> Self;
+    fn get(columns: &Self::Columns, slot: usize) -> Self::Ref<'_>;
+    fn get_mut(columns: &mut Self::Columns, slot: usize) -> Self::Mut<'_>;
+    fn iter(columns: &Self::Columns) -> impl Iterator<Item = Self::Ref<'_>>;
+    fn iter_mut(columns: &mut Self::Columns) -> impl Iterator<Item = Self::Mut<'_>>;
+}
+
+pub struct SoaStorage<T: SoaTrait> {
+    index: SparseIndex,
+    columns: T::Columns,
+}
+
+impl<T> Default for SoaStorage<T> where T: SoaTrait {
+    fn default() -> Self {
+        Self {
+            index: SparseIndex::default(),
+            columns: T::Columns::default(),
+        }
+    }
+}
+
+impl<T> SoaStorage<T> where T: SoaTrait {
+    /// The columns in slot order, lined up with [SparseIndex::entities], for
+    /// updaters that only touch a few fields.
+    pub fn columns(&self) -> &T::Columns {
+        &self.columns
+    }
+
+    /// Changing the length of a column breaks the storage, only write to them.
+    pub fn columns_mut(&mut self) -> &mut T::Columns {
+        &mut self.columns
+    }
+
+    pub fn index(&self) -> &SparseIndex {
+        &self.index
+    }
+}
+
+impl<T> DataStorage<T> for SoaStorage<T> where T: SoaTrait {
+    type Ref<'a> = T::Ref<'a>;
+    type Mut<'a> = T::Mut<'a>;
+
+    fn insert(&mut self, entity: Entity, data: T) -> Option<T> {
+        match self.index.occupied(entity) {
+            Some(slot) if self.index.older_than_occupant(entity, slot) => None,
+            Some(slot) => {
+                let same_entity = self.index.entities[slot] == entity;
+                self.index.entities[slot] = entity;
+                let previous = T::replace(&mut self.columns, slot, data);
+
+                same_entity.then_some(previous)
+            }
+            None => {
+                self.index.push(entity);
+                T::push(&mut self.columns, data);
+
+                None
+            }
+        }
+    }
+
+    fn remove(&mut self, entity: Entity) -> Option<T> {
+        let slot = self.index.swap_remove(entity)?;
+
+        Some(T::swap_remove(&mut self.columns, slot))
+    }
+
+    fn contains(&self, entity: Entity) -> bool {
+        self.index.slot(entity).is_some()
+    }
+
+    fn len(&self) -> usize {
+        self.index.entities().len()
+    }
+
+    fn get(&self, entity: Entity) -> Option<T::Ref<'_>> {
+        Some(T::get(&self.columns, self.index.slot(entity)?))
+    }
+
+    fn get_mut(&mut self, entity: Entity) -> Option<T::Mut<'_>> {
+        let slot = self.index.slot(entity)?;
+
+        Some(T::get_mut(&mut self.columns, slot))
+    }
+
+    fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.index.entities().iter().copied()
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (Entity, T::Ref<'_>)> {
+        self.index.entities().iter().copied().zip(T::iter(&self.columns))
+    }
+
+    fn iter_mut(&mut self) -> impl Iterator<Item = (Entity, T::Mut<'_>)> {
+        self.index.entities.iter().copied().zip(T::iter_mut(&mut self.columns))
+    }
+}
+
+/// Declare a struct along with the column, Ref and Mut types [SoaStorage]
+/// needs for it, and implement [SoaTrait].
+///
+/// soa! {
+///     #[derive(Debug)]
+///     pub struct Mover => MoverColumns, MoverRef, MoverMut {
+///         pub x: f32,
+///         pub vx: f32,
+///     }
+/// }
+///
+/// impl DataTrait for Mover {
+///     type Storage = SoaStorage<Self>;
+/// }
+#[macro_export]
+macro_rules! soa {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident => $columns:ident, $data_ref:ident, $data_mut:ident {
+            $($field_vis:vis $field:ident: $ty:ty),+ $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $($field_vis $field: $ty),+
+        }
+
+        #[derive(Default)]
+        $vis struct $columns {
+            $($field_vis $field: Vec<$ty>),+
+        }
+
+        $vis struct $data_ref<'a> {
+            $($field_vis $field: &'a $ty),+
+        }
+
+        $vis struct $data_mut<'a> {
+            $($field_vis $field: &'a mut $ty),+
+        }
+
+        impl $crate::SoaTrait for $name {
+            type Columns = $columns;
+            type Ref<'a> = $data_ref<'a>;
+            type Mut<'a> = $data_mut<'a>;
+
+            fn push(columns: &mut $columns, data: Self) {
+                $(columns.$field.push(data.$field);)+
+            }
+
+            fn replace(columns: &mut $columns, slot: usize, data: Self) -> Self {
+                Self {
+                    $($field: std::mem::replace(&mut columns.$field[slot], data.$field)),+
+                }
+            }
+
+            fn swap_remove(columns: &mut $columns, slot: usize) -> Self {
+                Self {
+                    $($field: columns.$field.swap_remove(slot)),+
+                }
+            }
+
+            fn get(columns: &$columns, slot: usize) -> $data_ref<'_> {
+                $data_ref {
+                    $($field: &columns.$field[slot]),+
+                }
+            }
+
+            fn get_mut(columns: &mut $columns, slot: usize) -> $data_mut<'_> {
+                $data_mut {
+                    $($field: &mut columns.$field[slot]),+
+                }
+            }
+
+            fn iter(columns: &$columns) -> impl Iterator<Item = $data_ref<'_>> {
+                $(let mut $field = columns.$field.iter();)+
+
+                std::iter::from_fn(move || Some($data_ref {
+                    $($field: $field.next()?),+
+                }))
+            }
+
+            fn iter_mut(columns: &mut $columns) -> impl Iterator<Item = $data_mut<'_>> {
+                $(let mut $field = columns.$field.iter_mut();)+
+
+                std::iter::from_fn(move || Some($data_mut {
+                    $($field: $field.next()?),+
+                }))
+            }
+        }
+    };
+}
@@ -0,0 +1,122 @@
+use data_storage::*;
+use entity::Entity;
+
+/// What every storage has to do the same way.
+fn check_storage<S: DataStorage<u32>>() {
+    let mut storage = S::default();
+    let entities: Vec<Entity> = (0..4).map(|index| Entity::new(0, index, 0)).collect();
+
+    for (value, entity) in entities.iter().enumerate() {
+        assert_eq!(storage.insert(*entity, value as u32), None);
+    }
+
+    assert_eq!(storage.len(), 4);
+    assert_eq!(storage.insert(entities[1], 10), Some(1));
+    assert_eq!(storage.remove(entities[0]), Some(0));
+    assert_eq!(storage.remove(entities[0]), None);
+    assert!(!storage.contains(entities[0]));
+    assert!(storage.contains(entities[3]));
+    assert_eq!(storage.len(), 3);
+
+    let mut remaining: Vec<Entity> = storage.entities().collect();
+
+    remaining.sort();
+
+    assert_eq!(remaining, entities[1..]);
+    assert_eq!(storage.iter().count(), 3);
+    assert!(storage.get(Entity::new(1, 3, 0)).is_none());
+
+    for entity in remaining {
+        storage.remove(entity);
+    }
+
+    assert!(storage.is_empty());
+}
+
+#[test]
+fn test_data_storages() {
+    check_storage::<BTreeMapStorage<u32>>();
+    check_storage::<HashMapStorage<u32>>();
+    check_storage::<SparseSetStorage<u32>>();
+}
+
+#[test]
+fn test_sparse_set_storage() {
+    let mut storage = SparseSetStorage::default();
+    let first = Entity::new(0, 0, 0);
+    let second = Entity::new(2, 5, 0);
+
+    storage.insert(first, 1);
+    storage.insert(second, 2);
+
+    // Removing moves the last entity into the freed slot.
+    storage.remove(first);
+
+    assert_eq!(storage.index().entities(), [second]);
+    assert_eq!(storage.datas(), [2]);
+    assert_eq!(storage.get(second), Some(&2));
+
+    // A newer generation at the same index replaces the stale data rather
+    // than handing it back.
+    let stale = Entity::new(2, 5, 0);
+    let reused = Entity::new(2, 5, 1);
+
+    assert_eq!(storage.insert(reused, 3), None);
+    assert!(!storage.contains(stale));
+    assert_eq!(storage.get(reused), Some(&3));
+    assert_eq!(storage.len(), 1);
+
+    // The stale entity coming back doesn't overwrite the live one.
+    assert_eq!(storage.insert(stale, 4), None);
+    assert!(!storage.contains(stale));
+    assert_eq!(storage.get(reused), Some(&3));
+
+    // Generations wrap, so the first one after the wrap is still newer.
+    let wrapped_from = Entity::new(1, 0, u16::MAX);
+    let wrapped = Entity::new(1, 0, 0);
+
+    storage.insert(wrapped_from, 5);
+    storage.insert(wrapped, 6);
+
+    assert_eq!(storage.get(wrapped), Some(&6));
+    assert_eq!(storage.insert(wrapped_from, 7), None);
+    assert_eq!(storage.get(wrapped), Some(&6));
+}
+
+soa! {
+    #[derive(Debug, PartialEq)]
+    pub struct Mover => MoverColumns, MoverRef, MoverMut {
+        pub x: f32,
+        pub vx: f32,
+    }
+}
+
+#[test]
+fn test_soa_storage() {
+    let mut storage = SoaStorage::<Mover>::default();
+    let first = Entity::new(0, 0, 0);
+    let second = Entity::new(0, 1, 0);
+
+    storage.insert(first, Mover { x: 0.0, vx: 1.0 });
+    storage.insert(second, Mover { x: 10.0, vx: -1.0 });
+
+    for (_, mover) in storage.iter_mut() {
+        *mover.x += *mover.vx;
+    }
+
+    assert_eq!(storage.columns().x, [1.0, 9.0]);
+    assert_eq!(storage.get(second).map(|mover| *mover.x), Some(9.0));
+    assert_eq!(storage.remove(first), Some(Mover { x: 1.0, vx: 1.0 }));
+    assert_eq!(storage.columns().vx, [-1.0]);
+    assert_eq!(storage.insert(second, Mover { x: 0.0, vx: 0.0 }), Some(Mover { x: 9.0, vx: -1.0 }));
+    assert_eq!(storage.len(), 1);
+
+    // An older generation at the same index is ignored.
+    let reused = Entity::new(0, 1, 1);
+
+    storage.insert(reused, Mover { x: 5.0, vx: 0.0 });
+
+    assert_eq!(storage.insert(second, Mover { x: 0.0, vx: 0.0 }), None);
+    assert_eq!(storage.get(reused).map(|mover| *mover.x), Some(5.0));
+    assert_eq!(storage.len(), 1);
+}
@@ -1 +1,8 @@
-pub trait DataTrait: Sized + 'static {}
\ No newline at end of file
+use data_storage::DataStorage;
+
+pub trait DataTrait: Sized + 'static {
+    /// Where the DataSet of this type keeps its data, see [DataStorage]. Types
+    /// that are only ever singletons still have to pick one, BTreeMapStorage
+    /// by convention.
+    type Storage: DataStorage<Self>;
+}
\ No newline at end of file
@@ -0,0 +1,99 @@
+use std::fmt;
+
+/// Everything that can go wrong setting up a Nucleus and its Runners. Data
+/// types and updaters are named by their type name.
+#[derive(Debug)]
+pub enum EcsError {
+    /// A DataSet of the type was already added.
+    DataSetExists { data: &'static str },
+    /// No DataSet of the type was added.
+    DataSetDoesNotExist { data: &'static str },
+    /// A DataSingleton of the type was already added.
+    DataSingletonExists { data: &'static str },
+    /// No DataSingleton of the type was added.
+    DataSingletonDoesNotExist { data: &'static str },
+    /// A ReadOnlySet of the type was already added.
+    ReadOnlySetExists { data: &'static str },
+    /// No ReadOnlySet of the type was added.
+    ReadOnlySetDoesNotExist { data: &'static str },
+    /// A ReadOnlySingleton of the type was already added.
+    ReadOnlySingletonExists { data: &'static str },
+    /// No ReadOnlySingleton of the type was added.
+    ReadOnlySingletonDoesNotExist { data: &'static str },
+    /// A thread panicked while holding the lock.
+    Poisoned { data: &'static str },
+    /// A Query named the same DataSet more than once, which would lock it twice.
+    DuplicateQuerySet { data: &'static str },
+    /// UpdaterTrait::new or UpdaterTrait::register failed.
+    Updater { updater: &'static str, error: Box<EcsError> },
+    /// The Runner already has an updater of the type.
+    UpdaterExists { updater: &'static str },
+    /// The Runner doesn't have an updater of the type.
+    UpdaterDoesNotExist { updater: String },
+    /// The updaters' after and before can't all be satisfied. Names the
+    /// updaters that are left waiting on each other.
+    UpdaterCycle { updaters: Vec<&'static str> },
+    /// An UpdateRate::Hz that isn't a positive, finite number with a period
+    /// that fits in an Instant, or an UpdateRate::EveryTicks(0).
+    InvalidUpdateRate { updater: &'static str, rate: String },
+    /// The Nucleus already has a runner with the name.
+    RunnerExists { runner: String },
+    /// The Nucleus doesn't have a runner with the name.
+    RunnerDoesNotExist { runner: String },
+    /// The runner's thread couldn't be spawned.
+    SpawnRunner { runner: String, error: std::io::ErrorKind },
+    /// The runner's thread panicked.
+    RunnerPanicked { runner: String, message: String },
+    /// The mods directory couldn't be read.
+    ModDirectory { path: String, error: std::io::ErrorKind },
+    /// The shared object couldn't be loaded, or is missing a mod symbol.
+    ModLoad { path: String, error: String },
+    /// The mod was built against a different version of the mod ABI.
+    ModAbiVersion { path: String, version: u32, expected: u32 },
+    /// A mod with the name was already loaded.
+    ModExists { name: String },
+    /// No mod with the name is loaded.
+    ModDoesNotExist { name: String },
+    /// The mod's load failed.
+    Mod { name: String, error: Box<EcsError> },
+    /// The runner isn't the Runner type the mod was built against.
+    ModRunnerType { runner: String },
+    /// The runner didn't drop the mod's updaters in time to unload it.
+    ModUnload { name: String, runner: String },
+}
+
+impl fmt::Display for EcsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EcsError::DataSetExists { data } => write!(f, "a DataSet<{}> was already added", data),
+            EcsError::DataSetDoesNotExist { data } => write!(f, "no DataSet<{}> was added", data),
+            EcsError::DataSingletonExists { data } => write!(f, "a DataSingleton<{}> was already added", data),
+            EcsError::DataSingletonDoesNotExist { data } => write!(f, "no DataSingleton<{}> was added", data),
+            EcsError::ReadOnlySetExists { data } => write!(f, "a ReadOnlySet<{}> was already added", data),
+            EcsError::ReadOnlySetDoesNotExist { data } => write!(f, "no ReadOnlySet<{}> was added", data),
+            EcsError::ReadOnlySingletonExists { data } => write!(f, "a ReadOnlySingleton<{}> was already added", data),
+            EcsError::ReadOnlySingletonDoesNotExist { data } => write!(f, "no ReadOnlySingleton<{}> was added", data),
+            EcsError::Poisoned { data } => write!(f, "{} was poisoned by a panic", data),
+            EcsError::DuplicateQuerySet { data } => write!(f, "DataSet<{}> is in the query more than once", data),
+            EcsError::Updater { updater, error } => write!(f, "{}: {}", updater, error),
+            EcsError::UpdaterExists { updater } => write!(f, "{} was already added to the runner", updater),
+            EcsError::UpdaterDoesNotExist { updater } => write!(f, "{} isn't in the runner", updater),
+            EcsError::UpdaterCycle { updaters } => write!(f, "updaters have to run before each other: {}", updaters.join(", ")),
+            EcsError::InvalidUpdateRate { updater, rate } => write!(f, "{} can't update at {}", updater, rate),
+            EcsError::RunnerExists { runner } => write!(f, "a runner named {} was already added", runner),
+            EcsError::RunnerDoesNotExist { runner } => write!(f, "no runner named {} was added", runner),
+            EcsError::SpawnRunner { runner, error } => write!(f, "couldn't spawn a thread for runner {}: {}", runner, error),
+            EcsError::RunnerPanicked { runner, message } => write!(f, "runner {} panicked: {}", runner, message),
+            EcsError::ModDirectory { path, error } => write!(f, "couldn't read mods directory {}: {}", path, error),
+            EcsError::ModLoad { path, error } => write!(f, "couldn't load mod {}: {}", path, error),
+            EcsError::ModAbiVersion { path, version, expected } => write!(f, "mod {} was built for mod ABI version {}, not {}", path, version, expected),
+            EcsError::ModExists { name } => write!(f, "a mod named {} was already loaded", name),
+            EcsError::ModDoesNotExist { name } => write!(f, "no mod named {} is loaded", name),
+            EcsError::Mod { name, error } => write!(f, "mod {}: {}", name, error),
+            EcsError::ModRunnerType { runner } => write!(f, "runner {} isn't the Runner the mod was built against", runner),
+            EcsError::ModUnload { name, runner } => write!(f, "runner {} didn't drop the updaters of mod {} in time", runner, name),
+        }
+    }
+}
+
+impl std::error::Error for EcsError {}
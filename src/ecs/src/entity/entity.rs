@@ -0,0 +1,137 @@
+use std::fmt;
+
+/// Entity ids are split into three parts, from the top bits down:
+/// - 16 bits of thread mask, so each thread hands out ids without asking the others.
+/// - 32 bits of index, reused once the entity at that index is despawned.
+/// - 16 bits of generation, bumped every time the index is reused.
+const INDEX_BITS: u32 = 32;
+const GENERATION_BITS: u32 = 16;
+
+/// An id for a thing that has data in DataSets. Two entities are only equal
+/// if their generation matches too, so a stale Entity kept around after a
+/// despawn never points at whatever got its index next.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Entity {
+    id: u64,
+}
+
+impl Entity {
+    pub fn new(thread_mask: u16, index: u32, generation: u16) -> Self {
+        Self {
+            id: ((thread_mask as u64) << (INDEX_BITS + GENERATION_BITS))
+                | ((index as u64) << GENERATION_BITS)
+                | generation as u64,
+        }
+    }
+
+    /// The mask of the [EntityAllocator] that made this entity.
+    pub fn thread_mask(&self) -> u16 {
+        (self.id >> (INDEX_BITS + GENERATION_BITS)) as u16
+    }
+
+    pub fn index(&self) -> u32 {
+        (self.id >> GENERATION_BITS) as u32
+    }
+
+    pub fn generation(&self) -> u16 {
+        self.id as u16
+    }
+
+    /// The raw id, for sending the entity somewhere that doesn't know the type.
+    pub fn to_bits(&self) -> u64 {
+        self.id
+    }
+
+    pub fn from_bits(id: u64) -> Self {
+        Self { id }
+    }
+}
+
+impl fmt::Debug for Entity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Entity({}:{}v{})", self.thread_mask(), self.index(), self.generation())
+    }
+}
+
+impl fmt::Display for Entity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}v{}", self.thread_mask(), self.index(), self.generation())
+    }
+}
+
+/// Hands out [Entity] ids for a single thread mask. Despawned indices are
+/// reused with the next generation.
+///
+/// The generation wraps after 65536 reuses of the same index, so a stale
+/// entity kept around for that long would look alive again.
+pub struct EntityAllocator {
+    thread_mask: u16,
+    /// The current generation for each index.
+    generations: Vec<u16>,
+    alive: Vec<bool>,
+    free: Vec<u32>,
+}
+
+impl EntityAllocator {
+    pub fn new(thread_mask: u16) -> Self {
+        Self {
+            thread_mask,
+            generations: Vec::new(),
+            alive: Vec::new(),
+            free: Vec::new(),
+        }
+    }
+
+    pub fn thread_mask(&self) -> u16 {
+        self.thread_mask
+    }
+
+    pub fn allocate(&mut self) -> Entity {
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                self.generations.push(0);
+                self.alive.push(false);
+                (self.generations.len() - 1) as u32
+            }
+        };
+
+        self.alive[index as usize] = true;
+
+        Entity::new(self.thread_mask, index, self.generations[index as usize])
+    }
+
+    /// Free the entity's index for reuse. Returns false if the entity was
+    /// already despawned or was made by another allocator.
+    pub fn free(&mut self, entity: Entity) -> bool {
+        if !self.is_alive(entity) {
+            return false;
+        }
+
+        let index = entity.index() as usize;
+
+        self.alive[index] = false;
+        self.generations[index] = self.generations[index].wrapping_add(1);
+        self.free.push(entity.index());
+
+        true
+    }
+
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        let index = entity.index() as usize;
+
+        entity.thread_mask() == self.thread_mask
+            && index < self.alive.len()
+            && self.alive[index]
+            && self.generations[index] == entity.generation()
+    }
+
+    /// How many entities are alive.
+    pub fn len(&self) -> usize {
+        self.alive.len() - self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
@@ -0,0 +1,42 @@
+use entity::*;
+
+#[test]
+fn test_entity_bits() {
+    let entity = Entity::new(3, 70000, 9);
+
+    assert_eq!(entity.thread_mask(), 3);
+    assert_eq!(entity.index(), 70000);
+    assert_eq!(entity.generation(), 9);
+    assert_eq!(Entity::from_bits(entity.to_bits()), entity);
+}
+
+#[test]
+fn test_entity_allocator_reuse() {
+    let mut allocator = EntityAllocator::new(1);
+
+    let first = allocator.allocate();
+    let second = allocator.allocate();
+
+    assert_eq!(allocator.len(), 2);
+    assert!(allocator.free(first));
+    assert!(!allocator.free(first));
+    assert!(!allocator.is_alive(first));
+
+    // The freed index comes back with the next generation.
+    let reused = allocator.allocate();
+
+    assert_eq!(reused.index(), first.index());
+    assert_eq!(reused.generation(), first.generation() + 1);
+    assert_ne!(reused, first);
+    assert!(allocator.is_alive(reused));
+    assert!(allocator.is_alive(second));
+    assert_eq!(allocator.len(), 2);
+
+    // Entities from another allocator are never alive here.
+    assert!(!allocator.is_alive(Entity::new(2, second.index(), second.generation())));
+    assert!(!allocator.free(Entity::new(2, second.index(), second.generation())));
+
+    assert!(allocator.free(reused));
+    assert!(allocator.free(second));
+    assert!(allocator.is_empty());
+}
@@ -1,11 +1,60 @@
 use nucleus::Nucleus;
-use std::time::Duration;
+use ecs_error::EcsError;
+use std::any::TypeId;
+
+/// Identifies an updater type within a Runner.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct UpdaterId {
+    type_id: TypeId,
+    name: &'static str,
+}
+
+impl UpdaterId {
+    pub fn of<T: UpdaterTrait>() -> Self {
+        Self {
+            type_id: TypeId::of::<T>(),
+            name: std::any::type_name::<T>(),
+        }
+    }
+
+    /// The updater's type name.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+/// How often a Runner calls an updater's update.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum UpdateRate {
+    EveryTick,
+    /// Once every n runner ticks, starting with the first.
+    EveryTicks(u32),
+    /// At most this many times a second. The update happens on the first
+    /// runner tick after it's due, so the rate can't be faster than the runner.
+    Hz(f64),
+}
 
 pub trait UpdaterTrait: 'static {
-    fn register(nucleus: Nucleus) where Self: Sized;
-    /// TODO(MakeFallible)
-    fn new(nucleus: Nucleus) -> Self where Self: Sized;
+    /// Add the data this updater owns to the nucleus.
+    fn register(nucleus: Nucleus) -> Result<(), EcsError> where Self: Sized;
+    fn new(nucleus: Nucleus) -> Result<Self, EcsError> where Self: Sized;
     /// This must be implemented in some way, otherwise why are you
     /// even using a function in the first place?
     fn update(&self);
-}
+
+    /// Updaters that have to update before this one each tick. Ones that
+    /// aren't in the same Runner are ignored.
+    fn after() -> Vec<UpdaterId> where Self: Sized {
+        Vec::new()
+    }
+
+    /// Updaters that have to update after this one each tick. Ones that
+    /// aren't in the same Runner are ignored.
+    fn before() -> Vec<UpdaterId> where Self: Sized {
+        Vec::new()
+    }
+
+    fn rate() -> UpdateRate where Self: Sized {
+        UpdateRate::EveryTick
+    }
+}
\ No newline at end of file
@@ -9,15 +9,20 @@
 
 // TODO
 // 1. tree manipulations should be fallible since those fail at the start
-// 2. get threading working
-// 3. express runner completion dependencies via Data data, but make it simpler to do.
+// 2. express runner completion dependencies via Data data, but make it simpler to do.
 
 pub use nucleus::*;
 pub use runner::*;
 pub use function::*;
 pub use data_set::*;
+pub use data_storage::*;
 pub use data_singleton::*;
+pub use read_only::*;
+pub use access_tracker::*;
+pub use mods::*;
 pub use data_trait::*;
+pub use entity::*;
+pub use ecs_error::*;
 
 // fn main() {
 //     let root_data = Arc::new(Mutex::new(RootData {
@@ -0,0 +1,353 @@
+use std::{
+    any::{Any, TypeId},
+    path::{Path, PathBuf},
+    sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender},
+    time::{Duration, Instant},
+};
+
+use libloading::Library;
+
+use nucleus::{AddedData, Nucleus};
+use runner::Runner;
+use function::{UpdaterId, UpdaterTrait};
+use ecs_error::EcsError;
+
+/// Bumped whenever [ModDeclaration] or [ModContext] change, so mods built
+/// against an older connors_ecs fail to load rather than crash.
+pub const MOD_ABI_VERSION: u32 = 2;
+
+/// The u32 a mod exports with its [MOD_ABI_VERSION], see [declare_mod].
+pub const MOD_ABI_VERSION_SYMBOL: &[u8] = b"CONNORS_ECS_MOD_ABI_VERSION\0";
+
+/// The [ModDeclaration] a mod exports, see [declare_mod].
+pub const MOD_DECLARATION_SYMBOL: &[u8] = b"CONNORS_ECS_MOD\0";
+
+/// What a mod exports for the [ModLoader].
+pub struct ModDeclaration {
+    pub name: &'static str,
+    /// Add the mod's data and updaters.
+    pub load: fn(&mut ModContext) -> Result<(), EcsError>,
+    /// Called as the mod unloads, after its updaters are out of their
+    /// runners and before its data is removed from the Nucleus.
+    pub unload: fn(&Nucleus),
+}
+
+/// Export a mod from a shared library.
+///
+/// connors_ecs::declare_mod!("hog_counter", load);
+/// connors_ecs::declare_mod!("hog_counter", load, unload);
+#[macro_export]
+macro_rules! declare_mod {
+    ($name:expr, $load:expr) => {
+        $crate::declare_mod!($name, $load, |_| {});
+    };
+    ($name:expr, $load:expr, $unload:expr) => {
+        #[no_mangle]
+        pub static CONNORS_ECS_MOD_ABI_VERSION: u32 = $crate::MOD_ABI_VERSION;
+
+        #[no_mangle]
+        pub static CONNORS_ECS_MOD: $crate::ModDeclaration = $crate::ModDeclaration {
+            name: $name,
+            load: $load,
+            unload: $unload,
+        };
+    };
+}
+
+/// Given to a mod's load, to add its data and updaters.
+pub struct ModContext {
+    /// Logs the data added through it, see [Nucleus::logging_added].
+    nucleus: Nucleus,
+    updaters: Vec<(String, &'static str)>,
+    errors: Sender<EcsError>,
+}
+
+impl ModContext {
+    /// Data added through this Nucleus, or by the registers of updaters added
+    /// with [ModContext::add_updater], is the mod's, and is removed when it
+    /// unloads. Data other threads add meanwhile isn't.
+    pub fn nucleus(&self) -> &Nucleus {
+        &self.nucleus
+    }
+
+    /// Register the updater, then add it to the named runner at the start of
+    /// the runner's next tick. Errors from the runner adding it come back
+    /// through [ModLoader::errors].
+    pub fn add_updater<T: UpdaterTrait>(&mut self, runner: &str) -> Result<(), EcsError> {
+        T::register(self.nucleus.clone()).map_err(|error| EcsError::Updater {
+            updater: std::any::type_name::<T>(),
+            error: Box::new(error),
+        })?;
+
+        let runner_name = runner.to_string();
+        let errors = self.errors.clone();
+
+        self.nucleus.send_runner_job(runner, Box::new(move |runner: &mut dyn Any| {
+            let result = match runner.downcast_mut::<Runner>() {
+                Some(runner) => runner.add_updater::<T>(),
+                None => Err(EcsError::ModRunnerType { runner: runner_name }),
+            };
+
+            if let Err(error) = result {
+                let _ = errors.send(error);
+            }
+        }))?;
+
+        self.updaters.push((runner.to_string(), UpdaterId::of::<T>().name()));
+
+        Ok(())
+    }
+}
+
+/// A loaded mod. Its library is last so it's unloaded after everything
+/// else here is dropped.
+struct LoadedMod {
+    name: String,
+    updaters: Vec<(String, &'static str)>,
+    data_types: Vec<TypeId>,
+    errors: Receiver<EcsError>,
+    unload: fn(&Nucleus),
+    library: Library,
+}
+
+/// Loads mods from shared objects into a Nucleus, and unloads them.
+///
+/// Rust has no stable ABI, so mods have to be built with the same compiler
+/// and connors_ecs sources as the program loading them. [MOD_ABI_VERSION]
+/// catches mods built against an older connors_ecs, but nothing catches a
+/// different compiler.
+///
+/// A mod links its own copy of connors_ecs, so the gets its updaters make
+/// are counted outside of any updater in [Nucleus::access_report].
+pub struct ModLoader {
+    nucleus: Nucleus,
+    /// In the order they were loaded.
+    mods: Vec<LoadedMod>,
+}
+
+impl ModLoader {
+    pub fn new(nucleus: Nucleus) -> Self {
+        Self {
+            nucleus,
+            mods: Vec::new(),
+        }
+    }
+
+    /// Load every shared object in the directory, in order of file name.
+    /// Stops at the first mod that fails, leaving the ones before it loaded.
+    /// Returns the names of the mods loaded.
+    ///
+    /// # Safety
+    ///
+    /// See [ModLoader::load].
+    pub unsafe fn load_dir(&mut self, directory: impl AsRef<Path>) -> Result<Vec<String>, EcsError> {
+        let directory = directory.as_ref();
+
+        let entries = std::fs::read_dir(directory).map_err(|error| EcsError::ModDirectory {
+            path: directory.display().to_string(),
+            error: error.kind(),
+        })?;
+
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == std::env::consts::DLL_EXTENSION))
+            .collect();
+
+        paths.sort();
+
+        paths.iter().map(|path| unsafe { self.load(path) }).collect()
+    }
+
+    /// Load the shared object and call its mod's load. Returns the mod's
+    /// name.
+    ///
+    /// A mod that fails to load has its updaters and data removed, but its
+    /// library is never unloaded, since runners may not have dropped its
+    /// updaters yet.
+    ///
+    /// # Safety
+    ///
+    /// Loading a shared object runs its initialisers, and calling into it
+    /// trusts that it was built with [declare_mod], the same compiler, and
+    /// the same connors_ecs.
+    pub unsafe fn load(&mut self, path: impl AsRef<Path>) -> Result<String, EcsError> {
+        let path = path.as_ref();
+        let load_error = |error: libloading::Error| EcsError::ModLoad {
+            path: path.display().to_string(),
+            error: error.to_string(),
+        };
+
+        let library = unsafe { Library::new(path) }.map_err(load_error)?;
+
+        let version = unsafe { **library.get::<*const u32>(MOD_ABI_VERSION_SYMBOL).map_err(load_error)? };
+
+        if version != MOD_ABI_VERSION {
+            return Err(EcsError::ModAbiVersion {
+                path: path.display().to_string(),
+                version,
+                expected: MOD_ABI_VERSION,
+            });
+        }
+
+        let declaration = unsafe { &**library.get::<*const ModDeclaration>(MOD_DECLARATION_SYMBOL).map_err(load_error)? };
+        let (name, load, unload) = (declaration.name.to_string(), declaration.load, declaration.unload);
+
+        if self.mods.iter().any(|loaded| loaded.name == name) {
+            return Err(EcsError::ModExists { name });
+        }
+
+        let added = AddedData::default();
+        let (errors_sender, errors) = channel();
+
+        let mut context = ModContext {
+            nucleus: self.nucleus.logging_added(added.clone()),
+            updaters: Vec::new(),
+            errors: errors_sender,
+        };
+
+        let result = load(&mut context);
+
+        // The library can't be dropped from here on, the runners may already
+        // have the mod's updaters.
+        let mut data_types = added.lock().map(|added| added.clone()).unwrap_or_default();
+
+        data_types.sort();
+        data_types.dedup();
+
+        let loaded = LoadedMod {
+            name: name.clone(),
+            updaters: context.updaters,
+            data_types,
+            errors,
+            unload,
+            library,
+        };
+
+        if let Err(error) = result {
+            // Don't wait on the runners, they may be on this thread.
+            let _ = self.remove_updaters(&loaded, None);
+            self.remove_data(&loaded);
+            std::mem::forget(loaded.library);
+
+            return Err(EcsError::Mod { name, error: Box::new(error) });
+        }
+
+        self.mods.push(loaded);
+
+        Ok(name)
+    }
+
+    /// Names of the loaded mods, in the order they were loaded.
+    pub fn names(&self) -> Vec<&str> {
+        self.mods.iter().map(|loaded| loaded.name.as_str()).collect()
+    }
+
+    /// Errors from runners adding the mod's updaters since the last call.
+    pub fn errors(&self, name: &str) -> Result<Vec<EcsError>, EcsError> {
+        let loaded = self.mods.iter()
+            .find(|loaded| loaded.name == name)
+            .ok_or_else(|| EcsError::ModDoesNotExist { name: name.to_string() })?;
+
+        Ok(loaded.errors.try_iter().collect())
+    }
+
+    /// Remove the mod's updaters from their runners, waiting up to timeout
+    /// for the runners to drop them, then its data, then unload it.
+    ///
+    /// Runners only drop updaters at the start of a tick, so a runner on
+    /// this thread has to be dropped first, or the wait times out and the
+    /// mod stays loaded.
+    ///
+    /// # Safety
+    ///
+    /// Unloading unmaps the mod's code, so nothing the mod made can still be
+    /// around: no clones of its data sets and singletons, no values of its
+    /// types in other data, and no boxes or closures it handed out. Using or
+    /// dropping any of them afterwards calls into unmapped code.
+    pub unsafe fn unload(&mut self, name: &str, timeout: Duration) -> Result<(), EcsError> {
+        let index = self.mods.iter()
+            .position(|loaded| loaded.name == name)
+            .ok_or_else(|| EcsError::ModDoesNotExist { name: name.to_string() })?;
+
+        self.remove_updaters(&self.mods[index], Some(Instant::now() + timeout))?;
+
+        let loaded = self.mods.remove(index);
+
+        (loaded.unload)(&self.nucleus);
+        self.remove_data(&loaded);
+
+        Ok(())
+    }
+
+    /// Unload every mod, the last loaded first. Returns what each mod that
+    /// couldn't be unloaded failed with.
+    ///
+    /// # Safety
+    ///
+    /// See [ModLoader::unload].
+    pub unsafe fn unload_all(&mut self, timeout: Duration) -> Vec<EcsError> {
+        let names: Vec<String> = self.mods.iter().rev().map(|loaded| loaded.name.clone()).collect();
+
+        names.iter()
+            .filter_map(|name| unsafe { self.unload(name, timeout) }.err())
+            .collect()
+    }
+
+    /// Send each of the mod's runners a job dropping its updaters, waiting
+    /// until the deadline for them to run if there is one.
+    fn remove_updaters(&self, loaded: &LoadedMod, deadline: Option<Instant>) -> Result<(), EcsError> {
+        let mut waiting = Vec::new();
+
+        for (runner, updater) in loaded.updaters.iter() {
+            let (done, removed) = channel();
+            let updater = *updater;
+
+            let job = Box::new(move |runner: &mut dyn Any| {
+                if let Some(runner) = runner.downcast_mut::<Runner>() {
+                    // Never added, if the runner couldn't add it.
+                    let _ = runner.remove_updater(updater);
+                }
+
+                let _ = done.send(());
+            });
+
+            match self.nucleus.send_runner_job(runner, job) {
+                Ok(()) => waiting.push((runner, removed)),
+                // The runner was dropped, and its updaters with it.
+                Err(EcsError::RunnerDoesNotExist { .. }) => {}
+                Err(error) => return Err(error),
+            }
+        }
+
+        let Some(deadline) = deadline else {
+            return Ok(());
+        };
+
+        for (runner, removed) in waiting {
+            // Disconnected means the runner was dropped before running the job.
+            if let Err(RecvTimeoutError::Timeout) = removed.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                return Err(EcsError::ModUnload { name: loaded.name.clone(), runner: runner.clone() });
+            }
+        }
+
+        Ok(())
+    }
+
+    fn remove_data(&self, loaded: &LoadedMod) {
+        for type_id in loaded.data_types.iter() {
+            // Only fails if the Nucleus is poisoned, which unloading can't fix.
+            let _ = self.nucleus.remove_data_type(*type_id);
+        }
+    }
+}
+
+impl Drop for ModLoader {
+    /// Mods still loaded are left mapped rather than unloaded, since runners
+    /// may still be updating their updaters. See [ModLoader::unload_all].
+    fn drop(&mut self) {
+        for loaded in self.mods.drain(..) {
+            std::mem::forget(loaded.library);
+        }
+    }
+}
@@ -0,0 +1,4 @@
+//! A mod built against an older connors_ecs, for test_mods.
+
+#[no_mangle]
+pub static CONNORS_ECS_MOD_ABI_VERSION: u32 = 1;
@@ -0,0 +1,46 @@
+//! A mod for test_mods. Adds a data set itself and a singleton through its
+//! updater's register.
+use connors_ecs::*;
+
+pub struct ModData;
+
+impl DataTrait for ModData {
+    type Storage = BTreeMapStorage<Self>;
+}
+
+pub struct ModCount(u64);
+
+impl DataTrait for ModCount {
+    type Storage = BTreeMapStorage<Self>;
+}
+
+pub struct CountUpdater {
+    count: DataSingleton<ModCount>,
+}
+
+impl UpdaterTrait for CountUpdater {
+    fn register(nucleus: Nucleus) -> Result<(), EcsError> {
+        nucleus.add_data_singleton(ModCount(0))?;
+
+        Ok(())
+    }
+
+    fn new(nucleus: Nucleus) -> Result<Self, EcsError> {
+        Ok(Self {
+            count: nucleus.get_data_singleton::<ModCount>()?,
+        })
+    }
+
+    fn update(&self) {
+        if let Ok(mut count) = self.count.get() {
+            count.0 += 1;
+        }
+    }
+}
+
+fn load(context: &mut ModContext) -> Result<(), EcsError> {
+    context.nucleus().add_data_set::<ModData>()?;
+    context.add_updater::<CountUpdater>("main")
+}
+
+declare_mod!("test_mod", load);
@@ -0,0 +1,65 @@
+use ecs_error::EcsError;
+use mods::*;
+use nucleus::Nucleus;
+use runner::Runner;
+use std::time::{Duration, Instant};
+
+fn library(name: &str) -> String {
+    std::env::var(name).unwrap()
+}
+
+#[test]
+fn test_mods_abi_version() {
+    let mut loader = ModLoader::new(Nucleus::new("test"));
+
+    match unsafe { loader.load(library("OLD_ABI_MOD")) } {
+        Err(EcsError::ModAbiVersion { version, expected, .. }) => {
+            assert_eq!(version, 1);
+            assert_eq!(expected, MOD_ABI_VERSION);
+        }
+        result => panic!("expected an ABI version error, got {:?}", result),
+    }
+
+    assert!(matches!(unsafe { loader.load("missing.so") }, Err(EcsError::ModLoad { .. })));
+    assert!(matches!(unsafe { loader.load_dir("missing") }, Err(EcsError::ModDirectory { .. })));
+    assert!(loader.names().is_empty());
+}
+
+#[test]
+fn test_mods_load_unload() -> Result<(), EcsError> {
+    let nucleus = Nucleus::new("test");
+
+    Runner::spawn(&nucleus, "main", |runner| {
+        runner.set_timestep(Some(Duration::from_millis(1)));
+
+        Ok(())
+    })?;
+
+    let data_before = nucleus.data_type_ids()?;
+    let mut loader = ModLoader::new(nucleus.clone());
+
+    assert_eq!(unsafe { loader.load(library("TEST_MOD")) }?, "test_mod");
+    assert!(matches!(unsafe { loader.load(library("TEST_MOD")) }, Err(EcsError::ModExists { .. })));
+    assert_eq!(loader.names(), ["test_mod"]);
+
+    // The runner adds the mod's updater at the start of its next tick.
+    let deadline = Instant::now() + Duration::from_secs(1);
+
+    while nucleus.updater_names("main")?.is_empty() && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(1));
+    }
+
+    assert_eq!(nucleus.updater_names("main")?.len(), 1);
+    assert!(loader.errors("test_mod")?.is_empty());
+    assert_eq!(nucleus.data_type_ids()?.len(), data_before.len() + 2);
+
+    // Nothing here holds onto the mod's data.
+    unsafe { loader.unload("test_mod", Duration::from_secs(1)) }?;
+
+    assert!(nucleus.updater_names("main")?.is_empty());
+    assert_eq!(nucleus.data_type_ids()?, data_before);
+    assert!(loader.names().is_empty());
+    assert!(nucleus.shutdown().is_empty());
+
+    Ok(())
+}
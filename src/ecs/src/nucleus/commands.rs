@@ -0,0 +1,354 @@
+use std::{
+    any::Any,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread::{JoinHandle, Thread},
+    time::Instant,
+    any::type_name,
+};
+
+use ecs_error::EcsError;
+
+use crate::{lock_named, Nucleus};
+
+/// Sent through the [Nucleus] to a Runner, which applies it at the start of
+/// its next tick.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum UpdaterCommand {
+    Enable,
+    /// Skip the updater's updates until it's enabled again.
+    Disable,
+    /// Drop the updater.
+    Remove,
+}
+
+/// Work sent through the [Nucleus] to run on a Runner's thread at the start
+/// of its next tick, before its commands. The Nucleus can't name the Runner
+/// type, so the job gets it as Any to downcast.
+pub type RunnerJob = Box<dyn FnOnce(&mut dyn Any) + Send>;
+
+/// Flags a running Runner checks between ticks.
+#[derive(Default)]
+struct RunnerControl {
+    parked: AtomicBool,
+    stopped: AtomicBool,
+    /// The thread running the runner, to wake it when the flags change.
+    thread: Mutex<Option<Thread>>,
+}
+
+impl RunnerControl {
+    fn wake(&self) -> Result<(), EcsError> {
+        if let Some(thread) = lock_named(&self.thread, type_name::<RunnerControl>())?.as_ref() {
+            thread.unpark();
+        }
+
+        Ok(())
+    }
+}
+
+/// The Nucleus's side of a Runner.
+pub(crate) struct RunnerHandle {
+    commands: Sender<(String, UpdaterCommand)>,
+    jobs: Sender<RunnerJob>,
+    /// Names of the updaters in the runner, kept up to date by the runner so
+    /// commands for updaters it doesn't have fail straight away.
+    updaters: Arc<Mutex<Vec<&'static str>>>,
+    control: Arc<RunnerControl>,
+}
+
+/// A thread spawned by [Nucleus::spawn_runner_thread].
+pub(crate) type RunnerThread = JoinHandle<Result<(), EcsError>>;
+
+/// The Runner's side of its channel to the [Nucleus]. Dropping it removes
+/// the runner from the Nucleus.
+pub struct RunnerCommands {
+    name: String,
+    nucleus: Nucleus,
+    commands: Receiver<(String, UpdaterCommand)>,
+    jobs: Receiver<RunnerJob>,
+    updaters: Arc<Mutex<Vec<&'static str>>>,
+    control: Arc<RunnerControl>,
+}
+
+impl RunnerCommands {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn nucleus(&self) -> &Nucleus {
+        &self.nucleus
+    }
+
+    /// Make the current thread the one woken by [Nucleus::unpark_runner]
+    /// and [Nucleus::stop_runner].
+    pub fn attach_thread(&self) -> Result<(), EcsError> {
+        *lock_named(&self.control.thread, type_name::<RunnerControl>())? = Some(std::thread::current());
+
+        Ok(())
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.control.stopped.load(Ordering::Acquire)
+    }
+
+    /// Block while the runner is parked and not stopped.
+    pub fn wait_while_parked(&self) {
+        while self.control.parked.load(Ordering::Acquire) && !self.is_stopped() {
+            std::thread::park();
+        }
+    }
+
+    /// Sleep until the deadline, waking early if the runner is stopped.
+    pub fn sleep_until(&self, deadline: Instant) {
+        loop {
+            let now = Instant::now();
+
+            if now >= deadline || self.is_stopped() {
+                return;
+            }
+
+            std::thread::park_timeout(deadline - now);
+        }
+    }
+
+    /// Tell the Nucleus which updaters the runner has.
+    pub fn set_updaters(&self, updaters: Vec<&'static str>) -> Result<(), EcsError> {
+        *lock_named(&self.updaters, type_name::<RunnerHandle>())? = updaters;
+
+        Ok(())
+    }
+
+    /// Commands sent since the last call, by updater name, in the order they
+    /// were sent.
+    pub fn receive(&self) -> Vec<(String, UpdaterCommand)> {
+        self.commands.try_iter().collect()
+    }
+
+    /// Jobs sent since the last call, in the order they were sent.
+    pub fn receive_jobs(&self) -> Vec<RunnerJob> {
+        self.jobs.try_iter().collect()
+    }
+}
+
+impl Drop for RunnerCommands {
+    fn drop(&mut self) {
+        if let Ok(mut nucleus) = self.nucleus.data.lock() {
+            nucleus.runner_map.remove(&self.name);
+        }
+    }
+}
+
+impl Nucleus {
+    /// Give a runner a name other threads can send it commands through. Used
+    /// by Runner::new.
+    pub fn add_runner(&self, name: impl Into<String>) -> Result<RunnerCommands, EcsError> {
+        let name = name.into();
+        let mut nucleus = self.lock()?;
+
+        if nucleus.runner_map.contains_key(&name) {
+            return Err(EcsError::RunnerExists { runner: name });
+        }
+
+        let (sender, receiver) = channel();
+        let (job_sender, job_receiver) = channel();
+        let updaters = Arc::new(Mutex::new(Vec::new()));
+        let control = Arc::new(RunnerControl::default());
+
+        nucleus.runner_map.insert(name.clone(), RunnerHandle {
+            commands: sender,
+            jobs: job_sender,
+            updaters: updaters.clone(),
+            control: control.clone(),
+        });
+
+        Ok(RunnerCommands {
+            name,
+            nucleus: self.clone(),
+            commands: receiver,
+            jobs: job_receiver,
+            updaters,
+            control,
+        })
+    }
+
+    /// Run a runner that was added with [Nucleus::add_runner] on a thread
+    /// named after it. The Nucleus keeps the thread until it's joined. Used
+    /// by Runner::spawn.
+    pub fn spawn_runner_thread<F>(&self, runner: &str, run: F) -> Result<(), EcsError>
+    where
+        F: FnOnce() -> Result<(), EcsError> + Send + 'static,
+    {
+        {
+            let mut nucleus = self.lock()?;
+
+            if !nucleus.runner_map.contains_key(runner) {
+                return Err(EcsError::RunnerDoesNotExist { runner: runner.to_string() });
+            }
+
+            if nucleus.runner_threads.contains_key(runner) || !nucleus.spawning_runners.insert(runner.to_string()) {
+                return Err(EcsError::RunnerExists { runner: runner.to_string() });
+            }
+        }
+
+        // Spawned without the lock, run locks the Nucleus when it's dropped,
+        // which happens right here if the thread can't be spawned. The name
+        // stays reserved in spawning_runners until then.
+        let spawned = std::thread::Builder::new()
+            .name(runner.to_string())
+            .spawn(run);
+
+        let mut nucleus = self.lock()?;
+
+        nucleus.spawning_runners.remove(runner);
+
+        let thread = spawned
+            .map_err(|error| EcsError::SpawnRunner { runner: runner.to_string(), error: error.kind() })?;
+
+        nucleus.runner_threads.insert(runner.to_string(), thread);
+
+        Ok(())
+    }
+
+    fn runner_control(&self, runner: &str) -> Result<Arc<RunnerControl>, EcsError> {
+        let nucleus = self.lock()?;
+
+        nucleus.runner_map.get(runner)
+            .map(|handle| handle.control.clone())
+            .ok_or_else(|| EcsError::RunnerDoesNotExist { runner: runner.to_string() })
+    }
+
+    /// Have the runner wait before its next tick until it's unparked or
+    /// stopped. Only runners in Runner::run check this.
+    pub fn park_runner(&self, runner: &str) -> Result<(), EcsError> {
+        self.runner_control(runner)?.parked.store(true, Ordering::Release);
+
+        Ok(())
+    }
+
+    pub fn unpark_runner(&self, runner: &str) -> Result<(), EcsError> {
+        let control = self.runner_control(runner)?;
+
+        control.parked.store(false, Ordering::Release);
+        control.wake()
+    }
+
+    /// Have Runner::run return before the runner's next tick. Doesn't wait
+    /// for it, see [Nucleus::join_runner].
+    pub fn stop_runner(&self, runner: &str) -> Result<(), EcsError> {
+        let control = self.runner_control(runner)?;
+
+        control.stopped.store(true, Ordering::Release);
+        control.wake()
+    }
+
+    /// Wait for the runner's thread to finish, returning the error it
+    /// finished with, or [EcsError::RunnerPanicked].
+    pub fn join_runner(&self, runner: &str) -> Result<(), EcsError> {
+        // The runner removes itself from the Nucleus as it finishes, so the
+        // lock can't be held while joining.
+        let thread = self.lock()?.runner_threads.remove(runner)
+            .ok_or_else(|| EcsError::RunnerDoesNotExist { runner: runner.to_string() })?;
+
+        join_runner_thread(runner, thread)
+    }
+
+    /// Stop every runner and join their threads. Returns what each runner
+    /// that didn't finish cleanly failed with.
+    pub fn shutdown(&self) -> Vec<EcsError> {
+        let mut errors = Vec::new();
+
+        let threads: Vec<(String, RunnerThread)> = match self.lock() {
+            Ok(mut nucleus) => {
+                for handle in nucleus.runner_map.values() {
+                    handle.control.stopped.store(true, Ordering::Release);
+
+                    if let Err(error) = handle.control.wake() {
+                        errors.push(error);
+                    }
+                }
+
+                nucleus.runner_threads.drain().collect()
+            }
+            Err(error) => return vec![error],
+        };
+
+        errors.extend(threads.into_iter()
+            .filter_map(|(runner, thread)| join_runner_thread(&runner, thread).err()));
+
+        errors
+    }
+
+    /// Names of the runners in the Nucleus.
+    pub fn runner_names(&self) -> Result<Vec<String>, EcsError> {
+        Ok(self.lock()?.runner_map.keys().cloned().collect())
+    }
+
+    /// Names of the updaters in the runner, in the order they update.
+    pub fn updater_names(&self, runner: &str) -> Result<Vec<&'static str>, EcsError> {
+        let nucleus = self.lock()?;
+
+        let handle = nucleus.runner_map.get(runner)
+            .ok_or_else(|| EcsError::RunnerDoesNotExist { runner: runner.to_string() })?;
+
+        let updaters = lock_named(&handle.updaters, type_name::<RunnerHandle>())?.clone();
+
+        Ok(updaters)
+    }
+
+    /// Send the command to the updater in the runner. Updaters are named by
+    /// their type name, see UpdaterId::name.
+    pub fn send_updater_command(&self, runner: &str, updater: &str, command: UpdaterCommand) -> Result<(), EcsError> {
+        let nucleus = self.lock()?;
+
+        let handle = nucleus.runner_map.get(runner)
+            .ok_or_else(|| EcsError::RunnerDoesNotExist { runner: runner.to_string() })?;
+
+        if !lock_named(&handle.updaters, type_name::<RunnerHandle>())?.contains(&updater) {
+            return Err(EcsError::UpdaterDoesNotExist { updater: updater.to_string() });
+        }
+
+        handle.commands.send((updater.to_string(), command))
+            .map_err(|_| EcsError::RunnerDoesNotExist { runner: runner.to_string() })
+    }
+
+    /// See [RunnerJob]. Jobs for a runner that's dropped before its next
+    /// tick are dropped without running.
+    pub fn send_runner_job(&self, runner: &str, job: RunnerJob) -> Result<(), EcsError> {
+        let nucleus = self.lock()?;
+
+        let handle = nucleus.runner_map.get(runner)
+            .ok_or_else(|| EcsError::RunnerDoesNotExist { runner: runner.to_string() })?;
+
+        handle.jobs.send(job)
+            .map_err(|_| EcsError::RunnerDoesNotExist { runner: runner.to_string() })
+    }
+
+    pub fn enable_updater(&self, runner: &str, updater: &str) -> Result<(), EcsError> {
+        self.send_updater_command(runner, updater, UpdaterCommand::Enable)
+    }
+
+    pub fn disable_updater(&self, runner: &str, updater: &str) -> Result<(), EcsError> {
+        self.send_updater_command(runner, updater, UpdaterCommand::Disable)
+    }
+
+    pub fn remove_updater(&self, runner: &str, updater: &str) -> Result<(), EcsError> {
+        self.send_updater_command(runner, updater, UpdaterCommand::Remove)
+    }
+}
+
+fn join_runner_thread(runner: &str, thread: RunnerThread) -> Result<(), EcsError> {
+    match thread.join() {
+        Ok(result) => result,
+        Err(panic) => {
+            let message = match panic.downcast_ref::<&str>() {
+                Some(message) => message.to_string(),
+                None => panic.downcast_ref::<String>().cloned().unwrap_or_default(),
+            };
+
+            Err(EcsError::RunnerPanicked { runner: runner.to_string(), message })
+        }
+    }
+}
@@ -1,27 +1,63 @@
 use as_any::AsAny;
 
 use std::{
-    collections::HashMap,
-    sync::{Arc, Mutex},
-    any::TypeId,
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex, MutexGuard},
+    any::{type_name, TypeId},
+    thread::ThreadId,
 };
 
 use data_trait::DataTrait;
 use data_singleton::DataSingleton;
-use data_set::DataSet;
+use data_set::{DataSet, DataSetTrait};
+use read_only::{ReadOnlySet, ReadOnlySingleton};
+use access_tracker::{AccessReport, AccessTracker};
+use entity::{Entity, EntityAllocator};
+use ecs_error::EcsError;
+
+mod commands;
+mod query;
+pub use commands::*;
+pub use query::*;
 
 struct NucleusData {
     name: String,
 
-    data_set_map: HashMap<TypeId, Box<dyn AsAny>>,
+    data_set_map: HashMap<TypeId, Box<dyn DataSetTrait>>,
 
     data_singleton_map: HashMap<TypeId, Box<dyn AsAny>>,
 
-    runner_map: HashMap<String, Box<dyn AsAny>>,
+    read_only_set_map: HashMap<TypeId, Box<dyn DataSetTrait>>,
+
+    read_only_singleton_map: HashMap<TypeId, Box<dyn AsAny>>,
+
+    /// For every data set and singleton, see [Nucleus::access_report].
+    access_trackers: Vec<(TypeId, AccessTracker)>,
+
+    /// Whether data added from now on counts its gets, see
+    /// [Nucleus::set_access_tracking].
+    access_tracking: bool,
+
+    runner_map: HashMap<String, RunnerHandle>,
+
+    runner_threads: HashMap<String, RunnerThread>,
+
+    /// Runners whose thread is being spawned, see [Nucleus::spawn_runner_thread].
+    spawning_runners: HashSet<String>,
+
+    /// One allocator per thread, indexed by the thread mask it stamps into
+    /// its entities.
+    entity_allocators: Vec<Arc<Mutex<EntityAllocator>>>,
+
+    thread_masks: HashMap<ThreadId, u16>,
 }
 
+/// The types of the data added through a Nucleus, see [Nucleus::logging_added].
+pub type AddedData = Arc<Mutex<Vec<TypeId>>>;
+
 pub struct Nucleus {
     data: Arc<Mutex<NucleusData>>,
+    added: Option<AddedData>,
 }
 
 impl Nucleus {
@@ -31,54 +67,289 @@ impl Nucleus {
                 name: name.into(),
                 data_set_map: HashMap::new(),
                 data_singleton_map: HashMap::new(),
+                read_only_set_map: HashMap::new(),
+                read_only_singleton_map: HashMap::new(),
+                access_trackers: Vec::new(),
+                access_tracking: false,
                 runner_map: HashMap::new(),
-            }))
+                runner_threads: HashMap::new(),
+                spawning_runners: HashSet::new(),
+                entity_allocators: Vec::new(),
+                thread_masks: HashMap::new(),
+            })),
+            added: None,
+        }
+    }
+
+    /// Another handle to this Nucleus that also logs the type of every data
+    /// set and singleton added through it or its clones. Used by ModLoader
+    /// to know which data is a mod's.
+    pub fn logging_added(&self, added: AddedData) -> Self {
+        Self {
+            data: self.data.clone(),
+            added: Some(added),
         }
     }
 
-    /// TODO(MakeFallible)
-    pub fn add_data_singleton<T: DataTrait>(&self, data: T) -> DataSingleton<T> {
-        let mut nucleus = self.data.lock().unwrap();
+    fn log_added<T: DataTrait>(&self) {
+        if let Some(added) = self.added.as_ref() {
+            // Only the log is lost, the data was still added.
+            if let Ok(mut added) = added.lock() {
+                added.push(TypeId::of::<T>());
+            }
+        }
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, NucleusData>, EcsError> {
+        lock_named(&self.data, type_name::<Nucleus>())
+    }
+
+    pub fn add_data_singleton<T: DataTrait>(&self, data: T) -> Result<DataSingleton<T>, EcsError> {
+        let mut nucleus = self.lock()?;
+
+        if nucleus.data_singleton_map.contains_key(&TypeId::of::<T>()) {
+            return Err(EcsError::DataSingletonExists { data: type_name::<T>() });
+        }
 
         let data_singleton = DataSingleton::new(data);
 
+        data_singleton.access_tracker().set_enabled(nucleus.access_tracking);
+        nucleus.access_trackers.push((TypeId::of::<T>(), data_singleton.access_tracker().clone()));
         nucleus.data_singleton_map.insert(TypeId::of::<T>(), Box::new(data_singleton.clone()));
+        self.log_added::<T>();
 
-        data_singleton
+        Ok(data_singleton)
     }
 
-    /// TODO(MakeFallible)
     /// TODO(HandleSingleMutability)
     /// Possible ways to do single mutability are keep track of what
     /// data have already been given out mutably. would need a new
     /// data singleton primitive?
-    pub fn get_data_singleton<T: DataTrait>(&self) -> DataSingleton<T> {
-        let nucleus = self.data.lock().unwrap();
-
-        let box_ref = nucleus.data_singleton_map.get(&TypeId::of::<T>()).unwrap();
+    pub fn get_data_singleton<T: DataTrait>(&self) -> Result<DataSingleton<T>, EcsError> {
+        let nucleus = self.lock()?;
 
-        (*box_ref.as_any().downcast_ref::<DataSingleton<T>>().unwrap()).clone()
+        nucleus.data_singleton_map.get(&TypeId::of::<T>())
+            .and_then(|box_ref| box_ref.as_any().downcast_ref::<DataSingleton<T>>())
+            .cloned()
+            .ok_or(EcsError::DataSingletonDoesNotExist { data: type_name::<T>() })
     }
 
-    /// TODO(MakeFallible)
-    pub fn add_data_set<T: DataTrait>(&self) -> DataSet<T> {
-        let mut nucleus = self.data.lock().unwrap();
+    pub fn add_data_set<T: DataTrait>(&self) -> Result<DataSet<T>, EcsError> {
+        let mut nucleus = self.lock()?;
+
+        if nucleus.data_set_map.contains_key(&TypeId::of::<T>()) {
+            return Err(EcsError::DataSetExists { data: type_name::<T>() });
+        }
 
         let data_set = DataSet::<T>::new();
+        data_set.access_tracker().set_enabled(nucleus.access_tracking);
+        nucleus.access_trackers.push((TypeId::of::<T>(), data_set.access_tracker().clone()));
         nucleus.data_set_map.insert(TypeId::of::<T>(), Box::new(data_set.clone()));
+        self.log_added::<T>();
 
-        data_set
+        Ok(data_set)
     }
 
-    /// TODO(MakeFallible)
     /// TODO(HandleSingleMutability)
-    pub fn get_data_set<T: DataTrait>(&self) -> DataSet<T> {
-        let nucleus = self.data.lock().unwrap();
+    pub fn get_data_set<T: DataTrait>(&self) -> Result<DataSet<T>, EcsError> {
+        let nucleus = self.lock()?;
+
+        nucleus.data_set_map.get(&TypeId::of::<T>())
+            .and_then(|box_ref| box_ref.as_any().downcast_ref::<DataSet<T>>())
+            .cloned()
+            .ok_or(EcsError::DataSetDoesNotExist { data: type_name::<T>() })
+    }
+
+    /// How often each updater got each data set and singleton, per tick,
+    /// and how long it waited for their locks, since they were added or the
+    /// counts were last reset. Empty unless [Nucleus::set_access_tracking]
+    /// is on.
+    pub fn access_report(&self) -> Result<AccessReport, EcsError> {
+        let nucleus = self.lock()?;
+
+        Ok(AccessReport::new(nucleus.access_trackers.iter().map(|(_, tracker)| tracker.report()).collect()))
+    }
+
+    /// Start or stop counting the gets on every data set and singleton, for
+    /// [Nucleus::access_report]. Off by default, counting adds a shared lock
+    /// to every get.
+    pub fn set_access_tracking(&self, enabled: bool) -> Result<(), EcsError> {
+        let mut nucleus = self.lock()?;
+
+        nucleus.access_tracking = enabled;
+
+        for (_, tracker) in nucleus.access_trackers.iter() {
+            tracker.set_enabled(enabled);
+        }
 
-        let box_ref = nucleus.data_set_map.get(&TypeId::of::<T>()).unwrap();
+        Ok(())
+    }
+
+    pub fn reset_access_counts(&self) -> Result<(), EcsError> {
+        for (_, tracker) in self.lock()?.access_trackers.iter() {
+            tracker.reset();
+        }
+
+        Ok(())
+    }
+
+    /// The types of every data set and singleton, read-only or not.
+    pub fn data_type_ids(&self) -> Result<HashSet<TypeId>, EcsError> {
+        let nucleus = self.lock()?;
 
-        (*box_ref.as_any().downcast_ref::<DataSet<T>>().unwrap()).clone()
+        Ok(nucleus.data_set_map.keys()
+            .chain(nucleus.data_singleton_map.keys())
+            .chain(nucleus.read_only_set_map.keys())
+            .chain(nucleus.read_only_singleton_map.keys())
+            .copied()
+            .collect())
     }
+
+    /// Drop the Nucleus's data sets and singletons of the type, of every
+    /// kind. Clones already handed out keep working, but can't be got again.
+    pub fn remove_data_type(&self, type_id: TypeId) -> Result<(), EcsError> {
+        let mut nucleus = self.lock()?;
+
+        nucleus.data_set_map.remove(&type_id);
+        nucleus.data_singleton_map.remove(&type_id);
+        nucleus.read_only_set_map.remove(&type_id);
+        nucleus.read_only_singleton_map.remove(&type_id);
+        nucleus.access_trackers.retain(|(tracker_type_id, _)| *tracker_type_id != type_id);
+
+        Ok(())
+    }
+
+    pub fn add_read_only_singleton<T: DataTrait + Send + Sync>(&self, data: T) -> Result<ReadOnlySingleton<T>, EcsError> {
+        let mut nucleus = self.lock()?;
+
+        if nucleus.read_only_singleton_map.contains_key(&TypeId::of::<T>()) {
+            return Err(EcsError::ReadOnlySingletonExists { data: type_name::<T>() });
+        }
+
+        let read_only_singleton = ReadOnlySingleton::new(data);
+
+        nucleus.read_only_singleton_map.insert(TypeId::of::<T>(), Box::new(read_only_singleton.clone()));
+        self.log_added::<T>();
+
+        Ok(read_only_singleton)
+    }
+
+    pub fn get_read_only_singleton<T: DataTrait + Send + Sync>(&self) -> Result<ReadOnlySingleton<T>, EcsError> {
+        let nucleus = self.lock()?;
+
+        nucleus.read_only_singleton_map.get(&TypeId::of::<T>())
+            .and_then(|box_ref| box_ref.as_any().downcast_ref::<ReadOnlySingleton<T>>())
+            .cloned()
+            .ok_or(EcsError::ReadOnlySingletonDoesNotExist { data: type_name::<T>() })
+    }
+
+    pub fn add_read_only_set<T: DataTrait + Send + Sync>(&self) -> Result<ReadOnlySet<T>, EcsError> {
+        let mut nucleus = self.lock()?;
+
+        if nucleus.read_only_set_map.contains_key(&TypeId::of::<T>()) {
+            return Err(EcsError::ReadOnlySetExists { data: type_name::<T>() });
+        }
+
+        let read_only_set = ReadOnlySet::<T>::new();
+        nucleus.read_only_set_map.insert(TypeId::of::<T>(), Box::new(read_only_set.clone()));
+        self.log_added::<T>();
+
+        Ok(read_only_set)
+    }
+
+    pub fn get_read_only_set<T: DataTrait + Send + Sync>(&self) -> Result<ReadOnlySet<T>, EcsError> {
+        let nucleus = self.lock()?;
+
+        nucleus.read_only_set_map.get(&TypeId::of::<T>())
+            .and_then(|box_ref| box_ref.as_any().downcast_ref::<ReadOnlySet<T>>())
+            .cloned()
+            .ok_or(EcsError::ReadOnlySetDoesNotExist { data: type_name::<T>() })
+    }
+
+    /// Make a new entity. Each thread gets its own allocator the first time it
+    /// spawns, and stamps its mask into the ids, so no two threads ever hand
+    /// out the same one.
+    pub fn spawn(&self) -> Result<Entity, EcsError> {
+        let allocator = {
+            let mut nucleus = self.lock()?;
+            let thread_id = std::thread::current().id();
+
+            let thread_mask = match nucleus.thread_masks.get(&thread_id) {
+                Some(thread_mask) => *thread_mask,
+                None => {
+                    let thread_mask = nucleus.entity_allocators.len() as u16;
+
+                    nucleus.entity_allocators.push(Arc::new(Mutex::new(EntityAllocator::new(thread_mask))));
+                    nucleus.thread_masks.insert(thread_id, thread_mask);
+
+                    thread_mask
+                }
+            };
+
+            nucleus.entity_allocators[thread_mask as usize].clone()
+        };
+
+        let mut allocator = lock_allocator(&allocator)?;
+
+        Ok(allocator.allocate())
+    }
+
+    /// Free the entity and drop its data from every [DataSet]. Returns false
+    /// if the entity was already despawned.
+    ///
+    /// This locks every DataSet in turn, so don't call it while holding one.
+    /// The Nucleus itself is unlocked first, since updaters holding a set
+    /// can still spawn.
+    pub fn despawn(&self, entity: Entity) -> Result<bool, EcsError> {
+        let data_sets: Vec<Box<dyn DataSetTrait>> = {
+            let nucleus = self.lock()?;
+
+            let allocator = match nucleus.entity_allocators.get(entity.thread_mask() as usize) {
+                Some(allocator) => allocator,
+                None => return Ok(false),
+            };
+
+            if !lock_allocator(allocator)?.free(entity) {
+                return Ok(false);
+            }
+
+            nucleus.data_set_map.values()
+                .chain(nucleus.read_only_set_map.values())
+                .map(|data_set| data_set.clone_box())
+                .collect()
+        };
+
+        // The entity is already freed, so its data is removed from every set
+        // that can be reached before reporting the first that can't.
+        let mut result = Ok(true);
+
+        for data_set in data_sets.iter() {
+            if let Err(error) = data_set.remove_entity(entity) {
+                result = result.and(Err(error));
+            }
+        }
+
+        result
+    }
+
+    /// False once the entity has been despawned, even if its index was reused.
+    pub fn is_alive(&self, entity: Entity) -> Result<bool, EcsError> {
+        let nucleus = self.lock()?;
+
+        match nucleus.entity_allocators.get(entity.thread_mask() as usize) {
+            Some(allocator) => Ok(lock_allocator(allocator)?.is_alive(entity)),
+            None => Ok(false),
+        }
+    }
+}
+
+/// Lock a mutex, naming what it guards in [EcsError::Poisoned].
+pub(crate) fn lock_named<'a, T>(mutex: &'a Mutex<T>, data: &'static str) -> Result<MutexGuard<'a, T>, EcsError> {
+    mutex.lock().map_err(|_| EcsError::Poisoned { data })
+}
+
+fn lock_allocator(allocator: &Mutex<EntityAllocator>) -> Result<MutexGuard<'_, EntityAllocator>, EcsError> {
+    lock_named(allocator, type_name::<EntityAllocator>())
 }
 
 unsafe impl Send for Nucleus {}
@@ -87,7 +358,8 @@ unsafe impl Sync for Nucleus {}
 impl Clone for Nucleus {
     fn clone(&self) -> Self {
         Self {
-            data: self.data.clone()
+            data: self.data.clone(),
+            added: self.added.clone(),
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,347 @@
+use std::{
+    marker::PhantomData,
+    sync::MutexGuard,
+};
+
+use data_trait::DataTrait;
+use data_set::{DataSet, DataSetTrait};
+use data_storage::DataStorage;
+use ecs_error::EcsError;
+use entity::Entity;
+
+use crate::Nucleus;
+
+/// Query for mutable access to the entity's T.
+pub struct Write<T: DataTrait>(PhantomData<T>);
+
+/// Query for shared access to the entity's T.
+pub struct Read<T: DataTrait>(PhantomData<T>);
+
+/// Wrap a [Write] or [Read] to also match entities without that data. The
+/// item is None for those.
+pub struct Maybe<P: QueryParam>(PhantomData<P>);
+
+/// A single [DataSet] in a [Query].
+pub trait QueryParam {
+    type Set;
+    type Guard<'a>;
+    type Item<'a>;
+
+    /// Entities without the data are skipped.
+    const REQUIRED: bool;
+
+    fn fetch(nucleus: &Nucleus) -> Result<Self::Set, EcsError>;
+    fn data_name() -> &'static str;
+    fn lock_key(set: &Self::Set) -> usize;
+    fn lock(set: &Self::Set) -> Result<Self::Guard<'_>, EcsError>;
+    fn len(guard: &Self::Guard<'_>) -> usize;
+    fn entities(guard: &Self::Guard<'_>) -> Vec<Entity>;
+    fn contains(guard: &Self::Guard<'_>, entity: Entity) -> bool;
+    /// Only called for entities the guard contains, unless the param isn't
+    /// required.
+    fn get<'a>(guard: &'a mut Self::Guard<'_>, entity: Entity) -> Self::Item<'a>;
+}
+
+impl<T> QueryParam for Write<T> where T: DataTrait {
+    type Set = DataSet<T>;
+    type Guard<'a> = MutexGuard<'a, T::Storage>;
+    type Item<'a> = <T::Storage as DataStorage<T>>::Mut<'a>;
+
+    const REQUIRED: bool = true;
+
+    fn fetch(nucleus: &Nucleus) -> Result<Self::Set, EcsError> {
+        nucleus.get_data_set::<T>()
+    }
+
+    fn data_name() -> &'static str {
+        std::any::type_name::<T>()
+    }
+
+    fn lock_key(set: &Self::Set) -> usize {
+        set.lock_key()
+    }
+
+    fn lock(set: &Self::Set) -> Result<Self::Guard<'_>, EcsError> {
+        set.get()
+    }
+
+    fn len(guard: &Self::Guard<'_>) -> usize {
+        guard.len()
+    }
+
+    fn entities(guard: &Self::Guard<'_>) -> Vec<Entity> {
+        guard.entities().collect()
+    }
+
+    fn contains(guard: &Self::Guard<'_>, entity: Entity) -> bool {
+        guard.contains(entity)
+    }
+
+    fn get<'a>(guard: &'a mut Self::Guard<'_>, entity: Entity) -> Self::Item<'a> {
+        guard.get_mut(entity).unwrap()
+    }
+}
+
+impl<T> QueryParam for Read<T> where T: DataTrait {
+    type Set = DataSet<T>;
+    type Guard<'a> = MutexGuard<'a, T::Storage>;
+    type Item<'a> = <T::Storage as DataStorage<T>>::Ref<'a>;
+
+    const REQUIRED: bool = true;
+
+    fn fetch(nucleus: &Nucleus) -> Result<Self::Set, EcsError> {
+        nucleus.get_data_set::<T>()
+    }
+
+    fn data_name() -> &'static str {
+        std::any::type_name::<T>()
+    }
+
+    fn lock_key(set: &Self::Set) -> usize {
+        set.lock_key()
+    }
+
+    fn lock(set: &Self::Set) -> Result<Self::Guard<'_>, EcsError> {
+        set.get()
+    }
+
+    fn len(guard: &Self::Guard<'_>) -> usize {
+        guard.len()
+    }
+
+    fn entities(guard: &Self::Guard<'_>) -> Vec<Entity> {
+        guard.entities().collect()
+    }
+
+    fn contains(guard: &Self::Guard<'_>, entity: Entity) -> bool {
+        guard.contains(entity)
+    }
+
+    fn get<'a>(guard: &'a mut Self::Guard<'_>, entity: Entity) -> Self::Item<'a> {
+        guard.get(entity).unwrap()
+    }
+}
+
+impl<P> QueryParam for Maybe<P> where P: QueryParam {
+    type Set = P::Set;
+    type Guard<'a> = P::Guard<'a>;
+    type Item<'a> = Option<P::Item<'a>>;
+
+    const REQUIRED: bool = false;
+
+    fn fetch(nucleus: &Nucleus) -> Result<Self::Set, EcsError> {
+        P::fetch(nucleus)
+    }
+
+    fn data_name() -> &'static str {
+        P::data_name()
+    }
+
+    fn lock_key(set: &Self::Set) -> usize {
+        P::lock_key(set)
+    }
+
+    fn lock(set: &Self::Set) -> Result<Self::Guard<'_>, EcsError> {
+        P::lock(set)
+    }
+
+    fn len(guard: &Self::Guard<'_>) -> usize {
+        P::len(guard)
+    }
+
+    fn entities(guard: &Self::Guard<'_>) -> Vec<Entity> {
+        P::entities(guard)
+    }
+
+    fn contains(_guard: &Self::Guard<'_>, _entity: Entity) -> bool {
+        true
+    }
+
+    fn get<'a>(guard: &'a mut Self::Guard<'_>, entity: Entity) -> Self::Item<'a> {
+        if P::contains(guard, entity) {
+            Some(P::get(guard, entity))
+        } else {
+            None
+        }
+    }
+}
+
+/// Which lock to take next while locking a [Query]'s sets.
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum QueryLock {
+    Param(usize),
+    Excluded(usize),
+}
+
+/// A tuple of [QueryParam]s. Implemented for tuples of up to 6.
+pub trait QueryParams {
+    type Sets;
+    type Items<'a>;
+
+    fn fetch(nucleus: &Nucleus) -> Result<Self::Sets, EcsError>;
+    /// The lock key and data type name of each set.
+    fn lock_keys(sets: &Self::Sets) -> Vec<(usize, &'static str)>;
+    fn for_each<F>(sets: &Self::Sets, excluded: &[Box<dyn DataSetTrait>], f: F) -> Result<(), EcsError>
+    where
+        F: for<'a> FnMut(Entity, Self::Items<'a>);
+}
+
+macro_rules! impl_query_params {
+    ($(($param:ident, $index:tt, $guard:ident)),+) => {
+        impl<$($param),+> QueryParams for ($($param,)+) where $($param: QueryParam),+ {
+            type Sets = ($($param::Set,)+);
+            type Items<'a> = ($($param::Item<'a>,)+);
+
+            fn fetch(nucleus: &Nucleus) -> Result<Self::Sets, EcsError> {
+                Ok(($($param::fetch(nucleus)?,)+))
+            }
+
+            fn lock_keys(sets: &Self::Sets) -> Vec<(usize, &'static str)> {
+                vec![$(($param::lock_key(&sets.$index), $param::data_name())),+]
+            }
+
+            fn for_each<F>(sets: &Self::Sets, excluded: &[Box<dyn DataSetTrait>], mut f: F) -> Result<(), EcsError>
+            where
+                F: for<'a> FnMut(Entity, Self::Items<'a>),
+            {
+                let mut order = vec![$(($param::lock_key(&sets.$index), QueryLock::Param($index))),+];
+
+                for (index, excluded_set) in excluded.iter().enumerate() {
+                    order.push((excluded_set.lock_key(), QueryLock::Excluded(index)));
+                }
+
+                order.sort();
+
+                $(let mut $guard = None;)+
+                let mut excluded_guards: Vec<_> = excluded.iter().map(|_| None).collect();
+
+                for (_, lock) in order.into_iter() {
+                    match lock {
+                        $(QueryLock::Param($index) => $guard = Some($param::lock(&sets.$index)?),)+
+                        QueryLock::Param(_) => unreachable!(),
+                        QueryLock::Excluded(index) => excluded_guards[index] = Some(excluded[index].lock_contains()?),
+                    }
+                }
+
+                $(let mut $guard = $guard.unwrap();)+
+                let excluded_guards: Vec<_> = excluded_guards.into_iter().map(Option::unwrap).collect();
+
+                // Walk the smallest required set, checking the rest against it.
+                let mut smallest: Option<(usize, usize)> = None;
+
+                $(
+                    if $param::REQUIRED && smallest.map_or(true, |(len, _)| $param::len(&$guard) < len) {
+                        smallest = Some(($param::len(&$guard), $index));
+                    }
+                )+
+
+                let entities = match smallest {
+                    $(Some((_, $index)) => $param::entities(&$guard),)+
+                    // Nothing is required, so every entity in any of the sets matches.
+                    _ => {
+                        let mut entities = Vec::new();
+                        $(entities.extend($param::entities(&$guard));)+
+                        entities.sort();
+                        entities.dedup();
+                        entities
+                    }
+                };
+
+                for entity in entities.into_iter() {
+                    if $(!$param::contains(&$guard, entity))||+ {
+                        continue;
+                    }
+
+                    if excluded_guards.iter().any(|contains| contains(entity)) {
+                        continue;
+                    }
+
+                    f(entity, ($($param::get(&mut $guard, entity),)+));
+                }
+
+                Ok(())
+            }
+        }
+    };
+}
+
+impl_query_params!((P0, 0, guard_0));
+impl_query_params!((P0, 0, guard_0), (P1, 1, guard_1));
+impl_query_params!((P0, 0, guard_0), (P1, 1, guard_1), (P2, 2, guard_2));
+impl_query_params!((P0, 0, guard_0), (P1, 1, guard_1), (P2, 2, guard_2), (P3, 3, guard_3));
+impl_query_params!((P0, 0, guard_0), (P1, 1, guard_1), (P2, 2, guard_2), (P3, 3, guard_3), (P4, 4, guard_4));
+impl_query_params!((P0, 0, guard_0), (P1, 1, guard_1), (P2, 2, guard_2), (P3, 3, guard_3), (P4, 4, guard_4), (P5, 5, guard_5));
+
+/// Iterates the entities that have data in several [DataSet]s at once, so
+/// updaters don't have to zip locked maps by hand.
+///
+/// let query = nucleus.query::<(Write<StatusEffects>, Read<Mover>)>()?.without::<Frozen>()?;
+/// query.for_each(|entity, (status_effects, mover)| { ... })?;
+///
+/// All of the sets are locked for the whole for_each, always in the same order
+/// regardless of the order they're named in. Two queries on different threads
+/// can't deadlock each other, but holding another set's guard while running a
+/// query still can.
+pub struct Query<P: QueryParams> {
+    nucleus: Nucleus,
+    sets: P::Sets,
+    excluded: Vec<Box<dyn DataSetTrait>>,
+}
+
+impl<P> Query<P> where P: QueryParams {
+    pub fn new(nucleus: &Nucleus) -> Result<Self, EcsError> {
+        let query = Self {
+            nucleus: nucleus.clone(),
+            sets: P::fetch(nucleus)?,
+            excluded: Vec::new(),
+        };
+
+        query.check_unique()?;
+
+        Ok(query)
+    }
+
+    /// Skip entities that have data in the T set. A builder type method.
+    pub fn without<T: DataTrait>(mut self) -> Result<Self, EcsError> {
+        self.excluded.push(Box::new(self.nucleus.get_data_set::<T>()?));
+
+        self.check_unique()?;
+
+        Ok(self)
+    }
+
+    /// Naming the same set twice would lock it twice and hang.
+    fn check_unique(&self) -> Result<(), EcsError> {
+        let mut lock_keys = P::lock_keys(&self.sets);
+
+        lock_keys.extend(self.excluded.iter().map(|excluded| (excluded.lock_key(), excluded.data_name())));
+        lock_keys.sort();
+
+        match lock_keys.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+            Some(pair) => Err(EcsError::DuplicateQuerySet { data: pair[0].1 }),
+            None => Ok(()),
+        }
+    }
+
+    /// Call f with every matching entity and its data. Fails without calling
+    /// f if any of the sets were poisoned.
+    pub fn for_each<F>(&self, f: F) -> Result<(), EcsError> where F: for<'a> FnMut(Entity, P::Items<'a>) {
+        P::for_each(&self.sets, &self.excluded, f)
+    }
+
+    /// The entities that currently match.
+    pub fn entities(&self) -> Result<Vec<Entity>, EcsError> {
+        let mut entities = Vec::new();
+
+        self.for_each(|entity, _| entities.push(entity))?;
+
+        Ok(entities)
+    }
+}
+
+impl Nucleus {
+    /// See [Query].
+    pub fn query<P: QueryParams>(&self) -> Result<Query<P>, EcsError> {
+        Query::new(self)
+    }
+}
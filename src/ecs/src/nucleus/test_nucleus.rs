@@ -0,0 +1,219 @@
+use data_storage::*;
+use data_trait::DataTrait;
+use ecs_error::EcsError;
+use nucleus::*;
+
+struct Position(i32);
+
+impl DataTrait for Position {
+    type Storage = BTreeMapStorage<Self>;
+}
+
+#[test]
+fn test_nucleus_despawn() -> Result<(), EcsError> {
+    let nucleus = Nucleus::new("test");
+    let positions = nucleus.add_data_set::<Position>()?;
+
+    let entity = nucleus.spawn()?;
+
+    positions.get()?.insert(entity, Position(1));
+
+    assert_eq!(positions.get()?.get(entity).map(|position| position.0), Some(1));
+    assert!(nucleus.is_alive(entity)?);
+    assert!(nucleus.despawn(entity)?);
+    assert!(!nucleus.despawn(entity)?);
+    assert!(!nucleus.is_alive(entity)?);
+    assert!(positions.get()?.is_empty());
+
+    // The index is reused, but the stale entity stays dead.
+    let reused = nucleus.spawn()?;
+
+    assert_eq!(reused.index(), entity.index());
+    assert!(nucleus.is_alive(reused)?);
+    assert!(!nucleus.is_alive(entity)?);
+
+    Ok(())
+}
+
+#[test]
+fn test_nucleus_spawn_per_thread() -> Result<(), EcsError> {
+    let nucleus = Nucleus::new("test");
+    let entity = nucleus.spawn()?;
+
+    let thread_nucleus = nucleus.clone();
+    let other = std::thread::spawn(move || thread_nucleus.spawn()).join().unwrap()?;
+
+    assert_ne!(entity.thread_mask(), other.thread_mask());
+    assert!(nucleus.is_alive(other)?);
+    assert!(nucleus.despawn(other)?);
+
+    Ok(())
+}
+
+struct Velocity(i32);
+
+impl DataTrait for Velocity {
+    type Storage = HashMapStorage<Self>;
+}
+
+struct Frozen;
+
+impl DataTrait for Frozen {
+    type Storage = BTreeMapStorage<Self>;
+}
+
+#[test]
+fn test_query_join() -> Result<(), EcsError> {
+    let nucleus = Nucleus::new("test");
+    let positions = nucleus.add_data_set::<Position>()?;
+    let velocities = nucleus.add_data_set::<Velocity>()?;
+    let frozen = nucleus.add_data_set::<Frozen>()?;
+
+    let moving = nucleus.spawn()?;
+    let still = nucleus.spawn()?;
+    let stuck = nucleus.spawn()?;
+
+    positions.get()?.insert(moving, Position(0));
+    positions.get()?.insert(still, Position(10));
+    positions.get()?.insert(stuck, Position(20));
+    velocities.get()?.insert(moving, Velocity(1));
+    velocities.get()?.insert(stuck, Velocity(1));
+    frozen.get()?.insert(stuck, Frozen);
+
+    let query = nucleus.query::<(Write<Position>, Read<Velocity>)>()?.without::<Frozen>()?;
+
+    assert_eq!(query.entities()?, [moving]);
+
+    query.for_each(|_, (position, velocity)| position.0 += velocity.0)?;
+
+    assert_eq!(positions.get()?.get(moving).map(|position| position.0), Some(1));
+    assert_eq!(positions.get()?.get(stuck).map(|position| position.0), Some(20));
+
+    let mut speeds = Vec::new();
+
+    nucleus.query::<(Read<Position>, Maybe<Read<Velocity>>)>()?
+        .for_each(|entity, (_, velocity)| speeds.push((entity, velocity.map(|velocity| velocity.0))))?;
+
+    assert_eq!(speeds, [(moving, Some(1)), (still, None), (stuck, Some(1))]);
+
+    Ok(())
+}
+
+#[test]
+fn test_query_duplicate_sets() -> Result<(), EcsError> {
+    let nucleus = Nucleus::new("test");
+
+    nucleus.add_data_set::<Position>()?;
+    nucleus.add_data_set::<Velocity>()?;
+
+    assert!(matches!(
+        nucleus.query::<(Read<Position>, Write<Position>)>(),
+        Err(EcsError::DuplicateQuerySet { .. })
+    ));
+    assert!(matches!(
+        nucleus.query::<(Read<Position>, Read<Velocity>)>()?.without::<Velocity>(),
+        Err(EcsError::DuplicateQuerySet { .. })
+    ));
+    assert!(matches!(
+        nucleus.query::<(Read<Frozen>,)>(),
+        Err(EcsError::DataSetDoesNotExist { .. })
+    ));
+
+    Ok(())
+}
+
+/// Both queries lock the sets in the same order, whichever order they name
+/// them in, so they never wait on each other. A deadlock times the test out.
+#[test]
+fn test_query_lock_order() -> Result<(), EcsError> {
+    let nucleus = Nucleus::new("test");
+    let positions = nucleus.add_data_set::<Position>()?;
+    let velocities = nucleus.add_data_set::<Velocity>()?;
+
+    let entity = nucleus.spawn()?;
+
+    positions.get()?.insert(entity, Position(0));
+    velocities.get()?.insert(entity, Velocity(0));
+
+    let backward = nucleus.query::<(Write<Velocity>, Write<Position>)>()?;
+    let thread_nucleus = nucleus.clone();
+
+    let thread = std::thread::spawn(move || {
+        let forward = thread_nucleus.query::<(Write<Position>, Write<Velocity>)>().unwrap();
+
+        for _ in 0..10000 {
+            forward.for_each(|_, (position, velocity)| {
+                position.0 += 1;
+                velocity.0 += 1;
+            }).unwrap();
+        }
+    });
+
+    for _ in 0..10000 {
+        backward.for_each(|_, (velocity, position)| {
+            position.0 += 1;
+            velocity.0 += 1;
+        })?;
+    }
+
+    thread.join().unwrap();
+
+    assert_eq!(positions.get()?.get(entity).map(|position| position.0), Some(20000));
+    assert_eq!(velocities.get()?.get(entity).map(|velocity| velocity.0), Some(20000));
+
+    Ok(())
+}
+
+#[test]
+fn test_nucleus_poisoned_set() -> Result<(), EcsError> {
+    let nucleus = Nucleus::new("test");
+    let positions = nucleus.add_data_set::<Position>()?;
+    let velocities = nucleus.add_data_set::<Velocity>()?;
+
+    let entity = nucleus.spawn()?;
+
+    positions.get()?.insert(entity, Position(0));
+    velocities.get()?.insert(entity, Velocity(0));
+
+    let thread_positions = positions.clone();
+
+    std::thread::spawn(move || {
+        let _positions = thread_positions.get().unwrap();
+
+        panic!("poison the positions");
+    }).join().unwrap_err();
+
+    // Every get fails instead of panicking, and despawn still clears the
+    // sets it can reach.
+    assert!(matches!(positions.get(), Err(EcsError::Poisoned { .. })));
+    assert!(matches!(
+        nucleus.query::<(Read<Position>,)>()?.for_each(|_, _| ()),
+        Err(EcsError::Poisoned { .. })
+    ));
+    assert!(matches!(nucleus.despawn(entity), Err(EcsError::Poisoned { .. })));
+    assert!(velocities.get()?.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_nucleus_access_tracking() -> Result<(), EcsError> {
+    let nucleus = Nucleus::new("test");
+    let positions = nucleus.add_data_set::<Position>()?;
+
+    drop(positions.get()?);
+
+    assert_eq!(nucleus.access_report()?.datas[0].gets(), 0);
+
+    nucleus.set_access_tracking(true)?;
+
+    // Data added after it's turned on is tracked too.
+    let velocities = nucleus.add_data_set::<Velocity>()?;
+
+    drop(positions.get()?);
+    drop(velocities.get()?);
+
+    assert!(nucleus.access_report()?.datas.iter().all(|data| data.gets() == 1));
+
+    Ok(())
+}
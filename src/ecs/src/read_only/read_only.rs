@@ -0,0 +1,243 @@
+use as_any::AsAny;
+use data_set::DataSetTrait;
+use data_trait::DataTrait;
+use ecs_error::EcsError;
+use entity::Entity;
+use std::{
+    any::Any,
+    cell::RefCell,
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+};
+
+/// The current value, shared by every handle to it.
+struct Swappable<V> {
+    /// Bumped on every swap, so handles can tell their value is stale without
+    /// taking the lock.
+    generation: AtomicU64,
+    /// Held while a swap makes the new value, so swaps don't lose each other's
+    /// changes. It guards nothing, so a panic while making a value leaves
+    /// nothing half changed and the poison can be ignored.
+    swapping: Mutex<()>,
+    /// Only ever held to clone or replace the Arc, never while running other
+    /// code, so it can't be poisoned part way through a change either.
+    current: Mutex<Arc<V>>,
+}
+
+/// A handle that keeps the value it last saw.
+struct Cached<V> {
+    shared: Arc<Swappable<V>>,
+    cached: RefCell<(u64, Arc<V>)>,
+}
+
+impl<V> Cached<V> {
+    fn new(value: V) -> Self {
+        let value = Arc::new(value);
+
+        Self {
+            shared: Arc::new(Swappable {
+                generation: AtomicU64::new(0),
+                swapping: Mutex::new(()),
+                current: Mutex::new(value.clone()),
+            }),
+            cached: RefCell::new((0, value)),
+        }
+    }
+
+    fn get(&self) -> Arc<V> {
+        let generation = self.shared.generation.load(Ordering::Acquire);
+        let mut cached = self.cached.borrow_mut();
+
+        if cached.0 != generation {
+            let current = self.current();
+
+            *cached = (self.shared.generation.load(Ordering::Acquire), current.clone());
+        }
+
+        cached.1.clone()
+    }
+
+    fn current(&self) -> MutexGuard<'_, Arc<V>> {
+        self.shared.current.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Replace the value with whatever update makes from the current one,
+    /// without letting another swap in between. Returns the old value.
+    ///
+    /// Update runs without the current value locked, so readers aren't held
+    /// up by it and a panic in it doesn't poison the value.
+    fn swap_with(&self, update: impl FnOnce(&Arc<V>) -> Arc<V>) -> Arc<V> {
+        let _swapping = self.shared.swapping.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let current = self.current().clone();
+        let new = update(&current);
+        let old = std::mem::replace(&mut *self.current(), new);
+
+        self.shared.generation.fetch_add(1, Ordering::AcqRel);
+
+        old
+    }
+}
+
+impl<V> Clone for Cached<V> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+            cached: RefCell::new(self.cached.borrow().clone()),
+        }
+    }
+}
+
+/// Data that's loaded once and then only read, like config files, sprites and
+/// meshes. Reads hand out an Arc rather than a lock guard, so any number of
+/// updaters can hold the data at once, and each handle only takes a lock the
+/// first time it reads after the data was swapped.
+///
+/// When an asset reloads, [ReadOnlySingleton::swap] replaces it for every
+/// handle at once. Anyone still holding the old Arc keeps reading the old
+/// data until they get it again.
+///
+/// Handles are for one thread each, clone one from the Nucleus per updater.
+/// The data is shared between threads, so it has to be Send and Sync.
+pub struct ReadOnlySingleton<T: DataTrait + Send + Sync> {
+    data: Cached<T>,
+}
+
+impl<T> ReadOnlySingleton<T> where T: DataTrait + Send + Sync {
+    pub fn new(data: T) -> Self {
+        Self {
+            data: Cached::new(data),
+        }
+    }
+
+    pub fn get(&self) -> Arc<T> {
+        self.data.get()
+    }
+
+    /// Returns the data it replaced.
+    pub fn swap(&self, data: T) -> Arc<T> {
+        self.swap_arc(Arc::new(data))
+    }
+
+    pub fn swap_arc(&self, data: Arc<T>) -> Arc<T> {
+        self.data.swap_with(|_| data)
+    }
+}
+
+impl<T> Clone for ReadOnlySingleton<T> where T: DataTrait + Send + Sync {
+    fn clone(&self) -> Self {
+        Self {
+            data: self.data.clone(),
+        }
+    }
+}
+
+impl<T> AsAny for ReadOnlySingleton<T> where T: DataTrait + Send + Sync {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// A [ReadOnlySingleton] for many entities. Changes copy the map of Arcs
+/// rather than the data, then swap it in, so readers never see a change half
+/// made.
+pub struct ReadOnlySet<T: DataTrait + Send + Sync> {
+    datas: Cached<BTreeMap<Entity, Arc<T>>>,
+}
+
+impl<T> Default for ReadOnlySet<T> where T: DataTrait + Send + Sync {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> ReadOnlySet<T> where T: DataTrait + Send + Sync {
+    pub fn new() -> Self {
+        Self {
+            datas: Cached::new(BTreeMap::new()),
+        }
+    }
+
+    /// The whole set as of now.
+    pub fn get_all(&self) -> Arc<BTreeMap<Entity, Arc<T>>> {
+        self.datas.get()
+    }
+
+    pub fn get(&self, entity: Entity) -> Option<Arc<T>> {
+        self.datas.get().get(&entity).cloned()
+    }
+
+    /// Add or replace the entity's data. Returns its previous data.
+    pub fn swap(&self, entity: Entity, data: T) -> Option<Arc<T>> {
+        let mut previous = None;
+
+        self.update(|datas| previous = datas.insert(entity, Arc::new(data)));
+
+        previous
+    }
+
+    pub fn remove(&self, entity: Entity) -> Option<Arc<T>> {
+        let mut previous = None;
+
+        self.update(|datas| previous = datas.remove(&entity));
+
+        previous
+    }
+
+    /// Make any number of changes to the set, swapped in all at once.
+    pub fn update(&self, update: impl FnOnce(&mut BTreeMap<Entity, Arc<T>>)) {
+        self.datas.swap_with(|datas| {
+            let mut datas = BTreeMap::clone(datas);
+
+            update(&mut datas);
+
+            Arc::new(datas)
+        });
+    }
+}
+
+impl<T> Clone for ReadOnlySet<T> where T: DataTrait + Send + Sync {
+    fn clone(&self) -> Self {
+        Self {
+            datas: self.datas.clone(),
+        }
+    }
+}
+
+impl<T> DataSetTrait for ReadOnlySet<T> where T: DataTrait + Send + Sync {
+    fn remove_entity(&self, entity: Entity) -> Result<(), EcsError> {
+        if self.get(entity).is_some() {
+            self.remove(entity);
+        }
+
+        Ok(())
+    }
+
+    fn lock_key(&self) -> usize {
+        Arc::as_ptr(&self.datas.shared) as *const () as usize
+    }
+
+    fn data_name(&self) -> &'static str {
+        std::any::type_name::<T>()
+    }
+
+    /// Doesn't lock anything, the check reads the set as of now.
+    fn lock_contains(&self) -> Result<Box<dyn Fn(Entity) -> bool + '_>, EcsError> {
+        let datas = self.get_all();
+
+        Ok(Box::new(move |entity| datas.contains_key(&entity)))
+    }
+
+    fn clone_box(&self) -> Box<dyn DataSetTrait> {
+        Box::new(self.clone())
+    }
+}
+
+impl<T> AsAny for ReadOnlySet<T> where T: DataTrait + Send + Sync {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
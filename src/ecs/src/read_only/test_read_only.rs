@@ -0,0 +1,86 @@
+use data_set::DataSetTrait;
+use data_storage::BTreeMapStorage;
+use data_trait::DataTrait;
+use entity::Entity;
+use read_only::*;
+
+#[derive(Debug, PartialEq)]
+struct Config {
+    speed: u32,
+}
+
+impl DataTrait for Config {
+    type Storage = BTreeMapStorage<Self>;
+}
+
+#[test]
+fn test_read_only_singleton_swap() {
+    let config = ReadOnlySingleton::new(Config { speed: 1 });
+    let other = config.clone();
+
+    let held = other.get();
+
+    assert_eq!(config.swap(Config { speed: 2 }).speed, 1);
+
+    // Every handle sees the swap, anyone holding the old Arc keeps it.
+    assert_eq!(other.get().speed, 2);
+    assert_eq!(config.get().speed, 2);
+    assert_eq!(held.speed, 1);
+
+    let thread_config = config.clone();
+
+    std::thread::spawn(move || {
+        thread_config.swap(Config { speed: 3 });
+    }).join().unwrap();
+
+    assert_eq!(other.get().speed, 3);
+}
+
+#[test]
+fn test_read_only_set_swap() {
+    let configs = ReadOnlySet::<Config>::new();
+    let other = configs.clone();
+    let first = Entity::new(0, 0, 0);
+    let second = Entity::new(0, 1, 0);
+
+    assert_eq!(configs.swap(first, Config { speed: 1 }), None);
+
+    let before = other.get_all();
+
+    configs.update(|datas| {
+        datas.remove(&first);
+        datas.insert(second, std::sync::Arc::new(Config { speed: 2 }));
+    });
+
+    // Changes land all at once, and earlier snapshots don't change.
+    assert_eq!(before.len(), 1);
+    assert!(before.contains_key(&first));
+    assert_eq!(other.get(first), None);
+    assert_eq!(other.get(second).map(|config| config.speed), Some(2));
+
+    other.remove_entity(second).unwrap();
+
+    assert!(configs.get_all().is_empty());
+    assert_eq!(configs.remove(second), None);
+}
+
+#[test]
+fn test_read_only_set_update_panics() {
+    let configs = ReadOnlySet::<Config>::new();
+    let first = Entity::new(0, 0, 0);
+
+    configs.swap(first, Config { speed: 1 });
+
+    let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        configs.update(|datas| {
+            datas.clear();
+            panic!("update failed");
+        });
+    }));
+
+    // Nothing from the failed update lands, and the set still works.
+    assert!(panicked.is_err());
+    assert_eq!(configs.get(first).map(|config| config.speed), Some(1));
+    assert_eq!(configs.swap(first, Config { speed: 2 }).map(|config| config.speed), Some(1));
+    assert_eq!(configs.clone().get(first).map(|config| config.speed), Some(2));
+}
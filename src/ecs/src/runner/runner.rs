@@ -1,63 +1,399 @@
-use nucleus::Nucleus;
-use function::UpdaterTrait;
+use nucleus::{Nucleus, Query, QueryParams, RunnerCommands, UpdaterCommand};
+use function::{UpdateRate, UpdaterId, UpdaterTrait};
 use data_trait::DataTrait;
 use data_singleton::DataSingleton;
 use data_set::DataSet;
+use read_only::{ReadOnlySet, ReadOnlySingleton};
+use ecs_error::EcsError;
+use access_tracker::with_access_context;
 use std::{
-    time::Duration,
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
+/// An updater in a Runner, along with what it needs to be scheduled.
+struct ScheduledUpdater {
+    id: UpdaterId,
+    after: Vec<UpdaterId>,
+    before: Vec<UpdaterId>,
+    rate: UpdateRate,
+    /// When an [UpdateRate::Hz] updater is next due. None before its first update.
+    next_due: Option<Instant>,
+    enabled: bool,
+    updater: Box<dyn UpdaterTrait>,
+}
+
+impl ScheduledUpdater {
+    fn is_due(&mut self, tick: u64, now: Instant) -> bool {
+        match self.rate {
+            UpdateRate::EveryTick => true,
+            UpdateRate::EveryTicks(ticks) => tick % ticks as u64 == 0,
+            UpdateRate::Hz(hz) => {
+                if self.next_due.is_some_and(|next_due| now < next_due) {
+                    return false;
+                }
+
+                // Checked to fit by check_rate.
+                let period = Duration::from_secs_f64(1.0 / hz);
+
+                // Keep to the rate, unless it fell a whole period behind.
+                self.next_due = match self.next_due {
+                    Some(next_due) if now < next_due + period => Some(next_due + period),
+                    _ => Some(now + period),
+                };
+
+                true
+            }
+        }
+    }
+}
+
 pub struct Runner {
     nucleus: Nucleus,
-    /// TODO(UpdaterSchedulingAndDependencies)
-    functions: Vec<Box<dyn UpdaterTrait>>,
+    commands: RunnerCommands,
+    /// The runner's name, to count gets against, see [Nucleus::access_report].
+    name: Arc<str>,
+    /// In the order they update, see [Runner::add_updater].
+    updaters: Vec<ScheduledUpdater>,
+    timestep: Option<Duration>,
+    tick: u64,
 }
 
 impl Runner {
-    pub fn new(name: impl Into<String>, nucleus: Nucleus) -> Self {
+    /// Runners are named in the Nucleus, so other threads can send commands
+    /// to their updaters, see [Nucleus::disable_updater].
+    pub fn new(name: impl Into<String>, nucleus: Nucleus) -> Result<Self, EcsError> {
+        Ok(Self::with_commands(nucleus.add_runner(name)?))
+    }
+
+    fn with_commands(commands: RunnerCommands) -> Self {
         Self {
-            nucleus,
-            functions: Vec::new(),
+            nucleus: commands.nucleus().clone(),
+            name: Arc::from(commands.name()),
+            commands,
+            updaters: Vec::new(),
+            timestep: None,
+            tick: 0,
         }
     }
 
-    pub fn update(&self) {
-        for function in self.functions.iter() {
-            function.update();
+    /// Make a runner on its own thread, owned by the Nucleus. setup adds its
+    /// updaters on that thread, then the runner runs until it's stopped.
+    /// Errors from setup and panics are returned by [Nucleus::join_runner]
+    /// and [Nucleus::shutdown].
+    pub fn spawn<F>(nucleus: &Nucleus, name: impl Into<String>, setup: F) -> Result<(), EcsError>
+    where
+        F: FnOnce(&mut Runner) -> Result<(), EcsError> + Send + 'static,
+    {
+        // Added here rather than on the thread, so the runner can be sent
+        // commands as soon as this returns.
+        let commands = nucleus.add_runner(name)?;
+        let name = commands.name().to_string();
+
+        nucleus.spawn_runner_thread(&name, move || {
+            let mut runner = Runner::with_commands(commands);
+
+            setup(&mut runner)?;
+            runner.run()
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        self.commands.name()
+    }
+
+    /// Have [Runner::run] start a tick every timestep, sleeping off whatever
+    /// time is left after the updaters. A tick that runs long pushes the next
+    /// ones back rather than running extra ticks to catch up. A builder type
+    /// method.
+    pub fn with_timestep(mut self, timestep: Duration) -> Self {
+        self.set_timestep(Some(timestep));
+
+        self
+    }
+
+    /// See [Runner::with_timestep]. None runs ticks back to back.
+    pub fn set_timestep(&mut self, timestep: Option<Duration>) {
+        self.timestep = timestep;
+    }
+
+    /// Apply the commands sent through the Nucleus, then update each enabled
+    /// updater that's due this tick, in order.
+    pub fn update(&mut self) -> Result<(), EcsError> {
+        self.apply_commands()?;
+
+        let now = Instant::now();
+
+        for scheduled in self.updaters.iter_mut().filter(|scheduled| scheduled.enabled) {
+            if scheduled.is_due(self.tick, now) {
+                with_access_context(&self.name, self.tick, scheduled.id.name(), || scheduled.updater.update());
+            }
         }
+
+        self.tick += 1;
+
+        Ok(())
     }
 
-    pub fn run(&self) {
+    /// Update until the runner is stopped through the Nucleus, waiting
+    /// while it's parked.
+    pub fn run(&mut self) -> Result<(), EcsError> {
+        self.commands.attach_thread()?;
+
+        let mut next_tick = Instant::now();
+
         loop {
-            self.update();
+            self.commands.wait_while_parked();
+
+            if self.commands.is_stopped() {
+                return Ok(());
+            }
+
+            self.update()?;
+
+            let Some(timestep) = self.timestep else {
+                continue;
+            };
+
+            next_tick += timestep;
+
+            let now = Instant::now();
+
+            if now < next_tick {
+                self.commands.sleep_until(next_tick);
+            } else {
+                next_tick = now;
+            }
         }
     }
 
-    pub fn register_updater<T: UpdaterTrait>(&mut self) {
-        T::register(self.nucleus.clone());
+    /// How many ticks have been updated.
+    pub fn ticks(&self) -> u64 {
+        self.tick
+    }
+
+    pub fn register_updater<T: UpdaterTrait>(&mut self) -> Result<(), EcsError> {
+        T::register(self.nucleus.clone()).map_err(updater_error::<T>)
     }
 
-    pub fn add_updater<T: UpdaterTrait>(&mut self) {
+    /// Add the updater, placing it after the updaters in its
+    /// [UpdaterTrait::after] and before the ones in its [UpdaterTrait::before],
+    /// and likewise for the ones already added. Otherwise updaters keep the
+    /// order they're added in.
+    pub fn add_updater<T: UpdaterTrait>(&mut self) -> Result<(), EcsError> {
+        let id = UpdaterId::of::<T>();
+
+        if self.updaters.iter().any(|scheduled| scheduled.id == id) {
+            return Err(EcsError::UpdaterExists { updater: id.name() });
+        }
 
-        self.functions.push(Box::new(T::new(self.nucleus.clone())));
+        check_rate(id, T::rate())?;
+
+        let updater = T::new(self.nucleus.clone()).map_err(updater_error::<T>)?;
+
+        self.updaters.push(ScheduledUpdater {
+            id,
+            after: T::after(),
+            before: T::before(),
+            rate: T::rate(),
+            next_due: None,
+            enabled: true,
+            updater: Box::new(updater),
+        });
+
+        if let Err(error) = self.schedule() {
+            self.updaters.pop();
+
+            return Err(error);
+        }
+
+        self.commands.set_updaters(self.updater_names())
     }
 
-    pub fn add_data_singleton<T: DataTrait>(&self, data: T) -> DataSingleton<T> {
+    fn apply_commands(&mut self) -> Result<(), EcsError> {
+        for job in self.commands.receive_jobs() {
+            job(self);
+        }
+
+        let mut removed = false;
+
+        for (updater, command) in self.commands.receive() {
+            let Some(index) = self.updaters.iter().position(|scheduled| scheduled.id.name() == updater) else {
+                // Removed by an earlier command.
+                continue;
+            };
+
+            match command {
+                UpdaterCommand::Enable => self.updaters[index].enabled = true,
+                UpdaterCommand::Disable => self.updaters[index].enabled = false,
+                UpdaterCommand::Remove => {
+                    self.updaters.remove(index);
+                    removed = true;
+                }
+            }
+        }
+
+        if removed {
+            self.commands.set_updaters(self.updater_names())?;
+        }
+
+        Ok(())
+    }
+
+    /// Drop the updater straight away, rather than at the start of the next
+    /// tick like [Nucleus::remove_updater].
+    pub fn remove_updater(&mut self, updater: &str) -> Result<(), EcsError> {
+        let index = self.updaters.iter()
+            .position(|scheduled| scheduled.id.name() == updater)
+            .ok_or_else(|| EcsError::UpdaterDoesNotExist { updater: updater.to_string() })?;
+
+        self.updaters.remove(index);
+        self.commands.set_updaters(self.updater_names())
+    }
+
+    /// False while the updater is disabled, None if it isn't in the runner.
+    pub fn is_enabled(&self, updater: &str) -> Option<bool> {
+        self.updaters.iter()
+            .find(|scheduled| scheduled.id.name() == updater)
+            .map(|scheduled| scheduled.enabled)
+    }
+
+    /// Change how often the updater updates from its [UpdaterTrait::rate].
+    pub fn set_rate<T: UpdaterTrait>(&mut self, rate: UpdateRate) -> Result<(), EcsError> {
+        let id = UpdaterId::of::<T>();
+
+        let scheduled = self.updaters.iter_mut()
+            .find(|scheduled| scheduled.id == id)
+            .ok_or_else(|| EcsError::UpdaterDoesNotExist { updater: id.name().to_string() })?;
+
+        check_rate(id, rate)?;
+
+        scheduled.rate = rate;
+        scheduled.next_due = None;
+
+        Ok(())
+    }
+
+    /// The updaters in the order they update.
+    pub fn updater_ids(&self) -> Vec<UpdaterId> {
+        self.updaters.iter().map(|scheduled| scheduled.id).collect()
+    }
+
+    fn updater_names(&self) -> Vec<&'static str> {
+        self.updaters.iter().map(|scheduled| scheduled.id.name()).collect()
+    }
+
+    /// Sort the updaters so each comes after everything it has to, keeping
+    /// them in their current order where nothing says otherwise.
+    fn schedule(&mut self) -> Result<(), EcsError> {
+        let ids = self.updater_ids();
+        let index_of = |id: &UpdaterId| ids.iter().position(|other| other == id);
+
+        // waits_on[i] are the indices that have to update before i.
+        let mut waits_on: Vec<Vec<usize>> = vec![Vec::new(); ids.len()];
+
+        for (index, scheduled) in self.updaters.iter().enumerate() {
+            for after in scheduled.after.iter().filter_map(index_of) {
+                waits_on[index].push(after);
+            }
+
+            for before in scheduled.before.iter().filter_map(index_of) {
+                waits_on[before].push(index);
+            }
+        }
+
+        let mut order = Vec::with_capacity(ids.len());
+        let mut placed = vec![false; ids.len()];
+
+        while order.len() < ids.len() {
+            let next = (0..ids.len()).find(|index| {
+                !placed[*index] && waits_on[*index].iter().all(|waiting_on| placed[*waiting_on])
+            });
+
+            match next {
+                Some(index) => {
+                    placed[index] = true;
+                    order.push(index);
+                }
+                None => {
+                    return Err(EcsError::UpdaterCycle {
+                        updaters: (0..ids.len()).filter(|index| !placed[*index]).map(|index| ids[index].name()).collect(),
+                    });
+                }
+            }
+        }
+
+        let mut updaters: Vec<Option<ScheduledUpdater>> = self.updaters.drain(..).map(Some).collect();
+
+        self.updaters = order.into_iter().map(|index| updaters[index].take().unwrap()).collect();
+
+        Ok(())
+    }
+
+    pub fn add_data_singleton<T: DataTrait>(&self, data: T) -> Result<DataSingleton<T>, EcsError> {
         self.nucleus.add_data_singleton::<T>(data)
     }
 
-    /// TODO(MakeFallible)
-    pub fn get_data_singleton<T: DataTrait>(&self) -> DataSingleton<T> {
+    pub fn get_data_singleton<T: DataTrait>(&self) -> Result<DataSingleton<T>, EcsError> {
         self.nucleus.get_data_singleton::<T>()
     }
 
-    /// TODO(MakeFallible)
-    pub fn add_data_set<T: DataTrait>(&self) -> DataSet<T> {
+    pub fn add_data_set<T: DataTrait>(&self) -> Result<DataSet<T>, EcsError> {
         self.nucleus.add_data_set::<T>()
     }
 
-    /// TODO(MakeFallible)
-    pub fn get_data_set<T: DataTrait>(&self) -> DataSet<T> {
+    pub fn get_data_set<T: DataTrait>(&self) -> Result<DataSet<T>, EcsError> {
         self.nucleus.get_data_set::<T>()
     }
+
+    pub fn add_read_only_singleton<T: DataTrait + Send + Sync>(&self, data: T) -> Result<ReadOnlySingleton<T>, EcsError> {
+        self.nucleus.add_read_only_singleton::<T>(data)
+    }
+
+    pub fn get_read_only_singleton<T: DataTrait + Send + Sync>(&self) -> Result<ReadOnlySingleton<T>, EcsError> {
+        self.nucleus.get_read_only_singleton::<T>()
+    }
+
+    pub fn add_read_only_set<T: DataTrait + Send + Sync>(&self) -> Result<ReadOnlySet<T>, EcsError> {
+        self.nucleus.add_read_only_set::<T>()
+    }
+
+    pub fn get_read_only_set<T: DataTrait + Send + Sync>(&self) -> Result<ReadOnlySet<T>, EcsError> {
+        self.nucleus.get_read_only_set::<T>()
+    }
+
+    /// See [Query].
+    pub fn query<P: QueryParams>(&self) -> Result<Query<P>, EcsError> {
+        self.nucleus.query::<P>()
+    }
+}
+
+/// Hz rates become a period, which has to be positive, finite and small enough
+/// to be added to an Instant. Tick rates can't be every 0 ticks.
+fn check_rate(id: UpdaterId, rate: UpdateRate) -> Result<(), EcsError> {
+    let valid = match rate {
+        UpdateRate::EveryTick => true,
+        UpdateRate::EveryTicks(ticks) => ticks > 0,
+        // The next due time can be up to two periods out, see is_due.
+        UpdateRate::Hz(hz) => hz.is_finite()
+            && hz > 0.0
+            && Duration::try_from_secs_f64(1.0 / hz)
+                .ok()
+                .and_then(|period| period.checked_mul(2))
+                .is_some_and(|period| Instant::now().checked_add(period).is_some()),
+    };
+
+    if valid {
+        Ok(())
+    } else {
+        Err(EcsError::InvalidUpdateRate { updater: id.name(), rate: format!("{:?}", rate) })
+    }
+}
+
+/// Name the updater whose register or new failed.
+fn updater_error<T: UpdaterTrait>(error: EcsError) -> EcsError {
+    EcsError::Updater {
+        updater: std::any::type_name::<T>(),
+        error: Box::new(error),
+    }
 }
\ No newline at end of file
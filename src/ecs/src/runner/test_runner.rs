@@ -0,0 +1,275 @@
+use data_singleton::DataSingleton;
+use data_storage::BTreeMapStorage;
+use data_trait::DataTrait;
+use ecs_error::EcsError;
+use function::*;
+use nucleus::Nucleus;
+use runner::*;
+
+/// The updaters that updated, in order.
+#[derive(Default)]
+struct Log(Vec<&'static str>);
+
+impl DataTrait for Log {
+    type Storage = BTreeMapStorage<Self>;
+}
+
+macro_rules! updater {
+    ($name:ident, after: [$($after:ty),*], before: [$($before:ty),*], rate: $rate:expr) => {
+        struct $name {
+            log: DataSingleton<Log>,
+        }
+
+        impl UpdaterTrait for $name {
+            fn register(_nucleus: Nucleus) -> Result<(), EcsError> {
+                Ok(())
+            }
+
+            fn new(nucleus: Nucleus) -> Result<Self, EcsError> {
+                Ok(Self {
+                    log: nucleus.get_data_singleton::<Log>()?,
+                })
+            }
+
+            fn update(&self) {
+                if let Ok(mut log) = self.log.get() {
+                    log.0.push(stringify!($name));
+                }
+            }
+
+            fn after() -> Vec<UpdaterId> {
+                vec![$(UpdaterId::of::<$after>()),*]
+            }
+
+            fn before() -> Vec<UpdaterId> {
+                vec![$(UpdaterId::of::<$before>()),*]
+            }
+
+            fn rate() -> UpdateRate {
+                $rate
+            }
+        }
+    };
+}
+
+updater!(Input, after: [], before: [Physics], rate: UpdateRate::EveryTick);
+updater!(Physics, after: [], before: [], rate: UpdateRate::EveryTick);
+updater!(Render, after: [Physics], before: [], rate: UpdateRate::EveryTick);
+updater!(First, after: [Second], before: [], rate: UpdateRate::EveryTick);
+updater!(Second, after: [First], before: [], rate: UpdateRate::EveryTick);
+updater!(EveryThird, after: [], before: [], rate: UpdateRate::EveryTicks(3));
+updater!(OncePerSecond, after: [], before: [], rate: UpdateRate::Hz(1.0));
+updater!(Never, after: [], before: [], rate: UpdateRate::Hz(0.0));
+
+fn runner() -> Result<(Runner, DataSingleton<Log>), EcsError> {
+    let nucleus = Nucleus::new("test");
+    let log = nucleus.add_data_singleton(Log::default())?;
+
+    Ok((Runner::new("test", nucleus)?, log))
+}
+
+#[test]
+fn test_runner_order() -> Result<(), EcsError> {
+    let (mut runner, log) = runner()?;
+
+    runner.add_updater::<Render>()?;
+    runner.add_updater::<Physics>()?;
+    runner.add_updater::<Input>()?;
+
+    assert_eq!(
+        runner.updater_ids(),
+        [UpdaterId::of::<Input>(), UpdaterId::of::<Physics>(), UpdaterId::of::<Render>()]
+    );
+
+    runner.update()?;
+
+    assert_eq!(log.get()?.0, ["Input", "Physics", "Render"]);
+    assert!(matches!(runner.add_updater::<Physics>(), Err(EcsError::UpdaterExists { .. })));
+
+    Ok(())
+}
+
+#[test]
+fn test_runner_cycle() -> Result<(), EcsError> {
+    let (mut runner, _) = runner()?;
+
+    runner.add_updater::<First>()?;
+
+    match runner.add_updater::<Second>() {
+        Err(EcsError::UpdaterCycle { updaters }) => assert_eq!(updaters.len(), 2),
+        result => panic!("expected a cycle, got {:?}", result),
+    }
+
+    // The updater that made the cycle isn't kept.
+    assert_eq!(runner.updater_ids(), [UpdaterId::of::<First>()]);
+
+    Ok(())
+}
+
+#[test]
+fn test_runner_rates() -> Result<(), EcsError> {
+    let (mut runner, log) = runner()?;
+
+    runner.add_updater::<EveryThird>()?;
+    runner.add_updater::<OncePerSecond>()?;
+
+    for _ in 0..7 {
+        runner.update()?;
+    }
+
+    assert_eq!(runner.ticks(), 7);
+    assert_eq!(log.get()?.0.iter().filter(|name| **name == "EveryThird").count(), 3);
+    assert_eq!(log.get()?.0.iter().filter(|name| **name == "OncePerSecond").count(), 1);
+
+    runner.set_rate::<OncePerSecond>(UpdateRate::EveryTick)?;
+    log.get()?.0.clear();
+
+    for _ in 0..2 {
+        runner.update()?;
+    }
+
+    assert_eq!(log.get()?.0, ["OncePerSecond", "OncePerSecond"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_runner_invalid_rates() -> Result<(), EcsError> {
+    let (mut runner, _) = runner()?;
+
+    assert!(matches!(runner.add_updater::<Never>(), Err(EcsError::InvalidUpdateRate { .. })));
+
+    runner.add_updater::<Physics>()?;
+
+    // 1e-20 Hz is a period too long for a Duration, 1e-19 Hz one too long to
+    // schedule from an Instant.
+    for rate in [0.0, -1.0, f64::NAN, f64::INFINITY, 1e-20, 1e-19].map(UpdateRate::Hz).into_iter().chain([UpdateRate::EveryTicks(0)]) {
+        assert!(matches!(runner.set_rate::<Physics>(rate), Err(EcsError::InvalidUpdateRate { .. })), "{:?}", rate);
+    }
+
+    assert!(matches!(
+        runner.set_rate::<Render>(UpdateRate::EveryTick),
+        Err(EcsError::UpdaterDoesNotExist { .. })
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_runner_commands() -> Result<(), EcsError> {
+    let nucleus = Nucleus::new("test");
+    let log = nucleus.add_data_singleton(Log::default())?;
+    let mut runner = Runner::new("test", nucleus.clone())?;
+    let physics = UpdaterId::of::<Physics>().name();
+
+    runner.add_updater::<Physics>()?;
+    runner.add_updater::<Render>()?;
+
+    assert_eq!(nucleus.runner_names()?, ["test"]);
+    assert_eq!(nucleus.updater_names("test")?, [physics, UpdaterId::of::<Render>().name()]);
+
+    // Commands apply at the start of the runner's next tick.
+    nucleus.disable_updater("test", physics)?;
+
+    assert_eq!(runner.is_enabled(physics), Some(true));
+
+    runner.update()?;
+
+    assert_eq!(runner.is_enabled(physics), Some(false));
+    assert_eq!(log.get()?.0, ["Render"]);
+
+    nucleus.enable_updater("test", physics)?;
+    nucleus.remove_updater("test", UpdaterId::of::<Render>().name())?;
+    runner.update()?;
+
+    assert_eq!(log.get()?.0, ["Render", "Physics"]);
+    assert_eq!(nucleus.updater_names("test")?, [physics]);
+
+    assert!(matches!(
+        nucleus.disable_updater("test", "missing"),
+        Err(EcsError::UpdaterDoesNotExist { .. })
+    ));
+    assert!(matches!(
+        nucleus.disable_updater("missing", physics),
+        Err(EcsError::RunnerDoesNotExist { .. })
+    ));
+
+    // Dropping the runner takes it out of the Nucleus.
+    drop(runner);
+
+    assert!(nucleus.runner_names()?.is_empty());
+
+    Ok(())
+}
+
+/// Wait for the runner thread to get through a few ticks.
+fn log_len_after_wait(log: &DataSingleton<Log>) -> Result<usize, EcsError> {
+    std::thread::sleep(std::time::Duration::from_millis(20));
+
+    Ok(log.get()?.0.len())
+}
+
+#[test]
+fn test_runner_threads() -> Result<(), EcsError> {
+    let nucleus = Nucleus::new("test");
+    let log = nucleus.add_data_singleton(Log::default())?;
+
+    Runner::spawn(&nucleus, "physics", |runner| {
+        runner.set_timestep(Some(std::time::Duration::from_millis(1)));
+        runner.add_updater::<Physics>()
+    })?;
+
+    assert!(log_len_after_wait(&log)? > 0);
+
+    nucleus.park_runner("physics")?;
+
+    // The tick in flight when it was parked can still finish.
+    let parked = log_len_after_wait(&log)?;
+
+    assert_eq!(log_len_after_wait(&log)?, parked);
+
+    nucleus.unpark_runner("physics")?;
+
+    assert!(log_len_after_wait(&log)? > parked);
+
+    nucleus.stop_runner("physics")?;
+    nucleus.join_runner("physics")?;
+
+    assert!(nucleus.runner_names()?.is_empty());
+    assert!(matches!(nucleus.join_runner("physics"), Err(EcsError::RunnerDoesNotExist { .. })));
+
+    Ok(())
+}
+
+#[test]
+fn test_runner_thread_errors() -> Result<(), EcsError> {
+    let nucleus = Nucleus::new("test");
+
+    Runner::spawn(&nucleus, "fails", |runner| runner.add_updater::<Physics>())?;
+    Runner::spawn(&nucleus, "panics", |_| panic!("setup panicked"))?;
+    Runner::spawn(&nucleus, "parked", |_| Ok(()))?;
+
+    nucleus.park_runner("parked")?;
+
+    assert!(matches!(
+        Runner::spawn(&nucleus, "parked", |_| Ok(())),
+        Err(EcsError::RunnerExists { .. })
+    ));
+
+    // Physics can't get the Log singleton, which was never added.
+    assert!(matches!(nucleus.join_runner("fails"), Err(EcsError::Updater { .. })));
+
+    match nucleus.join_runner("panics") {
+        Err(EcsError::RunnerPanicked { runner, message }) => {
+            assert_eq!(runner, "panics");
+            assert_eq!(message, "setup panicked");
+        }
+        result => panic!("expected a panic, got {:?}", result),
+    }
+
+    // Shutdown stops parked runners too.
+    assert!(nucleus.shutdown().is_empty());
+    assert!(nucleus.runner_names()?.is_empty());
+
+    Ok(())
+}
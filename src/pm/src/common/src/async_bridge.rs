@@ -0,0 +1,138 @@
+use pm::*;
+use std::{future::Future, time::Duration};
+use tokio::{
+    runtime::{Builder, Runtime},
+    task::{JoinHandle, LocalSet},
+};
+
+/// A current thread tokio runtime driven by the [AsyncBridge]. Doers spawn
+/// futures onto it and the bridge polls them for a bounded time each tick, so
+/// async clients can be used without blocking the Pm.
+///
+/// Futures run on the Pm's thread, so they don't have to be Send and can hold
+/// [State]. Futures spawned while the bridge is polling, from inside another
+/// future, should use tokio::task::spawn_local, since this state is borrowed
+/// at the time. Tokio timers and sockets need the runtime when they're made,
+/// so make them inside the spawned future rather than passing them in.
+#[derive(StateTrait)]
+pub struct AsyncRuntime {
+    runtime: Runtime,
+    local: LocalSet,
+    /// Futures spawned through this state that haven't finished.
+    tasks: Vec<JoinHandle<()>>,
+    /// The longest the bridge polls each tick while futures are pending. With
+    /// a zero budget, only futures that are ready to run get polled once.
+    pub poll_budget: Duration,
+}
+
+impl AsyncRuntime {
+    pub fn new() -> Result<Self, PmError> {
+        let runtime = Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|_| PmError::AddNewState)?;
+
+        Ok(Self {
+            runtime,
+            local: LocalSet::new(),
+            tasks: Vec::new(),
+            poll_budget: Duration::from_millis(1),
+        })
+    }
+
+    /// Run the future on the runtime, dropping its output.
+    pub fn spawn(&mut self, future: impl Future<Output = ()> + 'static) {
+        self.tasks.push(self.local.spawn_local(future));
+    }
+
+    /// Run the future on the runtime and hand its output to apply along with
+    /// the state once it finishes. Doers read the result from the state like
+    /// any other.
+    ///
+    /// runtime.spawn_into(&self.weather, client.forecast(), |weather, forecast| weather.forecast = forecast.ok())
+    pub fn spawn_into<T: StateTrait, F: Future + 'static>(
+        &mut self,
+        state: &State<T>,
+        future: F,
+        apply: impl FnOnce(&mut T, F::Output) + 'static,
+    ) {
+        let state = state.clone();
+
+        self.spawn(async move {
+            let output = future.await;
+
+            // Only the bridge runs while futures are polled, so the state can
+            // only be taken if a doer is holding on to it between ticks.
+            match state.get() {
+                Ok(mut state) => apply(&mut state, output),
+                Err(_) => log::error!(
+                    "dropping the output of a future, {} is already borrowed",
+                    std::any::type_name::<T>()
+                ),
+            }
+        });
+    }
+
+    /// Futures spawned through [Self::spawn] or [Self::spawn_into] that haven't
+    /// finished yet.
+    pub fn pending(&self) -> usize {
+        self.tasks.iter().filter(|task| !task.is_finished()).count()
+    }
+
+    /// Drive the futures for up to [Self::poll_budget], stopping early once
+    /// all of them finish.
+    pub fn poll(&mut self) {
+        self.tasks.retain(|task| !task.is_finished());
+
+        if self.tasks.is_empty() {
+            return;
+        }
+
+        // The budget's timer has to be made inside the runtime.
+        let _runtime = self.runtime.enter();
+
+        if self.poll_budget.is_zero() {
+            self.runtime
+                .block_on(self.local.run_until(tokio::task::yield_now()));
+        } else {
+            let tasks = &mut self.tasks;
+
+            let all_finished = async move {
+                for task in tasks.iter_mut() {
+                    if let Err(err) = task.await {
+                        log::error!("future spawned on the async runtime failed: {}", err);
+                    }
+                }
+            };
+
+            let _ = self.runtime.block_on(
+                self.local
+                    .run_until(tokio::time::timeout(self.poll_budget, all_finished)),
+            );
+        }
+
+        self.tasks.retain(|task| !task.is_finished());
+    }
+}
+
+/// Polls the [AsyncRuntime] in [Phase::PreUpdate], so outputs written into
+/// state by finished futures are seen by the other doers on the same tick.
+#[derive(Doer)]
+pub struct AsyncBridge {
+    #[pm(init = AsyncRuntime::new()?)]
+    runtime: State<AsyncRuntime>,
+}
+
+impl DoerTrait for AsyncBridge {
+    derived_doer!();
+
+    fn phases(&self) -> &'static [Phase] {
+        &[Phase::PreUpdate]
+    }
+
+    fn pre_update(&self) -> Result<(), PmError> {
+        self.runtime.get()?.poll();
+
+        Ok(())
+    }
+}
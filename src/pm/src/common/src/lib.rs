@@ -1,3 +1,4 @@
 pub mod thread_manager;
 pub mod loop_timing;
 pub mod logging;
+pub mod async_bridge;
@@ -8,38 +8,36 @@ pub struct LoopTiming {
     pub loop_sleep_duration: Duration,
 }
 
+#[derive(Doer)]
 pub struct LoopTimingManager {
+    #[pm(init = LoopTiming {
+        start_of_loop: Instant::now(),
+        desired_loop_duration: Duration::from_millis(100),
+        loop_sleep_duration: Duration::from_millis(100),
+    })]
     timing_data: State<LoopTiming>,
 }
 
 impl DoerTrait for LoopTimingManager {
-    fn new_state(state: &StateStore) -> Result<(), PmError>
-    where
-        Self: Sized,
-    {
-        let mut local_state = state.local.get()?;
+    derived_doer!();
 
-        local_state.add_state(LoopTiming {
-            start_of_loop: Instant::now(),
-            desired_loop_duration: Duration::from_millis(100),
-            loop_sleep_duration: Duration::from_millis(100),
-        })?;
-
-        Ok(())
+    fn phases(&self) -> &'static [Phase] {
+        &[Phase::PostUpdate, Phase::Paused]
     }
 
-    fn new(pm: &Pm) -> Result<Box<dyn DoerTrait>, PmError>
-    where
-        Self: Sized,
-    {
-        let local_state = pm.state.local.get()?;
+    /// Sleeps after every other doer's update so the whole loop is timed.
+    fn post_update(&self) -> Result<(), PmError> {
+        self.sleep_out_loop()
+    }
 
-        Ok(Box::new(Self {
-            timing_data: local_state.get_state::<LoopTiming>()?,
-        }))
+    /// Paused loops are timed too, so a paused Pm doesn't spin.
+    fn paused(&self) -> Result<(), PmError> {
+        self.sleep_out_loop()
     }
+}
 
-    fn update(&self) -> Result<(), PmError> {
+impl LoopTimingManager {
+    fn sleep_out_loop(&self) -> Result<(), PmError> {
         let mut timing_data = self.timing_data.get()?;
 
         let start_of_previous_loop =
@@ -39,46 +39,28 @@ impl ThreadStore {
         shared_state: SharedState<SharedStore>,
         thread_fn: impl FnOnce(SharedState<SharedStore>) -> Result<(), PmError> + Send + 'static,
     ) -> Result<(), PmError> {
-        self.join_handles
-            .push(std::thread::spawn(|| thread_fn(shared_state)));
+        // Threads that never build a Pm still show up in the trace.
+        self.join_handles.push(std::thread::spawn(|| {
+            attach_trace(&shared_state)?;
+            thread_fn(shared_state)
+        }));
 
         Ok(())
     }
 }
 
+#[derive(Doer)]
 pub struct ThreadManager {
+    #[pm(value = pm.state.shared.clone())]
     shared_state: SharedState<SharedStore>,
+    #[pm(init = ThreadRequest::new())]
     thread_requests: SharedState<ThreadRequest>,
+    #[pm(init = ThreadStore::new())]
     thread_store: State<ThreadStore>,
 }
 
 impl DoerTrait for ThreadManager {
-    fn new_state(state_store: &StateStore) -> Result<(), PmError>
-    where
-        Self: Sized,
-    {
-        let mut local_state = state_store.local.get()?;
-        let mut shared_state = state_store.shared.blocking_get()?;
-
-        local_state.add_state(ThreadStore::new())?;
-        shared_state.add_state(ThreadRequest::new())?;
-
-        Ok(())
-    }
-
-    fn new(pm: &Pm) -> Result<Box<dyn DoerTrait>, PmError>
-    where
-        Self: Sized,
-    {
-        let local_state = pm.state.local.get()?;
-        let shared_state = pm.state.shared.blocking_get()?;
-
-        Ok(Box::new(Self {
-            shared_state: pm.state.shared.clone(),
-            thread_store: local_state.get_state::<ThreadStore>()?,
-            thread_requests: shared_state.get_state::<ThreadRequest>()?,
-        }))
-    }
+    derived_doer!();
 
     fn update(&self) -> Result<(), PmError> {
         let mut thread_store = self.thread_store.get()?;
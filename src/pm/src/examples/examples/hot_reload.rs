@@ -0,0 +1,86 @@
+use std::{
+    cell::Cell,
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime},
+};
+
+use pm::*;
+use pm_common::loop_timing::LoopTimingManager;
+
+/// Watches the library file and asks the Pm to reload it when it changes.
+pub struct LibraryWatcher {
+    doer_state: State<DoerState>,
+    path: PathBuf,
+    last_modified: Cell<Option<SystemTime>>,
+    last_check: Cell<Instant>,
+}
+
+impl LibraryWatcher {
+    fn library_path() -> PathBuf {
+        let mut path = std::env::current_exe().unwrap_or_default();
+        path.set_file_name(format!(
+            "{}library{}",
+            std::env::consts::DLL_PREFIX,
+            std::env::consts::DLL_SUFFIX
+        ));
+        path
+    }
+
+    fn modified(&self) -> Option<SystemTime> {
+        std::fs::metadata(&self.path)
+            .and_then(|m| m.modified())
+            .ok()
+    }
+}
+
+impl DoerTrait for LibraryWatcher {
+    fn new(pm: &Pm) -> Result<Box<dyn DoerTrait>, PmError>
+    where
+        Self: Sized,
+    {
+        Ok(Box::new(Self {
+            doer_state: pm.doers.state.clone(),
+            path: Self::library_path(),
+            last_modified: Cell::new(None),
+            last_check: Cell::new(Instant::now()),
+        }))
+    }
+
+    fn first(&self, _pm: &Pm) -> Result<(), PmError> {
+        let mut doer_state = self.doer_state.get()?;
+
+        self.last_modified.set(self.modified());
+
+        doer_state
+            .message_queue
+            .push(DoerControlMessage::LoadLibrary(self.path.clone()));
+
+        Ok(())
+    }
+
+    fn update(&self) -> Result<(), PmError> {
+        if self.last_check.get().elapsed() < Duration::from_secs(1) {
+            return Ok(());
+        }
+
+        self.last_check.set(Instant::now());
+
+        let modified = self.modified();
+
+        if modified != self.last_modified.get() {
+            self.last_modified.set(modified);
+
+            self.doer_state
+                .get()?
+                .message_queue
+                .push(DoerControlMessage::ReloadLibrary(self.path.clone()));
+        }
+
+        Ok(())
+    }
+}
+
+fn main() -> Result<(), PmError> {
+    let mut pm = pm!(LoopTimingManager, LibraryWatcher);
+    pm.run()
+}
@@ -0,0 +1,53 @@
+//! A library of doers that the hot_reload example loads at runtime. Build it
+//! with `cargo build --example library`, then edit the message and rebuild while
+//! hot_reload is running.
+use pm::*;
+
+#[derive(StateTrait)]
+pub struct LibraryCounter {
+    pub count: u64,
+}
+
+pub struct LibraryDoer {
+    counter: State<LibraryCounter>,
+}
+
+impl DoerTrait for LibraryDoer {
+    fn new_state(state: &StateStore) -> Result<(), PmError>
+    where
+        Self: Sized,
+    {
+        let mut local_state = state.local.get()?;
+
+        local_state.add_state(LibraryCounter { count: 0 })
+    }
+
+    fn new(pm: &Pm) -> Result<Box<dyn DoerTrait>, PmError>
+    where
+        Self: Sized,
+    {
+        let local_state = pm.state.local.get()?;
+
+        Ok(Box::new(Self {
+            counter: local_state.get_state::<LibraryCounter>()?,
+        }))
+    }
+
+    fn update(&self) -> Result<(), PmError> {
+        let mut counter = self.counter.get()?;
+
+        counter.count += 1;
+
+        println!("Hello from the library, update {}.", counter.count);
+
+        Ok(())
+    }
+
+    fn remove(&self) -> Result<(), PmError> {
+        println!("LibraryDoer is being unloaded.");
+
+        Ok(())
+    }
+}
+
+pm_library!(LibraryDoer);
@@ -1,13 +1,7 @@
-#![feature(portable_simd)]
-
-use std::{
-    any::{type_name, TypeId},
-    time::{Duration, Instant},
-};
+use std::time::{Duration, Instant};
 
 use pm::*;
 use pm_common::{
-    // logging::{Logging, LoggingManager},
     loop_timing::{LoopTiming, LoopTimingManager},
     thread_manager::{ThreadManager, ThreadRequest},
 };
@@ -20,7 +14,7 @@ pub struct SomeSharedState {
 
 pub struct SharedDoer1 {
     shared_state: SharedState<SomeSharedState>,
-    doer_control: State<DoerControlMessage>,
+    doer_state: State<DoerState>,
     start_instant: Instant,
 }
 
@@ -48,26 +42,26 @@ impl DoerTrait for SharedDoer1 {
         Ok(Box::new(SharedDoer1 {
             shared_state: shared_state.get_state::<SomeSharedState>()?,
             start_instant: Instant::now(),
-            doer_control: pm.doers.control.clone(),
+            doer_state: pm.doers.state.clone(),
         }))
     }
 
     fn update(&self) -> Result<(), PmError> {
-        let shared_state = self.shared_state.blocking_get()?;
+        let mut shared_state = self.shared_state.blocking_get()?;
 
         shared_state.number += 1.0;
         shared_state.ready_for_work = true;
 
         if self.start_instant.elapsed() > Duration::from_secs(3) {
-            let mut doer_control = self.doer_control.get()?;
+            let mut doer_state = self.doer_state.get()?;
 
-            doer_control
+            doer_state
                 .message_queue
-                .push(DoerControlMessage::AddDoerToEnd(Box::new(SharedDoer2::new)));
+                .push(DoerControlMessage::AddToEnd(Box::new(SharedDoer2::new)));
 
-            doer_control
+            doer_state
                 .message_queue
-                .push(DoerControlMessage::RemoveDoer(type_name::<SharedDoer1>()));
+                .push(DoerControlMessage::Remove(self.name()));
         }
 
         Ok(())
@@ -75,14 +69,12 @@ impl DoerTrait for SharedDoer1 {
 }
 
 fn fun_thread(shared_state: SharedState<SharedStore>) -> Result<(), PmError> {
-    let mut pm = Pm::new(shared_state)?;
-    pm.doer::<LoopTimingManager>()?;
-    pm.run()
+    pm!(run shared_state; LoopTimingManager)
 }
 
 pub struct SharedDoer2 {
     shared_state: SharedState<SomeSharedState>,
-    timing: State<LoopTiming>,
+    _timing: State<LoopTiming>,
     thread_requests: SharedState<ThreadRequest>,
 }
 
@@ -102,19 +94,19 @@ impl DoerTrait for SharedDoer2 {
         );
 
         Ok(Box::new(SharedDoer2 {
-            timing: local_state.get_state::<LoopTiming>()?,
+            _timing: local_state.get_state::<LoopTiming>()?,
             shared_state: shared_state.get_state::<SomeSharedState>()?,
             thread_requests: shared_state.get_state::<ThreadRequest>()?,
         }))
     }
 
     fn first(&self, pm: &Pm) -> Result<(), PmError> {
-        let mut doer_control = pm.doers.control.get()?;
+        let mut doer_state = pm.doers.state.get()?;
         let mut thread_requests = self.thread_requests.blocking_get()?;
 
-        doer_control
+        doer_state
             .message_queue
-            .push(DoerControlMessage::RemoveDoer(TypeId::of::<SharedDoer2>()));
+            .push(DoerControlMessage::Remove(self.name()));
 
         thread_requests.add_thread(fun_thread);
 
@@ -134,6 +126,5 @@ impl DoerTrait for SharedDoer2 {
 }
 
 fn main() -> Result<(), PmError> {
-    let mut pm = pm!(LoopTimingManager, SharedDoer1, SharedDoer2, ThreadManager);
-    pm.run()
+    pm!(run LoopTimingManager, SharedDoer1, SharedDoer2, ThreadManager)
 }
@@ -5,7 +5,6 @@ use std::{
     collections::HashMap,
     io::{prelude::*, BufReader},
     net::{SocketAddr, TcpListener, TcpStream},
-    time::Duration,
 };
 
 /// TODO: returns boxed shared buffers for tcp streams to read or write into.
@@ -110,74 +109,98 @@ pub struct TcpConnectionsState {
     pub connections: HashMap<SocketAddr, BufferedTcpConnection>,
 }
 
-#[derive(StateTrait)]
-pub struct HttpRequests {
-    pub requests: Vec<HttpRequest>,
-}
-
-#[derive(Debug)]
 pub struct HttpRequest {
-    pub keep_alive: Option<Duration>,
+    pub socket_addr: SocketAddr,
+    pub lines: Vec<String>,
+    pub stream: TcpStream,
 }
 
+/// Sent for every request read from a connection. Readers respond through
+/// the stream, `&TcpStream` can be written to.
+pub type HttpRequestEvents = EventUpdater<HttpRequest>;
+
+#[derive(Doer)]
 pub struct TcpConnectionToHttpRequest {
     pub connections: State<TcpConnectionsState>,
-    pub requests: State<HttpRequests>,
+    pub requests: State<Events<HttpRequest>>,
 }
 
 impl DoerTrait for TcpConnectionToHttpRequest {
-    fn new_state(state: &StateStore) -> Result<(), PmError>
-    where
-        Self: Sized,
-    {
-        let mut local_state = state.local.get()?;
-
-        local_state.add_state(HttpRequests {
-            requests: Vec::new(),
-        })
-    }
-
-    fn new(pm: &Pm) -> Result<Box<dyn DoerTrait>, PmError>
-    where
-        Self: Sized,
-    {
-        let local_state = pm.state.local.get()?;
-
-        Ok(Box::new(TcpConnectionToHttpRequest {
-            requests: local_state.get_state::<HttpRequests>()?,
-            connections: local_state.get_state::<TcpConnectionsState>()?,
-        }))
-    }
+    derived_doer!();
 
     fn update(&self) -> Result<(), PmError> {
         let mut requests = self.requests.get()?;
         let mut connections = self.connections.get()?;
-        let connections = std::mem::replace(&mut connections.connections, HashMap::new());
+        let connections = std::mem::take(&mut connections.connections);
 
-        for (socket_addr, connection) in connections.iter_mut() {
-            let http_request: Vec<_> = connection
-                .stream
+        for (socket_addr, connection) in connections.into_iter() {
+            let lines: Vec<_> = BufReader::new(&connection.stream)
                 .lines()
-                .map(|result| result.unwrap())
+                .map_while(Result::ok)
                 .take_while(|line| !line.is_empty())
                 .collect();
 
-            requests.requests.push(http_request);
+            requests.send(HttpRequest {
+                socket_addr,
+                lines,
+                stream: connection.stream,
+            });
         }
 
         Ok(())
     }
 }
 
+/// Answers every request.
+#[derive(Doer)]
 pub struct ResponseSender {
-    requests: State<HttpRequests>,
+    requests: State<Events<HttpRequest>>,
+    reader: EventReader<HttpRequest>,
+}
+
+impl DoerTrait for ResponseSender {
+    derived_doer!();
+
+    fn update(&self) -> Result<(), PmError> {
+        let requests = self.requests.get()?;
+
+        for request in self.reader.read(&requests) {
+            let _ = (&request.stream).write_all(b"HTTP/1.1 200 OK\r\n\r\n");
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads the same requests as the [ResponseSender] without taking them away.
+#[derive(Doer)]
+pub struct RequestLogger {
+    requests: State<Events<HttpRequest>>,
+    reader: EventReader<HttpRequest>,
+}
+
+impl DoerTrait for RequestLogger {
+    derived_doer!();
+
+    fn update(&self) -> Result<(), PmError> {
+        let requests = self.requests.get()?;
+
+        for request in self.reader.read(&requests) {
+            println!("{} {:?}", request.socket_addr, request.lines.first());
+        }
+
+        Ok(())
+    }
 }
 
 fn main() -> Result<(), PmError> {
     let mut pm = pm!(
         LoopTimingManager,
+        HttpRequestEvents,
         TcpListenerHandler,
-        TcpConnectionToHttpRequest
+        TcpConnectionToHttpRequest,
+        ResponseSender,
+        RequestLogger
     );
     pm.run()
 }
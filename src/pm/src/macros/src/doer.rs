@@ -0,0 +1,233 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote, quote_spanned};
+use syn::{
+    spanned::Spanned, DeriveInput, Expr, Field, Fields, GenericArgument, PathArguments, Type,
+};
+
+/// Which store a field's state lives in.
+enum StateKind {
+    Local,
+    Shared,
+}
+
+/// How a single field of the doer gets built.
+enum FieldInit {
+    /// A `State<T>` or `SharedState<T>` fetched from the store, optionally added
+    /// to the store first in new_state.
+    State {
+        kind: StateKind,
+        state_type: Box<Type>,
+        init: Option<Box<Expr>>,
+    },
+    /// Any other field, built from an expression or its [Default].
+    Value(Option<Expr>),
+}
+
+pub fn derive_doer(input: DeriveInput) -> syn::Result<TokenStream> {
+    let syn::Data::Struct(data_struct) = &input.data else {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "Doer can only be derived for structs",
+        ));
+    };
+
+    let fields: Vec<&Field> = match &data_struct.fields {
+        Fields::Named(named) => named.named.iter().collect(),
+        Fields::Unit => Vec::new(),
+        Fields::Unnamed(unnamed) => {
+            return Err(syn::Error::new_spanned(
+                unnamed,
+                "Doer can only be derived for structs with named fields",
+            ))
+        }
+    };
+
+    let mut new_state = Vec::new();
+    let mut new_fields = Vec::new();
+    let mut field_idents = Vec::new();
+    let mut handle_checks = Vec::new();
+    let mut errors: Option<syn::Error> = None;
+    let pm = crate::pm_path();
+    let type_params: Vec<String> = input
+        .generics
+        .type_params()
+        .map(|param| param.ident.to_string())
+        .collect();
+
+    for field in fields {
+        let ident = field.ident.as_ref().expect("named field");
+
+        // Each field is built in its own statement so the store borrows are
+        // dropped before the next field borrows them again.
+        let var = format_ident!("__{}", ident);
+        field_idents.push(quote! { #ident: #var });
+
+        let field_init = match field_init(field) {
+            Ok(field_init) => field_init,
+            Err(err) => {
+                match errors.as_mut() {
+                    Some(errors) => errors.combine(err),
+                    None => errors = Some(err),
+                }
+                continue;
+            }
+        };
+
+        match field_init {
+            FieldInit::State {
+                kind,
+                state_type,
+                init,
+            } => {
+                let span = field.ty.span();
+
+                if let Some(init) = init {
+                    new_state.push(match kind {
+                        StateKind::Local => quote_spanned! {span=>
+                            state.local.get()?.add_state::<#state_type>(#init)?;
+                        },
+                        StateKind::Shared => quote_spanned! {span=>
+                            state.shared.blocking_get()?.add_state::<#state_type>(#init)?;
+                        },
+                    });
+                }
+
+                let store = match kind {
+                    StateKind::Local => quote! { pm.state.local.get()? },
+                    StateKind::Shared => quote! { pm.state.shared.blocking_get()? },
+                };
+
+                new_fields.push(quote_spanned! {span=>
+                    let #var = #store
+                        .get_state::<#state_type>()
+                        .map_err(|err| match err {
+                            PmError::StateDoesNotExist => PmError::MissingState {
+                                doer: std::any::type_name::<Self>(),
+                                state: std::any::type_name::<#state_type>(),
+                            },
+                            err => err,
+                        })?;
+                });
+            }
+            FieldInit::Value(Some(value)) => new_fields.push(quote! { let #var = #value; }),
+            FieldInit::Value(None) => {
+                let span = field.ty.span();
+                let ty = &field.ty;
+
+                // Types using the doer's generic parameters can't be checked here.
+                if !crate::uses_type_params(ty, &type_params) {
+                    let message = format!(
+                        "`{ident}` is a `State` or `SharedState` under another name. \
+                         The Doer derive only finds state fields named `State<T>` or \
+                         `SharedState<T>`, so use those names or set `#[pm(value = ...)]`."
+                    );
+
+                    handle_checks.push(quote_spanned! {span=>
+                        const _: () = {
+                            use #pm::NotStateHandle as _;
+                            assert!(!#pm::StateHandleCheck::<#ty>::STATE_HANDLE, #message);
+                        };
+                    });
+                }
+
+                new_fields.push(quote_spanned! {span=>
+                    let #var = std::default::Default::default();
+                });
+            }
+        }
+    }
+
+    if let Some(errors) = errors {
+        return Err(errors);
+    }
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics DerivedDoer for #ident #ty_generics #where_clause {
+            fn derived_new_state(state: &StateStore) -> Result<(), PmError> {
+                #(#new_state)*
+
+                Ok(())
+            }
+
+            fn derived_new(pm: &Pm) -> Result<Self, PmError> {
+                #(#new_fields)*
+
+                Ok(Self {
+                    #(#field_idents,)*
+                })
+            }
+        }
+
+        #(#handle_checks)*
+    })
+}
+
+fn field_init(field: &Field) -> syn::Result<FieldInit> {
+    let mut init = None;
+    let mut value = None;
+
+    for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("pm")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("init") {
+                init = Some(meta.value()?.parse::<Expr>()?);
+                Ok(())
+            } else if meta.path.is_ident("value") {
+                value = Some(meta.value()?.parse::<Expr>()?);
+                Ok(())
+            } else {
+                Err(meta.error("expected `init = ...` or `value = ...`"))
+            }
+        })?;
+    }
+
+    let state = state_kind(&field.ty);
+
+    match (state, init, value) {
+        (_, Some(init), Some(_)) => Err(syn::Error::new_spanned(
+            init,
+            "`init` and `value` can't be used on the same field",
+        )),
+        (Some((kind, state_type)), init, None) => Ok(FieldInit::State {
+            kind,
+            state_type: Box::new(state_type),
+            init: init.map(Box::new),
+        }),
+        (Some(_), None, Some(value)) => Ok(FieldInit::Value(Some(value))),
+        (None, Some(init), None) => Err(syn::Error::new_spanned(
+            init,
+            "`init` can only be used on `State<T>` or `SharedState<T>` fields",
+        )),
+        (None, None, value) => Ok(FieldInit::Value(value)),
+    }
+}
+
+/// Find out if the field is a `State<T>` or `SharedState<T>`, and if so, the T.
+/// Only the names are known here, aliased handles are caught by the
+/// `StateHandleCheck` the derive emits for Default fields.
+fn state_kind(ty: &Type) -> Option<(StateKind, Type)> {
+    let Type::Path(type_path) = ty else {
+        return None;
+    };
+
+    let segment = type_path.path.segments.last()?;
+
+    let kind = if segment.ident == "State" {
+        StateKind::Local
+    } else if segment.ident == "SharedState" {
+        StateKind::Shared
+    } else {
+        return None;
+    };
+
+    let PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+
+    match args.args.first()? {
+        GenericArgument::Type(state_type) => Some((kind, state_type.clone())),
+        _ => None,
+    }
+}
@@ -1,55 +1,290 @@
 use proc_macro::TokenStream;
-use quote::quote;
-use syn::{DeriveInput, parse_macro_input};
+use quote::{quote, quote_spanned};
+use syn::{
+    parse_macro_input, parse_quote, spanned::Spanned, DeriveInput, Fields, GenericArgument, Generics, PathArguments,
+    Type,
+};
 
-#[proc_macro_derive(StateTrait)]
+mod doer;
+
+#[proc_macro_derive(StateTrait, attributes(pm))]
 pub fn derive_state_trait(item: TokenStream) -> TokenStream {
     let input = parse_macro_input!(item as DeriveInput);
-    let ident = input.ident;
 
-    // TODO: maybe enforce that the fields should be of type State<T> or SharedState<T>
-    // if let syn::Data::Struct(data_struct) = input.data {
-    //     if let syn::Fields::Named(named_fields) = data_struct.fields {
-    //         for field in named_fields.named {
-    //             println!("fields: {:?}", field.ty);
-    //         }
-    //     }
-    // }
+    // State holding handles to other state hides that state from the stores,
+    // and Rc handles can't follow the state if it is ever moved to another thread.
+    let field_checks = check_fields(&input, |name| {
+        format!(
+            "`{name}` inside of StateTrait types nests state. \
+             Add it to the store on its own and keep the `State<T>` in a doer instead."
+        )
+    });
+
+    let field_checks = match field_checks {
+        Ok(field_checks) => field_checks,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    let use_default = match use_default(&input) {
+        Ok(use_default) => use_default,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    let ident = &input.ident;
+    let generics = state_generics(&input, false, use_default);
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let default_state = default_state(use_default);
 
     let expanded = quote! {
-        impl StateTrait for #ident {
+        impl #impl_generics StateTrait for #ident #ty_generics #where_clause {
             fn as_any(&self) -> &dyn std::any::Any {
                 self
             }
+
+            #default_state
         }
+
+        #field_checks
     };
 
     // Hand the output tokens back to the compiler
     TokenStream::from(expanded)
 }
 
-#[proc_macro_derive(SharedStateTrait)]
+#[proc_macro_derive(SharedStateTrait, attributes(pm))]
 pub fn derive_shared_state_trait(item: TokenStream) -> TokenStream {
     let input = parse_macro_input!(item as DeriveInput);
-    let ident = input.ident;
 
-    // TODO: maybe enforce that the fields should be of type State<T> or SharedState<T>
-    // if let syn::Data::Struct(data_struct) = input.data {
-    //     if let syn::Fields::Named(named_fields) = data_struct.fields {
-    //         for field in named_fields.named {
-    //             println!("fields: {:?}", field.ty);
-    //         }
-    //     }
-    // }
+    // SharedStateTrait requires Send, so these would fail anyway. Calling out the
+    // field gives a much better error than the generic one from the trait bound.
+    let field_checks = check_fields(&input, |name| {
+        format!(
+            "`{name}` is not thread safe and SharedStateTrait types are shared \
+             across threads. Use `SharedState<T>` or another Send type instead."
+        )
+    });
+
+    let field_checks = match field_checks {
+        Ok(field_checks) => field_checks,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    let use_default = match use_default(&input) {
+        Ok(use_default) => use_default,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    let ident = &input.ident;
+    let generics = state_generics(&input, true, use_default);
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let default_state = default_state(use_default);
 
     let expanded = quote! {
-        impl SharedStateTrait for #ident {
+        impl #impl_generics SharedStateTrait for #ident #ty_generics #where_clause {
             fn as_any(&self) -> &dyn std::any::Any {
                 self
             }
+
+            #default_state
         }
+
+        #field_checks
     };
 
     // Hand the output tokens back to the compiler
     TokenStream::from(expanded)
 }
+
+/// Generates the [DerivedDoer] impl for a doer struct. `State<T>` and
+/// `SharedState<T>` fields are fetched from their stores in `new`. Other fields
+/// use their [Default] unless given a value.
+///
+/// `#[pm(init = expr)]` on a state field adds the state in `new_state`.
+/// `#[pm(value = expr)]` sets a field directly, `pm` is in scope for the expr.
+///
+/// State fields are found by the `State` and `SharedState` names, since a derive
+/// can't see through a `use` alias. Aliased ones are a compile error.
+#[proc_macro_derive(Doer, attributes(pm))]
+pub fn derive_doer(item: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(item as DeriveInput);
+
+    match doer::derive_doer(input) {
+        Ok(expanded) => expanded.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// State is stored as [std::any::Any], so every type parameter must be 'static.
+/// Shared state type parameters must also be Send. When the default_state hook
+/// is generated, the impl only applies when the type is [Default].
+fn state_generics(input: &DeriveInput, send: bool, use_default: bool) -> Generics {
+    let mut generics = input.generics.clone();
+    let type_params: Vec<_> = generics
+        .type_params()
+        .map(|param| param.ident.clone())
+        .collect();
+    let (_, ty_generics, _) = input.generics.split_for_impl();
+    let ident = &input.ident;
+    let where_clause = generics.make_where_clause();
+
+    for param in type_params {
+        if send {
+            where_clause
+                .predicates
+                .push(parse_quote!(#param: Send + 'static));
+        } else {
+            where_clause.predicates.push(parse_quote!(#param: 'static));
+        }
+    }
+
+    if use_default {
+        where_clause
+            .predicates
+            .push(parse_quote!(#ident #ty_generics: std::default::Default));
+    }
+
+    generics
+}
+
+/// Check if the type is marked with `#[pm(default)]`.
+fn use_default(input: &DeriveInput) -> syn::Result<bool> {
+    let mut use_default = false;
+
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("pm")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("default") {
+                use_default = true;
+                Ok(())
+            } else {
+                Err(meta.error("expected `default`"))
+            }
+        })?;
+    }
+
+    Ok(use_default)
+}
+
+/// Generate the default_state hook.
+fn default_state(use_default: bool) -> proc_macro2::TokenStream {
+    if !use_default {
+        return quote! {};
+    }
+
+    quote! {
+        fn default_state() -> Option<Self>
+        where
+            Self: Sized,
+        {
+            Some(<Self as std::default::Default>::default())
+        }
+    }
+}
+
+/// Check every field type, including generic arguments, for thread-local
+/// handles. The check is on the types themselves, through [ThreadLocalCheck],
+/// so `use pm::State as S` is still caught and other crates' types that happen
+/// to be named `State` aren't. Types using the state's own generic parameters
+/// can't be checked here.
+///
+/// [ThreadLocalCheck]: ../pm/struct.ThreadLocalCheck.html
+fn check_fields(
+    input: &DeriveInput,
+    message: impl Fn(&str) -> String,
+) -> syn::Result<proc_macro2::TokenStream> {
+    let fields: Vec<&Fields> = match &input.data {
+        syn::Data::Struct(data_struct) => vec![&data_struct.fields],
+        syn::Data::Enum(data_enum) => data_enum
+            .variants
+            .iter()
+            .map(|variant| &variant.fields)
+            .collect(),
+        syn::Data::Union(data_union) => {
+            return Err(syn::Error::new_spanned(
+                data_union.union_token,
+                "state can't be derived for unions",
+            ));
+        }
+    };
+
+    let type_params: Vec<String> = input
+        .generics
+        .type_params()
+        .map(|param| param.ident.to_string())
+        .collect();
+
+    let mut types = Vec::new();
+
+    for field in fields.into_iter().flat_map(|fields| fields.iter()) {
+        inner_types(&field.ty, &mut types);
+    }
+
+    let pm = pm_path();
+    let checks = types
+        .into_iter()
+        .filter(|ty| !uses_type_params(ty, &type_params))
+        .map(|ty| {
+            let message = message(&quote!(#ty).to_string().replace(' ', ""));
+
+            quote_spanned! {ty.span()=>
+                const _: () = {
+                    use #pm::NotThreadLocal as _;
+                    assert!(!#pm::ThreadLocalCheck::<#ty>::THREAD_LOCAL, #message);
+                };
+            }
+        });
+
+    Ok(quote! { #(#checks)* })
+}
+
+/// Path to the pm crate from wherever the derive is expanded. Pm derives its own
+/// state too, where the crate is only reachable as `crate`.
+pub(crate) fn pm_path() -> proc_macro2::TokenStream {
+    match std::env::var("CARGO_CRATE_NAME") {
+        Ok(name) if name == "pm" => quote! { crate },
+        _ => quote! { ::pm },
+    }
+}
+
+/// The type and every type inside of it.
+fn inner_types<'a>(ty: &'a Type, types: &mut Vec<&'a Type>) {
+    match ty {
+        Type::Path(type_path) => {
+            types.push(ty);
+
+            let Some(segment) = type_path.path.segments.last() else {
+                return;
+            };
+
+            if let PathArguments::AngleBracketed(args) = &segment.arguments {
+                for arg in args.args.iter() {
+                    if let GenericArgument::Type(ty) = arg {
+                        inner_types(ty, types);
+                    }
+                }
+            }
+        }
+        Type::Array(array) => inner_types(&array.elem, types),
+        Type::Slice(slice) => inner_types(&slice.elem, types),
+        Type::Group(group) => inner_types(&group.elem, types),
+        Type::Paren(paren) => inner_types(&paren.elem, types),
+        Type::Reference(reference) => inner_types(&reference.elem, types),
+        Type::Tuple(tuple) => {
+            for elem in tuple.elems.iter() {
+                inner_types(elem, types);
+            }
+        }
+        _ => {}
+    }
+}
+
+pub(crate) fn uses_type_params(ty: &Type, type_params: &[String]) -> bool {
+    quote!(#ty)
+        .into_iter()
+        .flat_map(|token| match token {
+            proc_macro2::TokenTree::Group(group) => group.stream().into_iter().collect(),
+            token => vec![token],
+        })
+        .any(|token| {
+            matches!(&token, proc_macro2::TokenTree::Ident(ident) if type_params.contains(&ident.to_string()))
+        })
+}
@@ -1,11 +1,15 @@
-use crate::{pm::*, state::*, PmError};
-use std::any::{type_name_of_val, Any};
+use crate::{pm::*, schedule::*, state::*, trace::*, watchdog::*, PmError};
+use std::{
+    any::{type_name, Any},
+    collections::HashSet,
+    path::PathBuf,
+};
 
 /// Doers are how [State] is updated. They are trait objects stored in a single [Vec]
 /// within the [Pm]'s [DoerStore].
 ///
 /// [DoerTrait] impls [Any] so that the type_name can be used for addressing
-/// the doer.
+/// the doer. See [DoerTrait::name].
 pub trait DoerTrait: Any {
     /// Add any new [State] the [Doer] may require. When adding [Doer]s to
     /// the [Pm] or using [DoerGroup]s, this function is called before any [Doer]s
@@ -48,27 +52,208 @@ pub trait DoerTrait: Any {
         Ok(())
     }
 
+    /// Called before any doer's update on each loop. Only called if
+    /// [DoerTrait::phases] contains [Phase::PreUpdate].
+    fn pre_update(&self) -> Result<(), PmError> {
+        Ok(())
+    }
+
+    /// Called after every doer's update on each loop. This is the spot for
+    /// frame boundary work like flushing sockets or swapping buffers. Only
+    /// called if [DoerTrait::phases] contains [Phase::PostUpdate].
+    fn post_update(&self) -> Result<(), PmError> {
+        Ok(())
+    }
+
+    /// Called for user defined phases, see [Pm::add_phase_before]. The phase's
+    /// name is passed in so one doer can take part in several of them. Only
+    /// called for the [Phase::Custom]s in [DoerTrait::phases].
+    fn custom_phase(&self, _phase: &'static str) -> Result<(), PmError> {
+        Ok(())
+    }
+
+    /// Called on every loop while the whole [Pm] is paused, instead of any other
+    /// phase. Debugging doers use this to resume or step the Pm. Only called if
+    /// [DoerTrait::phases] contains [Phase::Paused].
+    fn paused(&self) -> Result<(), PmError> {
+        Ok(())
+    }
+
+    /// The phases this doer takes part in. Doers that override pre_update,
+    /// post_update or custom_phase must list those phases here too.
+    fn phases(&self) -> &'static [Phase] {
+        &[Phase::Update]
+    }
+
     /// Function called when doer is removed. If you need to differentiate
     /// about why the doer is being removed, you'll need to add that
     /// state yourself (such as if the whole program is exiting).
     fn remove(&self) -> Result<(), PmError> {
         Ok(())
     }
+
+    /// The name used to address the doer in [DoerControlMessage]s. This has to
+    /// be a trait method, since [std::any::type_name_of_val] on a
+    /// `Box<dyn DoerTrait>` only sees the trait object and not the doer itself.
+    fn name(&self) -> &'static str {
+        type_name::<Self>()
+    }
+}
+
+pub use pm_macros::Doer;
+
+/// Implemented by `#[derive(Doer)]`. Use [derived_doer] inside of the
+/// [DoerTrait] impl to hook these up as the doer's new_state and new.
+pub trait DerivedDoer: Sized {
+    /// Add any state marked with `#[pm(init = ...)]`.
+    fn derived_new_state(state: &StateStore) -> Result<(), PmError>;
+
+    /// Build the doer, fetching each [State] and [SharedState] field.
+    fn derived_new(pm: &Pm) -> Result<Self, PmError>;
+}
+
+/// Fill in [DoerTrait::new_state] and [DoerTrait::new] from a
+/// `#[derive(Doer)]`.
+///
+/// ```ignore
+/// impl DoerTrait for MyDoer {
+///     derived_doer!();
+///
+///     fn update(&self) -> Result<(), PmError> { ... }
+/// }
+/// ```
+#[macro_export]
+macro_rules! derived_doer {
+    () => {
+        fn new_state(state: &$crate::StateStore) -> Result<(), $crate::PmError>
+        where
+            Self: Sized,
+        {
+            <Self as $crate::DerivedDoer>::derived_new_state(state)
+        }
+
+        fn new(pm: &$crate::Pm) -> Result<Box<dyn $crate::DoerTrait>, $crate::PmError>
+        where
+            Self: Sized,
+        {
+            Ok(Box::new(<Self as $crate::DerivedDoer>::derived_new(pm)?))
+        }
+    };
+}
+
+/// A step of the [Pm]'s update. Every loop runs each of the [Pm]'s phases in
+/// order, and each phase runs the doers that list it in [DoerTrait::phases].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Runs [DoerTrait::pre_update].
+    PreUpdate,
+    /// Runs [DoerTrait::update].
+    Update,
+    /// Runs [DoerTrait::post_update].
+    PostUpdate,
+    /// Runs [DoerTrait::custom_phase] with the name.
+    Custom(&'static str),
+    /// Runs [DoerTrait::paused]. This is the only phase run while the [Pm] is
+    /// paused, and it is never part of the Pm's phases.
+    Paused,
+}
+
+impl Phase {
+    /// The phases a [Pm] starts with.
+    pub const DEFAULT: [Phase; 3] = [Phase::PreUpdate, Phase::Update, Phase::PostUpdate];
+
+    /// The phase's name, used as the category of its [Span]s.
+    pub fn name(self) -> &'static str {
+        match self {
+            Phase::PreUpdate => "pre_update",
+            Phase::Update => "update",
+            Phase::PostUpdate => "post_update",
+            Phase::Custom(name) => name,
+            Phase::Paused => "paused",
+        }
+    }
+
+    fn run(self, doer: &dyn DoerTrait) -> Result<(), PmError> {
+        let _span = span(doer.name(), self.name());
+
+        match self {
+            Phase::PreUpdate => doer.pre_update(),
+            Phase::Update => doer.update(),
+            Phase::PostUpdate => doer.post_update(),
+            Phase::Custom(name) => doer.custom_phase(name),
+            Phase::Paused => doer.paused(),
+        }
+    }
 }
 
 /// Typedef for the Doer's new function since it got used in multiple spots.
 /// Slight abstraction cost, but code is easier to read.
-type DoerNewFn = Box<dyn FnOnce(&Pm) -> Result<Box<dyn DoerTrait>, PmError>>;
+pub(crate) type DoerNewFn = Box<dyn FnOnce(&Pm) -> Result<Box<dyn DoerTrait>, PmError>>;
+
+/// Same as above, but for the Doer's new_state function.
+pub(crate) type DoerNewStateFn = Box<dyn FnOnce(&StateStore) -> Result<(), PmError>>;
 
 /// A message sent to the [DoerState] intended to add/remove/manipulate [Doer]
 /// execution order. 
+///
+/// Doers are addressed by their [DoerTrait::name]. Naming a doer that isn't
+/// active is an error, which is returned from the [Pm]'s update.
 pub enum DoerControlMessage {
+    /// Move the first doer to right after the second.
     MoveAfter(&'static str, &'static str),
+    /// Move the first doer to right before the second.
     MoveBefore(&'static str, &'static str),
+    /// Move the doer so it ends up at the index.
     MoveToIndex(&'static str, usize),
     AddToEnd(DoerNewFn),
     AddToStart(DoerNewFn),
     Remove(&'static str),
+    /// Create a new doer in place of the named one, which is then removed.
+    Replace(&'static str, DoerNewFn),
+    /// Swap the positions of two doers.
+    Swap(&'static str, &'static str),
+    /// Run the doer on a [Schedule] instead of on every tick.
+    Schedule(&'static str, Schedule),
+    /// Create a doer at the end and run it on a [Schedule], keyed by the new
+    /// doer's [DoerTrait::name]. See [Pm::add_scheduled_doer].
+    AddScheduled(DoerNewFn, Schedule),
+    /// Drop the doer's [Schedule] so it runs on every tick again.
+    Unschedule(&'static str),
+    /// Stop running the doer, keeping it and its place in the execution order.
+    Pause(&'static str),
+    /// Start running a paused doer again.
+    Resume(&'static str),
+    /// Pause the whole [Pm]. See [Pm::pause].
+    PausePm,
+    /// Resume the whole [Pm]. See [Pm::resume].
+    ResumePm,
+    /// Run a single loop of a paused [Pm]. See [Pm::step].
+    StepPm,
+    /// Move the most recent inactive doer with the name back into the active
+    /// doers. See [Pm::reactivate_doer].
+    Reactivate(&'static str, DoerPosition),
+    /// Same as [DoerControlMessage::Reactivate], but [DoerTrait::first] is run
+    /// again before the doer is put back.
+    ReactivateFirst(&'static str, DoerPosition),
+    /// Load a shared library of doers. See [Pm::load_library].
+    LoadLibrary(PathBuf),
+    /// Create the doers a library loaded with [Pm::load_library] is still
+    /// holding. Does nothing once they're created or the library is unloaded.
+    AddLibraryDoers(PathBuf),
+    /// Remove a library's doers and state, then unload it. See [Pm::unload_library].
+    UnloadLibrary(PathBuf),
+    /// Unload and load a library again, picking up a rebuilt version of it.
+    ReloadLibrary(PathBuf),
+}
+
+/// Where to put a doer in the execution order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DoerPosition {
+    Start,
+    End,
+    Index(usize),
+    Before(&'static str),
+    After(&'static str),
 }
 
 /// All info concering Doers BESIDES the list of Doers themselves is kept in
@@ -83,15 +268,48 @@ pub struct DoerState {
     /// The list of inactive Doers. A Doer can become inactive if it errors or is
     /// removed via a [DoerControlMessage].
     pub inactive: Vec<DoerInactive>,
+    /// The most inactive doers kept around. Once there are more, the oldest
+    /// are dropped after the next control message pass.
+    pub max_inactive: usize,
 }
 
 impl DoerState {
+    /// The [Self::max_inactive] a [Pm] starts with.
+    pub const DEFAULT_MAX_INACTIVE: usize = 64;
+
     pub fn new() -> Self {
         Self {
             message_queue: Vec::new(),
             inactive: Vec::new(),
+            max_inactive: Self::DEFAULT_MAX_INACTIVE,
+        }
+    }
+
+    /// Drop the oldest inactive doers until there are at most [Self::max_inactive].
+    pub fn trim_inactive(&mut self) {
+        if self.inactive.len() > self.max_inactive {
+            let excess = self.inactive.len() - self.max_inactive;
+
+            self.inactive.drain(..excess);
         }
     }
+
+    /// Take the most recent inactive doer with the name out of the inactive list.
+    pub fn take_inactive(&mut self, doer_name: &str) -> Result<Box<dyn DoerTrait>, PmError> {
+        let index = self
+            .inactive
+            .iter()
+            .rposition(|inactive| match inactive.doer() {
+                Some(doer) => doer.name() == doer_name,
+                None => false,
+            })
+            .ok_or(PmError::InactiveDoerDoesNotExist)?;
+
+        self.inactive
+            .remove(index)
+            .into_doer()
+            .ok_or(PmError::InactiveDoerDoesNotExist)
+    }
 }
 
 /// A DoerStore contains the doers and meta information about them.
@@ -106,6 +324,13 @@ pub struct DoerStore {
     /// it is also kept here so other Doers don't need to query the LocalStore
     /// in their methods, they can just access it via the &Pm.
     pub state: State<DoerState>,
+    /// Doers that don't run on every tick. See [Schedule].
+    pub schedules: DoerSchedules,
+    /// Doers that are paused, by name. They stay in the active list but skip
+    /// every phase until they are resumed.
+    pub paused: HashSet<&'static str>,
+    /// Set while a [Watchdog] is watching the doers run.
+    pub watchdog: Option<WatchdogHandle>,
 }
 
 impl DoerStore {
@@ -119,6 +344,9 @@ impl DoerStore {
         Ok(Self {
             active: Vec::new(),
             state: local_state.get_state::<DoerState>()?,
+            schedules: DoerSchedules::new(),
+            paused: HashSet::new(),
+            watchdog: None,
         })
     }
 
@@ -136,40 +364,47 @@ impl DoerStore {
         Ok(())
     }
 
+    /// Find the index a doer would be inserted at for the position.
+    pub fn position_index(&self, position: DoerPosition) -> Result<usize, PmError> {
+        match position {
+            DoerPosition::Start => Ok(0),
+            DoerPosition::End => Ok(self.active.len()),
+            DoerPosition::Index(index) if index <= self.active.len() => Ok(index),
+            DoerPosition::Index(_) => Err(PmError::DoerIndexOutOfBounds),
+            DoerPosition::Before(before_doer) => self.doer_index(before_doer),
+            DoerPosition::After(after_doer) => Ok(self.doer_index(after_doer)? + 1),
+        }
+    }
+
+    /// Find the index of an active doer by its [DoerTrait::name].
+    pub fn doer_index(&self, doer_name: &str) -> Result<usize, PmError> {
+        self.active
+            .iter()
+            .position(|doer| doer.name() == doer_name)
+            .ok_or(PmError::DoerDoesNotExist)
+    }
+
     /// Move one doer before another.
     pub fn move_doer_before_other(
         &mut self,
         before_doer: &'static str,
         move_doer: &'static str,
     ) -> Result<(), PmError> {
-        let mut maybe_before_index = None;
-        let mut maybe_move_index = None;
-
-        for (index, doer) in self.active.iter().enumerate() {
-            if maybe_move_index.is_some() && maybe_before_index.is_some() {
-                break;
-            }
+        // Check both exist before touching the list so an error leaves it as is.
+        self.doer_index(before_doer)?;
+        let move_index = self.doer_index(move_doer)?;
 
-            if type_name_of_val(&(**doer)) == before_doer {
-                maybe_before_index = Some(index);
-            }
-
-            if type_name_of_val(&(**doer)) == move_doer {
-                maybe_move_index = Some(index);
-            }
+        if before_doer == move_doer {
+            return Ok(());
         }
 
-        if let Some(before_index) = maybe_before_index {
-            if let Some(move_index) = maybe_move_index {
-                let move_doer = self.active.remove(move_index);
+        let doer = self.active.remove(move_index);
 
-                if before_index == 0 {
-                    self.active.insert(0, move_doer);
-                } else {
-                    self.active.insert(before_index - 1, move_doer);
-                }
-            }
-        }
+        // The before index has to be found after the removal, since removing
+        // a doer in front of it shifts it down by one.
+        let before_index = self.doer_index(before_doer)?;
+
+        self.active.insert(before_index, doer);
 
         Ok(())
     }
@@ -180,68 +415,224 @@ impl DoerStore {
         after_doer: &'static str,
         move_doer: &'static str,
     ) -> Result<(), PmError> {
-        let mut maybe_after_index = None;
-        let mut maybe_move_index = None;
+        self.doer_index(after_doer)?;
+        let move_index = self.doer_index(move_doer)?;
 
-        for (index, doer) in self.active.iter().enumerate() {
-            if maybe_move_index.is_some() && maybe_after_index.is_some() {
-                break;
-            }
+        if after_doer == move_doer {
+            return Ok(());
+        }
 
-            if type_name_of_val(&(**doer)) == after_doer {
-                maybe_after_index = Some(index);
-            }
+        let doer = self.active.remove(move_index);
 
-            if type_name_of_val(&(**doer)) == move_doer {
-                maybe_move_index = Some(index);
-            }
-        }
+        let after_index = self.doer_index(after_doer)?;
 
-        if let Some(before_index) = maybe_after_index {
-            if let Some(move_index) = maybe_move_index {
-                let move_doer = self.active.remove(move_index);
+        self.active.insert(after_index + 1, doer);
 
-                self.active.insert(before_index + 1, move_doer);
-            }
+        Ok(())
+    }
+
+    /// Move a doer so it ends up at the index in the execution order.
+    pub fn move_doer_to_index(
+        &mut self,
+        move_doer: &'static str,
+        index: usize,
+    ) -> Result<(), PmError> {
+        let move_index = self.doer_index(move_doer)?;
+
+        if index >= self.active.len() {
+            return Err(PmError::DoerIndexOutOfBounds);
         }
 
+        let doer = self.active.remove(move_index);
+
+        self.active.insert(index, doer);
+
+        Ok(())
+    }
+
+    /// Swap the positions of two doers.
+    pub fn swap_doers(
+        &mut self,
+        first_doer: &'static str,
+        second_doer: &'static str,
+    ) -> Result<(), PmError> {
+        let first_index = self.doer_index(first_doer)?;
+        let second_index = self.doer_index(second_doer)?;
+
+        self.active.swap(first_index, second_index);
+
         Ok(())
     }
 
+    /// Replace a doer with a new one in the same position. The old doer is
+    /// removed the same way as in [Self::remove_doer].
+    pub fn replace_doer(
+        &mut self,
+        old_doer: &'static str,
+        new_doer: Box<dyn DoerTrait>,
+    ) -> Result<(), PmError> {
+        let index = self.doer_index(old_doer)?;
+
+        let doer = std::mem::replace(&mut self.active[index], new_doer);
+
+        self.forget_doer(old_doer);
+
+        self.deactivate_removed(doer)
+    }
+
     /// Remove a doer from the execution. This calls [DoerTrait::remove] and puts the
     /// doer into the inactive list.
     pub fn remove_doer(&mut self, doer_name: &'static str) -> Result<(), PmError> {
+        let index = self.doer_index(doer_name)?;
+
+        let doer = self.active.remove(index);
+
+        self.forget_doer(doer_name);
+
+        self.deactivate_removed(doer)
+    }
+
+    /// Stop running a doer without removing it.
+    pub fn pause_doer(&mut self, doer_name: &'static str) -> Result<(), PmError> {
+        self.doer_index(doer_name)?;
+
+        self.paused.insert(doer_name);
+
+        Ok(())
+    }
+
+    /// Start running a paused doer again.
+    pub fn resume_doer(&mut self, doer_name: &'static str) -> Result<(), PmError> {
+        self.doer_index(doer_name)?;
+
+        self.paused.remove(doer_name);
+
+        Ok(())
+    }
+
+    /// Drop the schedule and pause of a doer once no active doer with the name
+    /// is left.
+    pub(crate) fn forget_doer(&mut self, doer_name: &str) {
+        if self.doer_index(doer_name).is_err() {
+            self.schedules.remove(doer_name);
+            self.paused.remove(doer_name);
+        }
+    }
+
+    /// Call [DoerTrait::remove] and put the doer into the inactive list.
+    fn deactivate_removed(&mut self, doer: Box<dyn DoerTrait>) -> Result<(), PmError> {
+        // Run before borrowing the DoerState, so remove can queue control messages.
+        let removed = {
+            let _span = span(doer.name(), "remove");
+            doer.remove()
+        };
+
         let mut doer_state = self.state.get()?;
 
-        let mut maybe_index = None;
+        match removed {
+            Ok(()) => doer_state.inactive.push(DoerInactive::Removed(doer)),
+            Err(err) => doer_state.inactive.push(DoerInactive::RemoveErr(doer, err)),
+        }
+
+        Ok(())
+    }
+
+    /// Run one phase for every doer that takes part in it, other than the
+    /// skipped ones. Doers that error are put into the inactive list and skip
+    /// the rest of the loop.
+    pub fn run_phase(
+        &mut self,
+        phase: Phase,
+        skipped: &HashSet<&'static str>,
+    ) -> Result<(), PmError> {
+        let doers = std::mem::take(&mut self.active);
+        let mut errored_doers = Vec::new();
+
+        for doer in doers.into_iter() {
+            if !doer.phases().contains(&phase) || skipped.contains(doer.name()) {
+                self.active.push(doer);
+                continue;
+            }
+
+            if let Some(watchdog) = self.watchdog.as_ref() {
+                watchdog.enter((doer.name(), phase.name()));
+            }
+
+            let result = phase.run(doer.as_ref());
+
+            if let Some(watchdog) = self.watchdog.as_ref() {
+                watchdog.exit();
+            }
+
+            match result {
+                Ok(()) => self.active.push(doer),
+                Err(err) => errored_doers.push(DoerInactive::UpdateErr(doer, err)),
+            }
+        }
 
-        for (index, doer) in self.active.iter().enumerate() {
-            if type_name_of_val(&(**doer)) == doer_name {
-                maybe_index = Some(index);
-                break;
+        if !errored_doers.is_empty() {
+            for errored_doer in errored_doers.iter() {
+                if let Some(doer) = errored_doer.doer() {
+                    self.forget_doer(doer.name());
+                }
             }
+
+            self.state.get()?.inactive.append(&mut errored_doers);
         }
 
-        if let Some(index) = maybe_index {
-            let doer = self.active.remove(index);
+        Ok(())
+    }
 
-            match doer.remove() {
-                Ok(()) => doer_state.inactive.push(DoerInactive::Removed(doer)),
-                Err(err) => doer_state.inactive.push(DoerInactive::RemoveErr(doer, err)),
+    /// Remove a doer and drop it instead of keeping it in the inactive list. Any
+    /// inactive doers with the same name are dropped as well. This is needed
+    /// when the code behind the doer is about to go away, like when unloading
+    /// a library.
+    pub fn drop_doer(&mut self, doer_name: &str) -> Result<(), PmError> {
+        let doers = std::mem::take(&mut self.active);
+
+        for doer in doers.into_iter() {
+            if doer.name() == doer_name {
+                // The doer is going away either way, so an error here has
+                // nowhere useful to go.
+                let _span = span(doer.name(), "remove");
+                let _ = doer.remove();
+            } else {
+                self.active.push(doer);
             }
         }
 
+        self.schedules.remove(doer_name);
+        self.paused.remove(doer_name);
+
+        // The DoerState is only borrowed after every remove has run, so they
+        // can queue control messages.
+        self.state
+            .get()?
+            .inactive
+            .retain(|inactive| match inactive.doer() {
+                Some(doer) => doer.name() != doer_name,
+                None => true,
+            });
+
         Ok(())
     }
 }
 
 /// A group of doers to simplify adding many at once to a Pm.
+#[derive(Default)]
 pub struct DoerGroup {
-    pub add_state: Vec<Box<dyn FnOnce(&StateStore) -> Result<(), PmError>>>,
+    pub add_state: Vec<DoerNewStateFn>,
     pub doers: Vec<DoerNewFn>,
 }
 
 impl DoerGroup {
+    pub fn new() -> Self {
+        Self {
+            add_state: Vec::new(),
+            doers: Vec::new(),
+        }
+    }
+
     /// A builder type method for adding doers to the DoerGroup.
     pub fn add_doer<T: DoerTrait>(&mut self) -> Result<(), PmError> {
         self.add_state.push(Box::new(T::new_state));
@@ -261,3 +652,27 @@ pub enum DoerInactive {
     RemoveErr(Box<dyn DoerTrait>, PmError),
     Removed(Box<dyn DoerTrait>),
 }
+
+impl DoerInactive {
+    /// The doer instance, if it got far enough to be created.
+    pub fn doer(&self) -> Option<&dyn DoerTrait> {
+        match self {
+            DoerInactive::NewStateErr(_) | DoerInactive::NewErr(_) => None,
+            DoerInactive::FirstErr(doer, _)
+            | DoerInactive::UpdateErr(doer, _)
+            | DoerInactive::RemoveErr(doer, _)
+            | DoerInactive::Removed(doer) => Some(doer.as_ref()),
+        }
+    }
+
+    /// Take the doer instance, if it got far enough to be created.
+    pub fn into_doer(self) -> Option<Box<dyn DoerTrait>> {
+        match self {
+            DoerInactive::NewStateErr(_) | DoerInactive::NewErr(_) => None,
+            DoerInactive::FirstErr(doer, _)
+            | DoerInactive::UpdateErr(doer, _)
+            | DoerInactive::RemoveErr(doer, _)
+            | DoerInactive::Removed(doer) => Some(doer),
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use crate::{doer::*, pm::*, state::*, PmError};
+use std::{cell::Cell, marker::PhantomData};
+
+/// A state for sending events from one doer to any number of others. Every
+/// reader keeps its own [EventReader] cursor, so reading doesn't take events
+/// away from other readers.
+///
+/// Events are double buffered. The [EventUpdater] swaps the buffers once per
+/// loop, so an event can be read during the loop it was sent and the next one,
+/// then it is dropped. Readers that go longer than that between reads miss
+/// events.
+///
+/// Add an [EventUpdater] for each event type, it adds the state as well.
+#[derive(StateTrait)]
+pub struct Events<T> {
+    /// Events sent during the last loop.
+    previous: Vec<T>,
+    /// Events sent during this loop.
+    current: Vec<T>,
+    /// The id of the first event in previous. Ids count up from 0 for every
+    /// event ever sent.
+    previous_start: u64,
+    /// The id of the first event in current.
+    current_start: u64,
+}
+
+impl<T> Default for Events<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Events<T> {
+    pub fn new() -> Self {
+        Self {
+            previous: Vec::new(),
+            current: Vec::new(),
+            previous_start: 0,
+            current_start: 0,
+        }
+    }
+
+    pub fn send(&mut self, event: T) {
+        self.current.push(event);
+    }
+
+    pub fn send_batch(&mut self, events: impl IntoIterator<Item = T>) {
+        self.current.extend(events);
+    }
+
+    /// Drop the events from the last loop and start a new buffer for this one.
+    /// The [EventUpdater] calls this, so it shouldn't be needed elsewhere.
+    pub fn swap(&mut self) {
+        std::mem::swap(&mut self.previous, &mut self.current);
+        self.current.clear();
+
+        self.previous_start = self.current_start;
+        self.current_start = self.previous_start + self.previous.len() as u64;
+    }
+
+    /// Drop every buffered event. Readers skip ahead past them.
+    pub fn clear(&mut self) {
+        self.swap();
+        self.swap();
+    }
+
+    /// The id the next event sent will get.
+    pub fn next_id(&self) -> u64 {
+        self.current_start + self.current.len() as u64
+    }
+
+    /// Every buffered event, oldest first, regardless of any reader.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.previous.iter().chain(self.current.iter())
+    }
+
+    pub fn len(&self) -> usize {
+        self.previous.len() + self.current.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A cursor into [Events]. Keep one in each doer that reads the events. It
+/// uses a [Cell] so it can be read from [DoerTrait::update].
+pub struct EventReader<T> {
+    cursor: Cell<u64>,
+    events: PhantomData<fn() -> T>,
+}
+
+impl<T> Default for EventReader<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> EventReader<T> {
+    /// A new reader starts with the oldest buffered events.
+    pub fn new() -> Self {
+        Self {
+            cursor: Cell::new(0),
+            events: PhantomData,
+        }
+    }
+
+    /// The events sent since this reader last read, oldest first.
+    pub fn read<'a>(&self, events: &'a Events<T>) -> impl Iterator<Item = &'a T> {
+        let start = self.cursor.replace(events.next_id());
+        let previous_skip = start.saturating_sub(events.previous_start) as usize;
+        let current_skip = start.saturating_sub(events.current_start) as usize;
+
+        let previous = events.previous.get(previous_skip..).unwrap_or_default();
+        let current = events.current.get(current_skip..).unwrap_or_default();
+
+        previous.iter().chain(current.iter())
+    }
+
+    /// How many events this reader missed by not reading for too long.
+    pub fn missed(&self, events: &Events<T>) -> u64 {
+        events.previous_start.saturating_sub(self.cursor.get())
+    }
+}
+
+/// Swaps the [Events] buffers in [Phase::PostUpdate], once per loop. Add one
+/// for each event type. Its new_state adds the [Events] state, so doers using
+/// the events can get it in their new.
+pub struct EventUpdater<T> {
+    events: State<Events<T>>,
+}
+
+impl<T: 'static> DoerTrait for EventUpdater<T> {
+    fn new_state(state: &StateStore) -> Result<(), PmError>
+    where
+        Self: Sized,
+    {
+        let mut local_state = state.local.get()?;
+
+        if !local_state.state_exists::<Events<T>>() {
+            local_state.add_state(Events::<T>::new())?;
+        }
+
+        Ok(())
+    }
+
+    fn new(pm: &Pm) -> Result<Box<dyn DoerTrait>, PmError>
+    where
+        Self: Sized,
+    {
+        Ok(Box::new(Self {
+            events: pm.state.local.get()?.get_state::<Events<T>>()?,
+        }))
+    }
+
+    fn phases(&self) -> &'static [Phase] {
+        &[Phase::PostUpdate]
+    }
+
+    fn post_update(&self) -> Result<(), PmError> {
+        self.events.get()?.swap();
+
+        Ok(())
+    }
+}
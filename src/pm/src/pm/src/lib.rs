@@ -30,12 +30,26 @@
 //! https://matklad.github.io/2021/09/05/Rust100k.html
 
 mod doer;
+mod events;
+mod library;
 mod pm;
+mod record;
+mod schedule;
 mod state;
+mod sub_pm;
+mod trace;
+mod watchdog;
 
 pub use doer::*;
+pub use events::*;
+pub use library::*;
 pub use pm::*;
+pub use record::*;
+pub use schedule::*;
 pub use state::*;
+pub use sub_pm::*;
+pub use trace::*;
+pub use watchdog::*;
 
 /// The high level errors possible while using Pm.
 #[derive(Debug)]
@@ -50,14 +64,61 @@ pub enum PmError {
     NewDoer,
     /// Errored inside of [DoerTrait::first].
     DoerFirst,
-    /// Errored inside of [DoerTrait::update].
+    /// Errored inside of [DoerTrait::update], or there were no doers to update.
     DoerUpdate,
+    /// The [Phase] was already added to the [Pm].
+    PhaseExists,
+    /// The [Phase] to add another phase next to isn't in the [Pm].
+    PhaseDoesNotExist,
+    /// A [DoerControlMessage] named a doer that isn't active.
+    DoerDoesNotExist,
+    /// A doer index or [DoerPosition::Index] was past the end of the doers.
+    DoerIndexOutOfBounds,
+    /// There is no inactive doer with the name to reactivate.
+    InactiveDoerDoesNotExist,
+    /// Reading or writing a recording file failed.
+    RecordingIo(std::io::ErrorKind),
+    /// The recording file wasn't made with the same [Recording] inputs.
+    RecordingMismatch,
+    /// The [Pm] is already recording or replaying.
+    RecordingExists,
+    /// The [Pm] isn't recording or replaying.
+    RecordingDoesNotExist,
+    /// Every recorded loop has been replayed.
+    ReplayFinished,
+    /// Writing a [Trace] file failed.
+    WriteTrace(std::io::ErrorKind),
+    /// The [Pm] already has a [Watchdog].
+    WatchdogExists,
+    /// The [Pm] doesn't have a [Watchdog].
+    WatchdogDoesNotExist,
+    /// A [Watchdog] made with [Watchdog::abort] caught a doer or tick going
+    /// over its budget.
+    WatchdogAbort(Box<Overrun>),
     /// Errored when attempting to add [State] to a [StateStore].
     StateExists,
     /// Errored when attempting to cast [State] to the desired type.
     CouldNotCastState,
     /// Errored when attempting to get [State] from a store.
     StateDoesNotExist,
+    /// A derived [Doer] couldn't find the [State] for one of its fields.
+    MissingState {
+        doer: &'static str,
+        state: &'static str,
+    },
     /// Errored when attempting to remove [State] from a store.
     RemoveState,
+    /// The state wasn't derived with `#[pm(default)]`, so it can't be added
+    /// with add_default_state.
+    NoDefaultState,
+    /// Errored when opening a library or finding its exported symbols.
+    LoadLibrary,
+    /// The library was built against a different [PM_ABI_VERSION].
+    LibraryAbiMismatch,
+    /// A library at the same path is already loaded.
+    LibraryLoaded,
+    /// No library at the path is loaded.
+    LibraryNotLoaded,
+    /// Errored when closing a library.
+    UnloadLibrary,
 }
@@ -0,0 +1,314 @@
+use crate::{doer::*, pm::*, PmError};
+use std::{
+    any::type_name,
+    path::{Path, PathBuf},
+};
+
+/// Bump this whenever [DoerTrait], [StateStore] or [LibraryRegistrar] change in a
+/// way that would break a library compiled against an older Pm. Libraries export
+/// the version they were built with and the [Pm] refuses to load mismatches.
+///
+/// Note that Rust has no stable ABI. The library and the program loading it must
+/// also be built by the same compiler with the same Pm source.
+pub const PM_ABI_VERSION: u32 = 1;
+
+/// Symbol the library exports to report the [PM_ABI_VERSION] it was built with.
+pub const LIBRARY_ABI_VERSION_SYMBOL: &[u8] = b"pm_library_abi_version";
+
+/// Symbol the library exports to register its doers.
+pub const LIBRARY_REGISTER_SYMBOL: &[u8] = b"pm_library_register";
+
+type LibraryAbiVersionFn = extern "C" fn() -> u32;
+type LibraryRegisterFn = fn(&mut LibraryRegistrar) -> Result<(), PmError>;
+
+/// Handed to a library's register function so it can describe the doers it adds.
+/// This works like a [DoerGroup], but also remembers the doer names so the
+/// [Pm] can find and remove them again before the library is unloaded.
+pub struct LibraryRegistrar {
+    add_state: Vec<DoerNewStateFn>,
+    doers: Vec<DoerNewFn>,
+    doer_names: Vec<&'static str>,
+}
+
+impl LibraryRegistrar {
+    fn new() -> Self {
+        Self {
+            add_state: Vec::new(),
+            doers: Vec::new(),
+            doer_names: Vec::new(),
+        }
+    }
+
+    /// A builder type method for adding doers to the library.
+    pub fn add_doer<T: DoerTrait>(&mut self) -> Result<(), PmError> {
+        self.add_state.push(Box::new(T::new_state));
+        self.doers.push(Box::new(T::new));
+        self.doer_names.push(type_name::<T>());
+
+        Ok(())
+    }
+}
+
+/// A library that is currently loaded into the [Pm]. Everything it added is
+/// tracked so it can be torn down before the code backing it goes away.
+pub struct LoadedLibrary {
+    pub path: PathBuf,
+    pub doer_names: Vec<&'static str>,
+    pub local_state_names: Vec<&'static str>,
+    pub shared_state_names: Vec<&'static str>,
+    /// Doers waiting on [DoerControlMessage::AddLibraryDoers]. Their new
+    /// functions are library code, so they are dropped with the library instead
+    /// of sitting in the message queue.
+    pending_doers: Vec<DoerNewFn>,
+    /// Kept last so it is dropped after everything above. The names point into
+    /// the library's memory.
+    library: libloading::Library,
+}
+
+/// All libraries loaded into a [Pm].
+#[derive(Default)]
+pub struct LibraryStore {
+    pub loaded: Vec<LoadedLibrary>,
+}
+
+impl LibraryStore {
+    pub fn new() -> Self {
+        Self { loaded: Vec::new() }
+    }
+
+    /// Check if a library at the path is loaded.
+    pub fn library_loaded(&self, path: &Path) -> bool {
+        self.loaded.iter().any(|library| library.path == path)
+    }
+}
+
+/// Export the [PM_ABI_VERSION] and register function from a `cdylib` crate so a
+/// [Pm] can load the listed doers with [Pm::load_library].
+///
+/// pm_library!(DoerA, DoerB);
+#[macro_export]
+macro_rules! pm_library {
+    ($($doer:ident),+) => {
+        #[no_mangle]
+        pub extern "C" fn pm_library_abi_version() -> u32 {
+            $crate::PM_ABI_VERSION
+        }
+
+        #[no_mangle]
+        pub fn pm_library_register(
+            registrar: &mut $crate::LibraryRegistrar,
+        ) -> Result<(), $crate::PmError> {
+            $(
+                registrar.add_doer::<$doer>()?;
+            )*
+
+            Ok(())
+        }
+    };
+}
+
+impl Pm {
+    /// Load a shared library exporting doers via [pm_library]. The library's
+    /// [DoerTrait::new_state] functions are run immediately and its doers are
+    /// created by a queued [DoerControlMessage::AddLibraryDoers] on the next
+    /// control message pass.
+    ///
+    /// Any state added while running the library's new_state functions is
+    /// recorded and removed again when the library is unloaded.
+    pub fn load_library(&mut self, path: impl AsRef<Path>) -> Result<(), PmError> {
+        let path = path.as_ref();
+
+        if self.libraries.library_loaded(path) {
+            return Err(PmError::LibraryLoaded);
+        }
+
+        // SAFETY: Loading a library runs its initializers. We have to trust the
+        // library, the same as any other code linked into the program.
+        let library =
+            unsafe { libloading::Library::new(path) }.map_err(|_| PmError::LoadLibrary)?;
+
+        // SAFETY: The symbol types match what [pm_library] exports.
+        let abi_version = unsafe {
+            library
+                .get::<LibraryAbiVersionFn>(LIBRARY_ABI_VERSION_SYMBOL)
+                .map_err(|_| PmError::LoadLibrary)?()
+        };
+
+        if abi_version != PM_ABI_VERSION {
+            return Err(PmError::LibraryAbiMismatch);
+        }
+
+        let mut registrar = LibraryRegistrar::new();
+
+        // SAFETY: See above, the ABI version has been checked.
+        unsafe {
+            library
+                .get::<LibraryRegisterFn>(LIBRARY_REGISTER_SYMBOL)
+                .map_err(|_| PmError::LoadLibrary)?(&mut registrar)?;
+        }
+
+        let local_before = self.state.local.get()?.state_names();
+        let shared_before = self.state.shared.blocking_get()?.state_names();
+
+        let new_state_result = registrar
+            .add_state
+            .into_iter()
+            .try_for_each(|new_state| new_state(&self.state));
+
+        let (local_state_names, shared_state_names) =
+            match self.state_added_since(&local_before, &shared_before) {
+                Ok(names) => names,
+                Err(error) => {
+                    // Whatever state the library added can't be found again, so
+                    // the code its drops need has to stay loaded.
+                    std::mem::forget(library);
+                    return Err(error);
+                }
+            };
+
+        if let Err(error) = new_state_result {
+            // Roll back the state added before the failure, since the library is
+            // closed when it's dropped here.
+            if self
+                .remove_library_state(&local_state_names, &shared_state_names)
+                .is_err()
+            {
+                std::mem::forget(library);
+            }
+
+            return Err(error);
+        }
+
+        // Tracked before queueing anything, so the state is removed again on
+        // unload even if the queue can't be reached.
+        self.libraries.loaded.push(LoadedLibrary {
+            path: path.to_path_buf(),
+            doer_names: registrar.doer_names,
+            local_state_names,
+            shared_state_names,
+            pending_doers: registrar.doers,
+            library,
+        });
+
+        self.doers
+            .state
+            .get()?
+            .message_queue
+            .push(DoerControlMessage::AddLibraryDoers(path.to_path_buf()));
+
+        Ok(())
+    }
+
+    /// Create the doers a loaded library is still holding and add them to the
+    /// end of the active doers.
+    pub(crate) fn add_library_doers(&mut self, path: &Path) -> Result<(), PmError> {
+        let Some(library) = self
+            .libraries
+            .loaded
+            .iter_mut()
+            .find(|library| library.path == path)
+        else {
+            // Unloaded before the doers were created.
+            return Ok(());
+        };
+
+        let pending_doers = std::mem::take(&mut library.pending_doers);
+
+        for new in pending_doers.into_iter() {
+            self.doers.doer_to_end(new(self)?)?;
+        }
+
+        Ok(())
+    }
+
+    /// Unload a library. Its doers are removed first (calling [DoerTrait::remove])
+    /// and dropped instead of being kept as inactive, then its state is removed
+    /// from the stores, and only then is the library closed.
+    ///
+    /// [State] or [SharedState] handles to library state that are held elsewhere,
+    /// like in another thread, will outlive the library. Don't do that.
+    pub fn unload_library(&mut self, path: impl AsRef<Path>) -> Result<(), PmError> {
+        let path = path.as_ref();
+
+        let Some(index) = self
+            .libraries
+            .loaded
+            .iter()
+            .position(|library| library.path == path)
+        else {
+            return Err(PmError::LibraryNotLoaded);
+        };
+
+        // The library stays in the store until its doers and state are gone, so
+        // a failure here leaves it loaded rather than closing code still in use.
+        let library = &self.libraries.loaded[index];
+        let doer_names = library.doer_names.clone();
+        let local_state_names = library.local_state_names.clone();
+        let shared_state_names = library.shared_state_names.clone();
+
+        for doer_name in doer_names.iter() {
+            self.doers.drop_doer(doer_name)?;
+        }
+
+        self.remove_library_state(&local_state_names, &shared_state_names)?;
+
+        let LoadedLibrary {
+            pending_doers,
+            library,
+            ..
+        } = self.libraries.loaded.remove(index);
+
+        // Doers that were never created run library code to drop, so they have
+        // to go before the library is closed.
+        drop(pending_doers);
+
+        library.close().map_err(|_| PmError::UnloadLibrary)
+    }
+
+    /// Names of the local and shared state added since the before names were taken.
+    fn state_added_since(
+        &self,
+        local_before: &[&'static str],
+        shared_before: &[&'static str],
+    ) -> Result<(Vec<&'static str>, Vec<&'static str>), PmError> {
+        let mut local_state_names = self.state.local.get()?.state_names();
+        let mut shared_state_names = self.state.shared.blocking_get()?.state_names();
+
+        local_state_names.retain(|name| !local_before.contains(name));
+        shared_state_names.retain(|name| !shared_before.contains(name));
+
+        Ok((local_state_names, shared_state_names))
+    }
+
+    fn remove_library_state(
+        &self,
+        local_state_names: &[&'static str],
+        shared_state_names: &[&'static str],
+    ) -> Result<(), PmError> {
+        let mut local_state = self.state.local.get()?;
+
+        // The library's doers may have already removed some of their own state,
+        // so missing state is fine here.
+        for state_name in local_state_names.iter() {
+            let _ = local_state.remove_state_by_name(state_name);
+        }
+
+        drop(local_state);
+
+        let mut shared_state = self.state.shared.blocking_get()?;
+
+        for state_name in shared_state_names.iter() {
+            let _ = shared_state.remove_state_by_name(state_name);
+        }
+
+        Ok(())
+    }
+
+    /// Unload and then load a library again from the same path.
+    pub fn reload_library(&mut self, path: impl AsRef<Path>) -> Result<(), PmError> {
+        let path = path.as_ref();
+
+        self.unload_library(path)?;
+        self.load_library(path)
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{doer::*, state::*, PmError};
+use crate::{doer::*, library::*, record::*, state::*, trace::*, PmError};
 
 /// Pm is the top level struct. It is passed around by immutable reference
 /// 
@@ -12,6 +12,17 @@ use crate::{doer::*, state::*, PmError};
 pub struct Pm {
     pub state: StateStore,
     pub doers: DoerStore,
+    /// The phases run on every update, in order. See [Phase].
+    pub phases: Vec<Phase>,
+    /// While paused, updates only run [Phase::Paused] unless a step was requested.
+    pub paused: bool,
+    /// Loops requested with [Self::step] that haven't run yet.
+    pub steps: u64,
+    /// Set while recording or replaying inputs. See [Recording].
+    pub recorder: Option<Recorder>,
+    /// Declared last so doers and state are dropped before any library code
+    /// backing them is unloaded.
+    pub libraries: LibraryStore,
 }
 
 impl Pm {
@@ -20,10 +31,20 @@ impl Pm {
     }
 
     pub fn new(shared_state: SharedState<SharedStore>) -> Result<Self, PmError> {
+        attach_trace(&shared_state)?;
+
         let state = StateStore::new(shared_state);
         let doers = DoerStore::new(&state)?;
 
-        Ok(Self { state, doers })
+        Ok(Self {
+            state,
+            doers,
+            phases: Phase::DEFAULT.to_vec(),
+            paused: false,
+            steps: 0,
+            recorder: None,
+            libraries: LibraryStore::new(),
+        })
     }
 
     pub fn add_doer<T: DoerTrait>(&self) -> Result<(), PmError> {
@@ -38,32 +59,38 @@ impl Pm {
         Ok(())
     }
 
-    /// Add doers in a group. This will call new_state and new, then add the doer
-    /// to the active list. After all doers are added to the list, a
-    /// Self::manage_control_messages call occurs so doers get situated.
+    /// Add doers in a group. This will call new_state for every doer in the
+    /// group, then queue each doer's new. A Self::manage_control_messages call
+    /// then occurs so the doers get situated, in the order they were added
+    /// behind any doers that were already queued.
     ///
     /// Doer groups allow doers to remove state they may want to hide. Each
     /// doer can get_state during its new call. A final doer can remove the
     /// state from the store while all the doers still hold a State instance.
     pub fn add_doer_group(&mut self, doer_group: DoerGroup) -> Result<(), PmError> {
-        let new_state_funcs = doer_group.add_state;
-        let new_funcs = doer_group.doers;
-        let mut new_doers: Vec<Box<dyn DoerTrait>> = Vec::new();
+        self.queue_doer_group(doer_group)?;
+
+        self.manage_control_messages()?;
 
-        for new_state in new_state_funcs.into_iter() {
+        Ok(())
+    }
+
+    /// Same as [Self::add_doer_group], but the doers are only queued. They are
+    /// created on the next control message pass along with any other queued
+    /// doers, like the ones from [Self::add_doer].
+    pub fn queue_doer_group(&self, doer_group: DoerGroup) -> Result<(), PmError> {
+        for new_state in doer_group.add_state.into_iter() {
             new_state(&self.state)?;
         }
 
-        for new in new_funcs.into_iter() {
-            new_doers.push(new(&self)?);
-        }
+        let mut doer_state = self.doers.state.get()?;
 
-        for doer in new_doers.into_iter() {
-            self.doers.active.push(doer);
+        for new in doer_group.doers.into_iter() {
+            doer_state
+                .message_queue
+                .push(DoerControlMessage::AddToEnd(new));
         }
 
-        self.manage_control_messages()?;
-
         Ok(())
     }
 
@@ -81,14 +108,14 @@ impl Pm {
     /// loop, we would do doers.get()?, then iterate over them and call "update".
     /// But any doer attempting to mutate doers would then do doers.get()
     /// itself and hit the double access.
-    fn manage_control_messages(&mut self) -> Result<(), PmError> {
+    pub(crate) fn manage_control_messages(&mut self) -> Result<(), PmError> {
         let mut doer_state = self.doers.state.get()?;
 
         let control_messages = std::mem::replace(&mut doer_state.message_queue, Vec::new());
 
         drop(doer_state);
 
-        for control_message in control_messages.iter() {
+        for control_message in control_messages.into_iter() {
             match control_message {
                 DoerControlMessage::AddToStart(new_fn) => {
                     self.doers.doer_to_start(new_fn(&self)?)?;
@@ -105,12 +132,104 @@ impl Pm {
                 DoerControlMessage::Remove(doer) => {
                     self.doers.remove_doer(doer)?;
                 }
-                DoerControlMessage::MoveToIndex(_doer, _index) => {
-                    unimplemented!("Moving a Doer to a specific index is currently not supported.")
+                DoerControlMessage::MoveToIndex(doer, index) => {
+                    self.doers.move_doer_to_index(doer, index)?;
+                }
+                DoerControlMessage::Replace(old_doer, new_fn) => {
+                    // Don't create the new doer if there's nothing to replace.
+                    self.doers.doer_index(old_doer)?;
+                    self.doers.replace_doer(old_doer, new_fn(self)?)?;
+                }
+                DoerControlMessage::Swap(first_doer, second_doer) => {
+                    self.doers.swap_doers(first_doer, second_doer)?;
+                }
+                DoerControlMessage::Schedule(doer, schedule) => {
+                    let schedule = schedule.activate(self)?;
+                    self.doers.schedule_doer(doer, schedule)?;
+                }
+                DoerControlMessage::AddScheduled(new_fn, schedule) => {
+                    let doer = new_fn(self)?;
+                    let doer_name = doer.name();
+
+                    self.doers.doer_to_end(doer)?;
+
+                    let schedule = schedule.activate(self)?;
+                    self.doers.schedule_doer(doer_name, schedule)?;
+                }
+                DoerControlMessage::Unschedule(doer) => {
+                    self.doers.unschedule_doer(doer)?;
+                }
+                DoerControlMessage::Pause(doer) => {
+                    self.doers.pause_doer(doer)?;
+                }
+                DoerControlMessage::Resume(doer) => {
+                    self.doers.resume_doer(doer)?;
+                }
+                DoerControlMessage::PausePm => self.pause(),
+                DoerControlMessage::ResumePm => self.resume(),
+                DoerControlMessage::StepPm => self.step(),
+                DoerControlMessage::Reactivate(doer, position) => {
+                    self.reactivate_doer(doer, position, false)?;
+                }
+                DoerControlMessage::ReactivateFirst(doer, position) => {
+                    self.reactivate_doer(doer, position, true)?;
+                }
+                DoerControlMessage::LoadLibrary(path) => {
+                    self.load_library(path)?;
+                }
+                DoerControlMessage::AddLibraryDoers(path) => {
+                    self.add_library_doers(&path)?;
+                }
+                DoerControlMessage::UnloadLibrary(path) => {
+                    self.unload_library(path)?;
+                }
+                DoerControlMessage::ReloadLibrary(path) => {
+                    self.reload_library(path)?;
                 }
             }
         }
 
+        self.doers.state.get()?.trim_inactive();
+
+        Ok(())
+    }
+
+    /// Move the most recent inactive doer with the name back into the active
+    /// doers at the position. The doer keeps its instance, so it picks up with
+    /// whatever it held when it went inactive.
+    ///
+    /// If run_first is set, [DoerTrait::first] is called first. Should that
+    /// error, the doer goes back into the inactive list as a FirstErr.
+    pub fn reactivate_doer(
+        &mut self,
+        doer_name: &'static str,
+        position: DoerPosition,
+        run_first: bool,
+    ) -> Result<(), PmError> {
+        // Checked before taking the doer so a bad position doesn't lose it.
+        let index = self.doers.position_index(position)?;
+
+        let doer = self.doers.state.get()?.take_inactive(doer_name)?;
+
+        if run_first {
+            let first = {
+                let _span = span(doer.name(), "first");
+                doer.first(self)
+            };
+
+            if let Err(err) = first {
+                self.doers
+                    .state
+                    .get()?
+                    .inactive
+                    .push(DoerInactive::FirstErr(doer, err));
+
+                return Ok(());
+            }
+        }
+
+        self.doers.active.insert(index, doer);
+
         Ok(())
     }
 
@@ -118,77 +237,251 @@ impl Pm {
         self.manage_control_messages()?;
 
         let doers_len = self.doers.active.len();
-        let mut doer_state = self.doers.state.get()?;
 
         let doers = std::mem::replace(&mut self.doers.active, Vec::with_capacity(doers_len));
         let mut doers_after_first = Vec::new();
+        let mut errored_doers = Vec::new();
 
+        // DoerState isn't held during the calls since doers commonly queue
+        // control messages from their first function.
         for doer in doers.into_iter() {
-            match doer.first(self) {
+            let first = {
+                let _span = span(doer.name(), "first");
+                doer.first(self)
+            };
+
+            match first {
                 Ok(()) => doers_after_first.push(doer),
-                Err(err) => doer_state.inactive.push(DoerInactive::FirstErr(doer, err)),
+                Err(err) => errored_doers.push(DoerInactive::FirstErr(doer, err)),
             }
         }
 
-        drop(doer_state);
+        self.doers.state.get()?.inactive.append(&mut errored_doers);
 
         for doer in doers_after_first.into_iter() {
             self.doers.active.push(doer);
         }
 
+        flush_trace();
+
         Ok(())
     }
 
+    /// Run one loop. Control messages are handled first, then each of the
+    /// [Self::phases] runs in order. Doers that are paused or whose [Schedule]
+    /// isn't due sit out every phase of the loop.
+    ///
+    /// While the Pm is paused, only [Phase::Paused] runs, unless a loop was
+    /// requested with [Self::step].
     pub fn update(&mut self) -> Result<(), PmError> {
-        self.manage_control_messages()?;
+        if let Some(watchdog) = self.doers.watchdog.as_ref() {
+            watchdog.start_tick();
+        }
 
-        let mut errored_doer_indices = Vec::new();
-        let mut errored_doers = Vec::new();
+        let update = {
+            let _span = span("Pm::update", "pm");
+            self.update_phases()
+        };
+
+        if let Some(watchdog) = self.doers.watchdog.as_ref() {
+            watchdog.end_tick();
+        }
+
+        flush_trace();
+
+        update
+    }
+
+    fn update_phases(&mut self) -> Result<(), PmError> {
+        self.manage_control_messages()?;
 
-        if self.doers.active.len() == 0 {
+        if self.doers.active.is_empty() {
             return Err(PmError::DoerUpdate);
         }
 
-        for (index, doer) in self.doers.active.iter().enumerate() {
-            match doer.update() {
-                Ok(()) => continue,
-                Err(err) => errored_doer_indices.push((index, err)),
+        if self.paused {
+            if self.steps == 0 {
+                self.doers
+                    .run_phase(Phase::Paused, &self.doers.paused.clone())?;
+
+                return self.check_watchdog();
             }
-        }
 
-        for (index, err) in errored_doer_indices.into_iter() {
-            errored_doers.push((self.doers.active.remove(index), err));
+            self.steps -= 1;
         }
 
-        if errored_doers.len() > 0 {
-            let mut doer_state = self.doers.state.get()?;
+        let skipped = self.doers.skipped_doers();
 
-            for (errored_doer, err) in errored_doers.into_iter() {
-                doer_state
-                    .inactive
-                    .push(DoerInactive::UpdateErr(errored_doer, err))
+        for phase in self.phases.iter() {
+            self.doers.run_phase(*phase, &skipped)?;
+            self.check_watchdog()?;
+
+            if let Some(recorder) = self.recorder.as_mut() {
+                if recorder.after == *phase {
+                    recorder.tick()?;
+                }
             }
         }
 
         Ok(())
     }
+
+    fn check_watchdog(&self) -> Result<(), PmError> {
+        match self.doers.watchdog.as_ref() {
+            Some(watchdog) => watchdog.check_abort(),
+            None => Ok(()),
+        }
+    }
+
+    /// Stop running the doers' phases on update. Control messages are still
+    /// handled and [Phase::Paused] still runs.
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    /// Go back to running every loop. Any steps that weren't run are dropped.
+    pub fn resume(&mut self) {
+        self.paused = false;
+        self.steps = 0;
+    }
+
+    /// Run one more loop while paused. Steps add up, so stepping twice runs
+    /// the next two updates.
+    pub fn step(&mut self) {
+        if self.paused {
+            self.steps += 1;
+        }
+    }
+
+    /// Add a user defined phase to run right before another phase.
+    pub fn add_phase_before(&mut self, phase: Phase, before: Phase) -> Result<(), PmError> {
+        let index = self.phase_index(phase, before)?;
+
+        self.phases.insert(index, phase);
+
+        Ok(())
+    }
+
+    /// Add a user defined phase to run right after another phase.
+    pub fn add_phase_after(&mut self, phase: Phase, after: Phase) -> Result<(), PmError> {
+        let index = self.phase_index(phase, after)?;
+
+        self.phases.insert(index + 1, phase);
+
+        Ok(())
+    }
+
+    /// Find the other phase's index, making sure the new phase isn't there yet.
+    fn phase_index(&self, phase: Phase, other: Phase) -> Result<usize, PmError> {
+        if self.phases.contains(&phase) {
+            return Err(PmError::PhaseExists);
+        }
+
+        self.phases
+            .iter()
+            .position(|existing| *existing == other)
+            .ok_or(PmError::PhaseDoesNotExist)
+    }
 }
 
+/// Build a [Pm] from a list of doers.
+///
+/// pm!(DoerA, DoerB)
+/// pm!(shared_state; DoerA, DoerB)
+///
+/// Doers that should be created together go in a group, see [DoerGroup].
+///
+/// pm!(DoerA, group { DoerB, DoerC })
+///
+/// Ordering hints move doers around once they've been created.
+///
+/// pm!(DoerA, DoerB, order { DoerB before DoerA })
+///
+/// Starting with `run` builds the [Pm] and runs it.
+///
+/// pm!(run DoerA, DoerB)
+///
+/// The doers are only queued, so every doer's new_state is called before any
+/// doer is created. This means the macro must be used in a function returning
+/// `Result<_, PmError>`.
 #[macro_export]
 macro_rules! pm {
-    ($($doer:ident),+) => {{
-        let mut pm = Pm::with_shared_state()?;
-        $(
-            pm.add_doer::<$doer>()?;
-        )*
+    (run $($rest:tt)+) => {{
+        let mut pm = $crate::pm!($($rest)+);
+        pm.run()
+    }};
+
+    // Look for a `;` to find the shared state expression, if there is one.
+    (@shared [$($shared_state:tt)+] ; $($items:tt)+) => {{
+        let mut pm = $crate::Pm::new($($shared_state)+)?;
+        $crate::pm!(@items pm; $($items)+);
         pm
     }};
 
-    ($shared_state:expr; $($doer:ident),+) => {{
-        let mut pm = Pm::new($shared_state)?;
-        $(
-            pm.doer::<$doer>()?;
-        )*
+    (@shared [$($shared_state:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::pm!(@shared [$($shared_state)* $next] $($rest)*)
+    };
+
+    (@shared [$($items:tt)+]) => {{
+        let mut pm = $crate::Pm::with_shared_state()?;
+        $crate::pm!(@items pm; $($items)+);
         pm
     }};
+
+    (@items $pm:ident;) => {};
+
+    (@items $pm:ident; group { $($doer:ident),+ $(,)? } $(, $($rest:tt)*)?) => {
+        let mut group = $crate::DoerGroup::new();
+        $(
+            group.add_doer::<$doer>()?;
+        )+
+        $pm.queue_doer_group(group)?;
+        $crate::pm!(@items $pm; $($($rest)*)?);
+    };
+
+    (@items $pm:ident; order { $($move_doer:ident $hint:ident $other_doer:ident),+ $(,)? } $(, $($rest:tt)*)?) => {
+        $(
+            $crate::pm!(@order $pm; $move_doer $hint $other_doer);
+        )+
+        $crate::pm!(@items $pm; $($($rest)*)?);
+    };
+
+    (@items $pm:ident; $doer:ident $(, $($rest:tt)*)?) => {
+        $pm.add_doer::<$doer>()?;
+        $crate::pm!(@items $pm; $($($rest)*)?);
+    };
+
+    (@order $pm:ident; $move_doer:ident before $other_doer:ident) => {
+        $pm.doers.state.get()?.message_queue.push($crate::DoerControlMessage::MoveBefore(
+            std::any::type_name::<$move_doer>(),
+            std::any::type_name::<$other_doer>(),
+        ));
+    };
+
+    (@order $pm:ident; $move_doer:ident after $other_doer:ident) => {
+        $pm.doers.state.get()?.message_queue.push($crate::DoerControlMessage::MoveAfter(
+            std::any::type_name::<$move_doer>(),
+            std::any::type_name::<$other_doer>(),
+        ));
+    };
+
+    (@order $pm:ident; $move_doer:ident $hint:ident $other_doer:ident) => {
+        compile_error!(concat!(
+            "expected `before` or `after` in the ordering hint, found `",
+            stringify!($hint),
+            "`"
+        ));
+    };
+
+    (@items $pm:ident; $($rest:tt)+) => {
+        compile_error!(concat!(
+            "expected a doer, `group { .. }` or `order { .. }`, found `",
+            stringify!($($rest)+),
+            "`"
+        ));
+    };
+
+    ($($rest:tt)+) => {
+        $crate::pm!(@shared [] $($rest)+)
+    };
 }
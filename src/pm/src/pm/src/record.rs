@@ -0,0 +1,319 @@
+use crate::{doer::*, pm::*, state::*, PmError};
+use std::{
+    any::type_name,
+    fs::File,
+    io::{BufReader, BufWriter, ErrorKind, Read, Write},
+    path::Path,
+};
+
+/// Marks the start of a recording file.
+const RECORDING_MAGIC: &[u8; 4] = b"PMRC";
+
+/// Bump this whenever the recording file layout changes.
+const RECORDING_VERSION: u32 = 1;
+
+/// [State] that comes from outside of the program, like network reads, user
+/// input or clock readings. Recording these every loop is enough to run the
+/// same doers again and get the same results.
+///
+/// The encoding is up to the state. Whatever record writes is handed back to
+/// replay as is.
+pub trait InputState: StateTrait {
+    /// Append the state's current value to the buffer.
+    fn record(&self, buffer: &mut Vec<u8>) -> Result<(), PmError>;
+
+    /// Overwrite the state with a value written by record.
+    fn replay(&mut self, recorded: &[u8]) -> Result<(), PmError>;
+}
+
+type RecordFn = Box<dyn Fn(&mut Vec<u8>) -> Result<(), PmError>>;
+type ReplayFn = Box<dyn Fn(&[u8]) -> Result<(), PmError>>;
+type InputNewFn = Box<dyn Fn(&Pm) -> Result<RecordedInput, PmError>>;
+
+/// The input states to record and the doers that write them. The same
+/// recording is used to record a run and to replay it again.
+///
+/// Recording::new().input::<Clock>().producer::<ClockReader>()
+pub struct Recording {
+    after: Phase,
+    inputs: Vec<(&'static str, InputNewFn)>,
+    producers: Vec<&'static str>,
+}
+
+impl Default for Recording {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Recording {
+    pub fn new() -> Self {
+        Self {
+            after: Phase::PreUpdate,
+            inputs: Vec::new(),
+            producers: Vec::new(),
+        }
+    }
+
+    /// Record the state every loop. A builder type method.
+    pub fn input<T: InputState>(mut self) -> Self {
+        self.inputs.push((
+            type_name::<T>(),
+            Box::new(|pm: &Pm| {
+                let state = pm.state.local.get()?.get_state::<T>()?;
+                let replay_state = state.clone();
+
+                Ok(RecordedInput {
+                    record: Box::new(move |buffer| state.get()?.record(buffer)),
+                    replay: Box::new(move |recorded| replay_state.get()?.replay(recorded)),
+                })
+            }),
+        ));
+
+        self
+    }
+
+    /// A doer that writes the inputs. Producers are paused during a replay so
+    /// they don't overwrite the recorded inputs.
+    pub fn producer<T: DoerTrait>(mut self) -> Self {
+        self.producers.push(type_name::<T>());
+
+        self
+    }
+
+    /// The [Phase] after which the inputs are recorded or replayed. This
+    /// defaults to [Phase::PreUpdate], so producers should do their reads
+    /// there. The phase has to be one of the [Pm]'s phases.
+    pub fn after(mut self, phase: Phase) -> Self {
+        self.after = phase;
+
+        self
+    }
+
+    fn activate(&self, pm: &Pm) -> Result<Vec<RecordedInput>, PmError> {
+        if !pm.phases.contains(&self.after) {
+            return Err(PmError::PhaseDoesNotExist);
+        }
+
+        let mut inputs = Vec::with_capacity(self.inputs.len());
+
+        for (_, input) in self.inputs.iter() {
+            inputs.push(input(pm)?);
+        }
+
+        Ok(inputs)
+    }
+}
+
+struct RecordedInput {
+    record: RecordFn,
+    replay: ReplayFn,
+}
+
+enum RecorderMode {
+    Record(BufWriter<File>),
+    Replay(BufReader<File>),
+}
+
+/// A recording or replay in progress. See [Pm::start_recording] and
+/// [Pm::start_replay].
+pub struct Recorder {
+    pub after: Phase,
+    /// Loops recorded or replayed so far.
+    pub ticks: u64,
+    inputs: Vec<RecordedInput>,
+    mode: RecorderMode,
+    buffer: Vec<u8>,
+}
+
+impl Recorder {
+    pub fn replaying(&self) -> bool {
+        matches!(self.mode, RecorderMode::Replay(_))
+    }
+
+    /// Record or replay one loop's inputs.
+    pub(crate) fn tick(&mut self) -> Result<(), PmError> {
+        match &mut self.mode {
+            RecorderMode::Record(writer) => {
+                write_u64(writer, self.ticks)?;
+
+                for input in self.inputs.iter() {
+                    self.buffer.clear();
+                    (input.record)(&mut self.buffer)?;
+                    write_bytes(writer, &self.buffer)?;
+                }
+            }
+            RecorderMode::Replay(reader) => {
+                let tick = match read_u64(reader) {
+                    Err(PmError::RecordingIo(ErrorKind::UnexpectedEof)) => {
+                        return Err(PmError::ReplayFinished)
+                    }
+                    tick => tick?,
+                };
+
+                if tick != self.ticks {
+                    return Err(PmError::RecordingMismatch);
+                }
+
+                for input in self.inputs.iter() {
+                    read_bytes(reader, &mut self.buffer)?;
+                    (input.replay)(&self.buffer)?;
+                }
+            }
+        }
+
+        self.ticks += 1;
+
+        Ok(())
+    }
+
+    fn finish(self) -> Result<(), PmError> {
+        match self.mode {
+            RecorderMode::Record(mut writer) => writer.flush().map_err(io_err),
+            RecorderMode::Replay(_) => Ok(()),
+        }
+    }
+}
+
+impl Pm {
+    /// Start writing the recording's inputs to the file on every loop. The
+    /// doers and state the recording names must already be added.
+    pub fn start_recording(
+        &mut self,
+        path: impl AsRef<Path>,
+        recording: &Recording,
+    ) -> Result<(), PmError> {
+        if self.recorder.is_some() {
+            return Err(PmError::RecordingExists);
+        }
+
+        let inputs = recording.activate(self)?;
+
+        let mut writer = BufWriter::new(File::create(path).map_err(io_err)?);
+
+        writer.write_all(RECORDING_MAGIC).map_err(io_err)?;
+        write_u64(&mut writer, RECORDING_VERSION as u64)?;
+        write_u64(&mut writer, recording.inputs.len() as u64)?;
+
+        for (name, _) in recording.inputs.iter() {
+            write_bytes(&mut writer, name.as_bytes())?;
+        }
+
+        self.recorder = Some(Recorder {
+            after: recording.after,
+            ticks: 0,
+            inputs,
+            mode: RecorderMode::Record(writer),
+            buffer: Vec::new(),
+        });
+
+        Ok(())
+    }
+
+    /// Feed the inputs from a file made by [Self::start_recording] back in on
+    /// every loop, pausing the recording's producers. The Pm should have the
+    /// same doers as the recorded one, in the same order. Once the recording
+    /// runs out, update returns [PmError::ReplayFinished].
+    pub fn start_replay(
+        &mut self,
+        path: impl AsRef<Path>,
+        recording: &Recording,
+    ) -> Result<(), PmError> {
+        if self.recorder.is_some() {
+            return Err(PmError::RecordingExists);
+        }
+
+        let inputs = recording.activate(self)?;
+
+        let mut reader = BufReader::new(File::open(path).map_err(io_err)?);
+        let mut magic = [0; 4];
+
+        reader.read_exact(&mut magic).map_err(io_err)?;
+
+        if &magic != RECORDING_MAGIC || read_u64(&mut reader)? != RECORDING_VERSION as u64 {
+            return Err(PmError::RecordingMismatch);
+        }
+
+        // The inputs have to match by name and order, otherwise the bytes
+        // would be handed to the wrong state.
+        if read_u64(&mut reader)? != recording.inputs.len() as u64 {
+            return Err(PmError::RecordingMismatch);
+        }
+
+        let mut name = Vec::new();
+
+        for (expected, _) in recording.inputs.iter() {
+            read_bytes(&mut reader, &mut name)?;
+
+            if name != expected.as_bytes() {
+                return Err(PmError::RecordingMismatch);
+            }
+        }
+
+        let mut doer_state = self.doers.state.get()?;
+
+        for producer in recording.producers.iter() {
+            doer_state
+                .message_queue
+                .push(DoerControlMessage::Pause(producer));
+        }
+
+        drop(doer_state);
+
+        self.recorder = Some(Recorder {
+            after: recording.after,
+            ticks: 0,
+            inputs,
+            mode: RecorderMode::Replay(reader),
+            buffer: Vec::new(),
+        });
+
+        Ok(())
+    }
+
+    /// Stop recording or replaying, flushing anything left to the file. Paused
+    /// producers stay paused.
+    pub fn stop_recording(&mut self) -> Result<(), PmError> {
+        match self.recorder.take() {
+            Some(recorder) => recorder.finish(),
+            None => Err(PmError::RecordingDoesNotExist),
+        }
+    }
+}
+
+fn io_err(err: std::io::Error) -> PmError {
+    PmError::RecordingIo(err.kind())
+}
+
+fn write_u64(writer: &mut impl Write, value: u64) -> Result<(), PmError> {
+    writer.write_all(&value.to_le_bytes()).map_err(io_err)
+}
+
+fn write_bytes(writer: &mut impl Write, bytes: &[u8]) -> Result<(), PmError> {
+    write_u64(writer, bytes.len() as u64)?;
+    writer.write_all(bytes).map_err(io_err)
+}
+
+fn read_u64(reader: &mut impl Read) -> Result<u64, PmError> {
+    let mut bytes = [0; 8];
+
+    reader.read_exact(&mut bytes).map_err(io_err)?;
+
+    Ok(u64::from_le_bytes(bytes))
+}
+
+/// The length comes from the file, so it's never allocated up front. A
+/// corrupt length runs out of file instead of out of memory.
+fn read_bytes(reader: &mut impl Read, buffer: &mut Vec<u8>) -> Result<(), PmError> {
+    let len = read_u64(reader)?;
+
+    buffer.clear();
+
+    let read = reader.take(len).read_to_end(buffer).map_err(io_err)?;
+
+    if read as u64 != len {
+        return Err(PmError::RecordingIo(ErrorKind::UnexpectedEof));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,219 @@
+use crate::{doer::*, pm::*, state::*, PmError};
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+/// How often a scheduled doer runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rate {
+    /// Run on every [Pm::update]. This is what unscheduled doers do.
+    EveryTick,
+    /// Run on the first tick and then once every N ticks.
+    EveryTicks(u64),
+    /// Run on the first tick and then once at least this long has passed.
+    Every(Duration),
+}
+
+/// A condition checked right before a scheduled doer would run. A condition
+/// that errors is treated as not holding, so the doer sits out the tick.
+type Condition = Box<dyn Fn() -> Result<bool, PmError>>;
+
+/// Conditions are described before the doer exists, so the [State] they check
+/// is only fetched once the schedule is given to the [Pm].
+type ConditionNewFn = Box<dyn FnOnce(&Pm) -> Result<Condition, PmError>>;
+
+/// When a doer runs. Schedules are given to the [Pm] with
+/// [DoerControlMessage::Schedule] or [Pm::add_scheduled_doer], and the Pm checks
+/// them once per tick. A doer skipped for a tick skips all of its [Phase]s.
+///
+/// Schedule::every(Duration::from_secs(1)).run_if::<Connections>(|c| c.open > 0)
+pub struct Schedule {
+    rate: Rate,
+    conditions: Vec<ConditionNewFn>,
+}
+
+impl Schedule {
+    pub fn new(rate: Rate) -> Self {
+        Self {
+            rate,
+            conditions: Vec::new(),
+        }
+    }
+
+    /// Run on every tick, but only when the conditions hold.
+    pub fn every_tick() -> Self {
+        Self::new(Rate::EveryTick)
+    }
+
+    /// Run once every N ticks.
+    pub fn every_ticks(ticks: u64) -> Self {
+        Self::new(Rate::EveryTicks(ticks))
+    }
+
+    /// Run once per duration at most.
+    pub fn every(duration: Duration) -> Self {
+        Self::new(Rate::Every(duration))
+    }
+
+    /// Only run while the predicate over the [State] holds. A builder type method,
+    /// so several conditions can be chained. All of them have to hold. If the
+    /// state is already borrowed, the doer skips the tick.
+    pub fn run_if<T: StateTrait>(mut self, predicate: impl Fn(&T) -> bool + 'static) -> Self {
+        self.conditions.push(Box::new(move |pm: &Pm| {
+            let state = pm.state.local.get()?.get_state::<T>()?;
+
+            let condition: Condition = Box::new(move || Ok(predicate(&*state.get()?)));
+
+            Ok(condition)
+        }));
+
+        self
+    }
+
+    /// Only run while the predicate over the [SharedState] holds. If another
+    /// thread holds the state, the doer skips the tick instead of blocking.
+    pub fn run_if_shared<T: SharedStateTrait>(
+        mut self,
+        predicate: impl Fn(&T) -> bool + 'static,
+    ) -> Self {
+        self.conditions.push(Box::new(move |pm: &Pm| {
+            let state = pm.state.shared.blocking_get()?.get_state::<T>()?;
+
+            let condition: Condition = Box::new(move || match state.get() {
+                Ok(state) => Ok(predicate(&*state)),
+                Err(_) => Ok(false),
+            });
+
+            Ok(condition)
+        }));
+
+        self
+    }
+
+    /// Fetch the state for each condition so the schedule can be checked.
+    pub(crate) fn activate(self, pm: &Pm) -> Result<DoerSchedule, PmError> {
+        let mut conditions = Vec::with_capacity(self.conditions.len());
+
+        for condition in self.conditions.into_iter() {
+            conditions.push(condition(pm)?);
+        }
+
+        Ok(DoerSchedule {
+            rate: self.rate,
+            conditions,
+            ticks_since_run: 0,
+            last_run: None,
+        })
+    }
+}
+
+/// A [Schedule] the [Pm] is tracking for an active doer.
+pub struct DoerSchedule {
+    pub rate: Rate,
+    conditions: Vec<Condition>,
+    ticks_since_run: u64,
+    last_run: Option<Instant>,
+}
+
+/// The schedules of the active doers, by doer name.
+pub type DoerSchedules = HashMap<&'static str, DoerSchedule>;
+
+impl DoerSchedule {
+    /// Check if the doer should run this tick. The rate is only used up when
+    /// the conditions hold too, so a doer waiting on a condition runs as soon
+    /// as it does.
+    fn due(&mut self, doer_name: &str, now: Instant) -> bool {
+        self.ticks_since_run = self.ticks_since_run.saturating_add(1);
+
+        let rate_due = match self.rate {
+            Rate::EveryTick => true,
+            Rate::EveryTicks(ticks) => self.last_run.is_none() || self.ticks_since_run >= ticks,
+            Rate::Every(duration) => match self.last_run {
+                Some(last_run) => now.duration_since(last_run) >= duration,
+                None => true,
+            },
+        };
+
+        if !rate_due {
+            return false;
+        }
+
+        for condition in self.conditions.iter() {
+            match condition() {
+                Ok(true) => {}
+                Ok(false) => return false,
+                Err(err) => {
+                    log::warn!("{doer_name} skipped a tick, its schedule condition failed: {err:?}");
+                    return false;
+                }
+            }
+        }
+
+        self.ticks_since_run = 0;
+        self.last_run = Some(now);
+
+        true
+    }
+}
+
+impl DoerStore {
+    /// Give an active doer a schedule, replacing any schedule it had. Doers are
+    /// scheduled by name, so every instance of the same doer shares it.
+    pub fn schedule_doer(
+        &mut self,
+        doer_name: &'static str,
+        schedule: DoerSchedule,
+    ) -> Result<(), PmError> {
+        self.doer_index(doer_name)?;
+
+        self.schedules.insert(doer_name, schedule);
+
+        Ok(())
+    }
+
+    /// Go back to running the doer on every tick.
+    pub fn unschedule_doer(&mut self, doer_name: &'static str) -> Result<(), PmError> {
+        self.doer_index(doer_name)?;
+
+        self.schedules.remove(doer_name);
+
+        Ok(())
+    }
+
+    /// Check every schedule for this tick, returning the doers that sit it out.
+    /// Paused doers always sit out, and their schedules don't move forward.
+    pub(crate) fn skipped_doers(&mut self) -> HashSet<&'static str> {
+        let now = Instant::now();
+        let mut skipped = self.paused.clone();
+
+        for (doer_name, schedule) in self.schedules.iter_mut() {
+            if skipped.contains(doer_name) {
+                continue;
+            }
+
+            if !schedule.due(doer_name, now) {
+                skipped.insert(*doer_name);
+            }
+        }
+
+        skipped
+    }
+}
+
+impl Pm {
+    /// Add a doer that runs on a [Schedule] instead of on every tick.
+    /// The schedule is keyed by the doer's [DoerTrait::name], the same as
+    /// [DoerControlMessage::Schedule].
+    pub fn add_scheduled_doer<T: DoerTrait>(&self, schedule: Schedule) -> Result<(), PmError> {
+        let mut doer_state = self.doers.state.get()?;
+
+        T::new_state(&self.state)?;
+
+        doer_state
+            .message_queue
+            .push(DoerControlMessage::AddScheduled(Box::new(T::new), schedule));
+
+        Ok(())
+    }
+}
@@ -1,13 +1,15 @@
 use std::{
     any::{type_name, Any},
     collections::HashMap,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
     rc::Rc,
     sync::{Arc, Mutex, MutexGuard},
 };
 
 use mut_cell::{MutCell, MutCellRef};
 
-use crate::PmError;
+use crate::{trace::*, PmError};
 
 // These derive names don't conflict with the trait names? Nice.
 pub use pm_macros::{SharedStateTrait, StateTrait};
@@ -43,8 +45,77 @@ impl<T> State<T> {
     ///
     /// TODO: May want to make this part of StateTrait instead, allow users to use
     /// their own sorts of state storage types.
-    pub fn get(&self) -> Result<MutCellRef<'_, T>, PmError> {
-        self.state.get().map_err(|_| PmError::GetState)
+    pub fn get(&self) -> Result<StateRef<'_, T>, PmError> {
+        let span = borrow_span(type_name::<T>());
+
+        Ok(StateRef {
+            state: self.state.get().map_err(|_| PmError::GetState)?,
+            _span: span,
+        })
+    }
+}
+
+/// Lets the state derives find [State] and Rc fields by their type rather than
+/// their name. `ThreadLocalCheck::<T>::THREAD_LOCAL` is the inherent const for
+/// those, and falls back to [NotThreadLocal]'s for everything else.
+#[doc(hidden)]
+pub struct ThreadLocalCheck<T: ?Sized>(PhantomData<T>);
+
+#[doc(hidden)]
+pub trait NotThreadLocal {
+    const THREAD_LOCAL: bool = false;
+}
+
+impl<T: ?Sized> NotThreadLocal for ThreadLocalCheck<T> {}
+
+impl<T> ThreadLocalCheck<State<T>> {
+    pub const THREAD_LOCAL: bool = true;
+}
+
+impl<T: ?Sized> ThreadLocalCheck<Rc<T>> {
+    pub const THREAD_LOCAL: bool = true;
+}
+
+/// Lets the Doer derive catch `State<T>` and `SharedState<T>` fields it only
+/// recognizes by name, like ones behind a `use` alias, instead of quietly
+/// falling back to their [Default]. Works the same way as [ThreadLocalCheck].
+#[doc(hidden)]
+pub struct StateHandleCheck<T: ?Sized>(PhantomData<T>);
+
+#[doc(hidden)]
+pub trait NotStateHandle {
+    const STATE_HANDLE: bool = false;
+}
+
+impl<T: ?Sized> NotStateHandle for StateHandleCheck<T> {}
+
+impl<T> StateHandleCheck<State<T>> {
+    pub const STATE_HANDLE: bool = true;
+}
+
+impl<T> StateHandleCheck<SharedState<T>> {
+    pub const STATE_HANDLE: bool = true;
+}
+
+/// A borrowed [State]. Derefs to the state, the same as the MutCellRef inside.
+/// It only exists to close the borrow's [Span] when tracing.
+pub struct StateRef<'a, T> {
+    // Dropped before the span so the span covers the whole borrow.
+    state: MutCellRef<'a, T>,
+    _span: Option<Span>,
+}
+
+impl<T> Deref for StateRef<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.state
+    }
+}
+
+impl<T> DerefMut for StateRef<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.state
     }
 }
 
@@ -67,6 +138,16 @@ impl<T> Clone for State<T> {
 
 pub trait StateTrait: Any {
     fn as_any(&self) -> &dyn Any;
+
+    /// Construct the state from its [Default]. The derive generates this when
+    /// the type is marked with `#[pm(default)]`, which lets a doer's
+    /// [crate::DoerTrait::new_state] use [LocalStore::add_default_state].
+    fn default_state() -> Option<Self>
+    where
+        Self: Sized,
+    {
+        None
+    }
 }
 
 impl<T> StateTrait for Rc<MutCell<T>>
@@ -91,14 +172,45 @@ impl<T> SharedState<T> {
 
     /// Accessor to get to the internal state. Non-blocking since we don't
     /// want to block the doer loop.
-    pub fn get(&self) -> Result<MutexGuard<'_, T>, PmError> {
-        self.state.try_lock().map_err(|_| PmError::GetState)
+    pub fn get(&self) -> Result<SharedStateRef<'_, T>, PmError> {
+        let span = borrow_span(type_name::<T>());
+
+        Ok(SharedStateRef {
+            state: self.state.try_lock().map_err(|_| PmError::GetState)?,
+            _span: span,
+        })
     }
 
     /// Use a block lock for when you need to wait for some shared state to
-    /// become available and you don't care to wait.
-    pub fn blocking_get(&self) -> Result<MutexGuard<'_, T>, PmError> {
-        self.state.lock().map_err(|_| PmError::GetStateBlocking)
+    /// become available and you don't care to wait. When tracing, the borrow's
+    /// span includes the wait.
+    pub fn blocking_get(&self) -> Result<SharedStateRef<'_, T>, PmError> {
+        let span = borrow_span(type_name::<T>());
+
+        Ok(SharedStateRef {
+            state: self.state.lock().map_err(|_| PmError::GetStateBlocking)?,
+            _span: span,
+        })
+    }
+}
+
+/// A locked [SharedState], the [StateRef] of shared state.
+pub struct SharedStateRef<'a, T> {
+    state: MutexGuard<'a, T>,
+    _span: Option<Span>,
+}
+
+impl<T> Deref for SharedStateRef<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.state
+    }
+}
+
+impl<T> DerefMut for SharedStateRef<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.state
     }
 }
 
@@ -119,8 +231,19 @@ impl<T> Clone for SharedState<T> {
     }
 }
 
-pub trait SharedStateTrait: Any {
+/// Shared state is handed between threads, so it must be [Send]. The derive
+/// also calls out [Rc] and [State] fields by their type, through
+/// [ThreadLocalCheck], including ones behind a `use` alias.
+pub trait SharedStateTrait: Any + Send {
     fn as_any(&self) -> &dyn Any;
+
+    /// See [StateTrait::default_state].
+    fn default_state() -> Option<Self>
+    where
+        Self: Sized,
+    {
+        None
+    }
 }
 
 impl<T> SharedStateTrait for Arc<Mutex<T>>
@@ -190,6 +313,25 @@ impl LocalStore {
         Ok(())
     }
 
+    /// Add state constructed by [StateTrait::default_state].
+    pub fn add_default_state<T: StateTrait>(&mut self) -> Result<(), PmError> {
+        let state = T::default_state().ok_or(PmError::NoDefaultState)?;
+
+        self.add_state(state)
+    }
+
+    /// Add a handle to state that already lives in another store, like a
+    /// child [crate::Pm]'s. Both stores then hand out the same state.
+    pub fn add_state_handle<T: StateTrait>(&mut self, state: State<T>) -> Result<(), PmError> {
+        if self.state_exists::<T>() {
+            return Err(PmError::StateExists);
+        }
+
+        self.store.insert(type_name::<T>(), Box::new(state.state));
+
+        Ok(())
+    }
+
     /// Get state from the store. 
     pub fn get_state<T: StateTrait>(&self) -> Result<State<T>, PmError> {
         let Some(boxed_state) = self.store.get(type_name::<T>()) else {
@@ -228,6 +370,20 @@ impl LocalStore {
     pub fn state_exists<T: StateTrait>(&self) -> bool {
         self.store.contains_key(type_name::<T>())
     }
+
+    /// The type names of all state in the store.
+    pub fn state_names(&self) -> Vec<&'static str> {
+        self.store.keys().copied().collect()
+    }
+
+    /// Remove state by its type name, for when the type itself isn't known,
+    /// like when tearing down a library.
+    pub fn remove_state_by_name(&mut self, name: &str) -> Result<(), PmError> {
+        self.store
+            .remove(name)
+            .map(|_| ())
+            .ok_or(PmError::StateDoesNotExist)
+    }
 }
 
 pub struct SharedStore {
@@ -255,6 +411,13 @@ impl SharedStore {
         Ok(())
     }
 
+    /// Add state constructed by [SharedStateTrait::default_state].
+    pub fn add_default_state<T: SharedStateTrait>(&mut self) -> Result<(), PmError> {
+        let state = T::default_state().ok_or(PmError::NoDefaultState)?;
+
+        self.add_state(state)
+    }
+
     pub fn get_state<T: SharedStateTrait>(&self) -> Result<SharedState<T>, PmError> {
         let Some(boxed_state) = self.store.get(type_name::<T>()) else {
             return Err(PmError::StateDoesNotExist);
@@ -291,6 +454,19 @@ impl SharedStore {
     pub fn state_exists<T: SharedStateTrait>(&self) -> bool {
         self.store.contains_key(type_name::<T>())
     }
+
+    /// The type names of all state in the store.
+    pub fn state_names(&self) -> Vec<&'static str> {
+        self.store.keys().copied().collect()
+    }
+
+    /// Remove state by its type name, for when the type itself isn't known.
+    pub fn remove_state_by_name(&mut self, name: &str) -> Result<(), PmError> {
+        self.store
+            .remove(name)
+            .map(|_| ())
+            .ok_or(PmError::StateDoesNotExist)
+    }
 }
 
 pub struct StateStore {
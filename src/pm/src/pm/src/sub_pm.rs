@@ -0,0 +1,92 @@
+use crate::{doer::*, pm::*, state::*, PmError};
+use mut_cell::MutCell;
+use std::marker::PhantomData;
+
+/// Describes a child [Pm] run by a [SubPm]. Subsystems can be packaged this way
+/// and tested on their own by building the child directly.
+pub trait SubPmTrait: 'static {
+    /// Build the child and add its doers. This is called from the [SubPm]'s
+    /// new_state, so only the parent's [StateStore] is available. The child
+    /// gets its own [LocalStore]. Build it with `Pm::new(parent.shared.clone())`
+    /// to share the parent's [SharedStore], or [Pm::with_shared_state] to keep
+    /// it separate too.
+    fn build(parent: &StateStore) -> Result<Pm, PmError>;
+
+    /// Make child state available to the parent's doers, see [expose_state].
+    /// This runs right after build, before any parent doer is created.
+    fn expose(_child: &Pm, _parent: &StateStore) -> Result<(), PmError> {
+        Ok(())
+    }
+
+    /// How many times the child updates per parent update.
+    fn updates_per_update() -> usize {
+        1
+    }
+}
+
+/// Put a handle to the child's state into the parent's [LocalStore], so the
+/// parent's doers can get it like their own. The state still belongs to the
+/// child, the stores just share it.
+pub fn expose_state<T: StateTrait>(child: &Pm, parent: &StateStore) -> Result<(), PmError> {
+    let state = child.state.local.get()?.get_state::<T>()?;
+
+    parent.local.get()?.add_state_handle(state)
+}
+
+/// Holds the child between the [SubPm]'s new_state and new.
+#[derive(StateTrait)]
+struct SubPmChild<T> {
+    pm: Option<Pm>,
+    sub_pm: PhantomData<fn() -> T>,
+}
+
+/// A doer that runs a child [Pm]. The child runs its first along with the
+/// parent's doers, then updates [SubPmTrait::updates_per_update] times on every
+/// parent update. Any error from the child makes the SubPm inactive.
+pub struct SubPm<T> {
+    child: MutCell<Pm>,
+    sub_pm: PhantomData<fn() -> T>,
+}
+
+impl<T: SubPmTrait> DoerTrait for SubPm<T> {
+    fn new_state(state: &StateStore) -> Result<(), PmError>
+    where
+        Self: Sized,
+    {
+        let child = T::build(state)?;
+
+        T::expose(&child, state)?;
+
+        state.local.get()?.add_state(SubPmChild::<T> {
+            pm: Some(child),
+            sub_pm: PhantomData,
+        })
+    }
+
+    fn new(pm: &Pm) -> Result<Box<dyn DoerTrait>, PmError>
+    where
+        Self: Sized,
+    {
+        let child = pm.state.local.get()?.remove_state::<SubPmChild<T>>()?;
+        let child = child.get()?.pm.take().ok_or(PmError::StateDoesNotExist)?;
+
+        Ok(Box::new(Self {
+            child: MutCell::new(child),
+            sub_pm: PhantomData,
+        }))
+    }
+
+    fn first(&self, _pm: &Pm) -> Result<(), PmError> {
+        self.child.get().map_err(|_| PmError::GetState)?.first()
+    }
+
+    fn update(&self) -> Result<(), PmError> {
+        let mut child = self.child.get().map_err(|_| PmError::GetState)?;
+
+        for _ in 0..T::updates_per_update() {
+            child.update()?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,324 @@
+use crate::{pm::*, state::*, PmError};
+use std::{
+    cell::{Cell, RefCell},
+    fmt::Write as _,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+/// A finished span.
+struct TraceEvent {
+    name: &'static str,
+    category: &'static str,
+    start: Duration,
+    duration: Duration,
+    thread: u64,
+}
+
+/// Spans collected from every thread tracing into it. It lives in the
+/// [SharedStore], so every [Pm] built on the same store traces into it once
+/// tracing is enabled, including the ones on threads started by the
+/// ThreadManager.
+///
+/// Each thread buffers its spans and hands them over at the end of each of its
+/// Pm's updates, and when the thread exits.
+#[derive(SharedStateTrait)]
+pub struct Trace {
+    /// Span times are relative to this.
+    start: Instant,
+    events: Vec<TraceEvent>,
+    /// The id and name of every thread that has traced.
+    threads: Vec<(u64, String)>,
+    /// Also record a span for every [State] and [SharedState] borrow. These
+    /// are frequent, so they're off by default.
+    pub state_borrows: bool,
+}
+
+impl Default for Trace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Trace {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            events: Vec::new(),
+            threads: Vec::new(),
+            state_borrows: false,
+        }
+    }
+
+    /// How many spans have been handed over so far.
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// The spans in the Chrome trace event format, which Perfetto and
+    /// chrome://tracing can open.
+    pub fn chrome_trace(&self) -> String {
+        let mut json = String::from("{\"traceEvents\":[");
+        let mut first = true;
+
+        for (thread, name) in self.threads.iter() {
+            if !first {
+                json.push(',');
+            }
+
+            first = false;
+
+            let _ = write!(
+                json,
+                "{{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":1,\"tid\":{},\"args\":{{\"name\":\"{}\"}}}}",
+                thread,
+                escape(name),
+            );
+        }
+
+        for event in self.events.iter() {
+            if !first {
+                json.push(',');
+            }
+
+            first = false;
+
+            let _ = write!(
+                json,
+                "{{\"name\":\"{}\",\"cat\":\"{}\",\"ph\":\"X\",\"ts\":{:.3},\"dur\":{:.3},\"pid\":1,\"tid\":{}}}",
+                escape(event.name),
+                escape(event.category),
+                event.start.as_secs_f64() * 1_000_000.0,
+                event.duration.as_secs_f64() * 1_000_000.0,
+                event.thread,
+            );
+        }
+
+        json.push_str("]}");
+
+        json
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => {
+                let _ = write!(escaped, "\\u{:04x}", c as u32);
+            }
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// The tracing state of a single thread.
+struct ThreadTrace {
+    trace: SharedState<Trace>,
+    start: Instant,
+    thread: u64,
+    state_borrows: bool,
+    buffer: Vec<TraceEvent>,
+}
+
+impl ThreadTrace {
+    /// Hand the buffered spans over to the [Trace]. When blocking is off and
+    /// another thread holds the trace, they stay buffered for next time.
+    fn flush(&mut self, blocking: bool) {
+        if self.buffer.is_empty() {
+            return;
+        }
+
+        let trace = if blocking {
+            self.trace.blocking_get()
+        } else {
+            self.trace.get()
+        };
+
+        if let Ok(mut trace) = trace {
+            trace.events.append(&mut self.buffer);
+            self.state_borrows = trace.state_borrows;
+        }
+    }
+}
+
+impl Drop for ThreadTrace {
+    fn drop(&mut self) {
+        // The thread is exiting, so nothing can be traced past this point.
+        let _ = TRACING.try_with(|tracing| tracing.set(false));
+
+        self.flush(true);
+    }
+}
+
+thread_local! {
+    /// Checked before anything else, so spans cost next to nothing while
+    /// tracing is off.
+    static TRACING: Cell<bool> = const { Cell::new(false) };
+    static THREAD_TRACE: RefCell<Option<ThreadTrace>> = const { RefCell::new(None) };
+}
+
+/// Start tracing this thread into the [Trace] in the shared store, if there is
+/// one. [Pm::new] calls this, so it is only needed on threads that don't build
+/// a Pm.
+pub fn attach_trace(shared_state: &SharedState<SharedStore>) -> Result<(), PmError> {
+    if TRACING.with(Cell::get) {
+        return Ok(());
+    }
+
+    let shared_store = shared_state.blocking_get()?;
+
+    if !shared_store.state_exists::<Trace>() {
+        return Ok(());
+    }
+
+    let trace = shared_store.get_state::<Trace>()?;
+
+    drop(shared_store);
+
+    let mut trace_data = trace.blocking_get()?;
+    let thread = trace_data.threads.len() as u64 + 1;
+    let name = match std::thread::current().name() {
+        Some(name) => name.to_string(),
+        None => format!("thread {}", thread),
+    };
+
+    trace_data.threads.push((thread, name));
+
+    let thread_trace = ThreadTrace {
+        trace: trace.clone(),
+        start: trace_data.start,
+        thread,
+        state_borrows: trace_data.state_borrows,
+        buffer: Vec::new(),
+    };
+
+    drop(trace_data);
+
+    THREAD_TRACE.with(|thread_trace_cell| *thread_trace_cell.borrow_mut() = Some(thread_trace));
+    TRACING.with(|tracing| tracing.set(true));
+
+    Ok(())
+}
+
+/// Hand this thread's spans over to the [Trace] without blocking.
+pub(crate) fn flush_trace() {
+    if !TRACING.with(Cell::get) {
+        return;
+    }
+
+    THREAD_TRACE.with(|thread_trace| {
+        if let Ok(mut thread_trace) = thread_trace.try_borrow_mut() {
+            if let Some(thread_trace) = thread_trace.as_mut() {
+                thread_trace.flush(false);
+            }
+        }
+    });
+}
+
+/// An open span, recorded when dropped.
+pub struct Span {
+    name: &'static str,
+    category: &'static str,
+    start: Instant,
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        let end = Instant::now();
+
+        let _ = THREAD_TRACE.try_with(|thread_trace| {
+            // The trace is busy flushing, so the span is dropped.
+            let Ok(mut thread_trace) = thread_trace.try_borrow_mut() else {
+                return;
+            };
+
+            if let Some(thread_trace) = thread_trace.as_mut() {
+                thread_trace.buffer.push(TraceEvent {
+                    name: self.name,
+                    category: self.category,
+                    start: self.start.saturating_duration_since(thread_trace.start),
+                    duration: end - self.start,
+                    thread: thread_trace.thread,
+                });
+            }
+        });
+    }
+}
+
+/// Open a span if this thread is tracing. Keep the result alive for as long as
+/// the span should last.
+pub fn span(name: &'static str, category: &'static str) -> Option<Span> {
+    if !TRACING.try_with(Cell::get).unwrap_or(false) {
+        return None;
+    }
+
+    Some(Span {
+        name,
+        category,
+        start: Instant::now(),
+    })
+}
+
+/// Same as [span], for [State] and [SharedState] borrows.
+pub(crate) fn borrow_span(name: &'static str) -> Option<Span> {
+    if !TRACING.try_with(Cell::get).unwrap_or(false) {
+        return None;
+    }
+
+    let state_borrows = THREAD_TRACE
+        .try_with(|thread_trace| match thread_trace.try_borrow() {
+            Ok(thread_trace) => thread_trace
+                .as_ref()
+                .is_some_and(|thread_trace| thread_trace.state_borrows),
+            Err(_) => false,
+        })
+        .unwrap_or(false);
+
+    if !state_borrows {
+        return None;
+    }
+
+    span(name, "state")
+}
+
+impl Pm {
+    /// Start tracing every [Pm] that uses this Pm's [SharedStore], on every
+    /// thread. Pms on other threads pick it up the next time they are built.
+    pub fn enable_tracing(&self) -> Result<(), PmError> {
+        let mut shared_store = self.state.shared.blocking_get()?;
+
+        if !shared_store.state_exists::<Trace>() {
+            shared_store.add_state(Trace::new())?;
+        }
+
+        drop(shared_store);
+
+        attach_trace(&self.state.shared)
+    }
+
+    /// Write everything traced so far as a Chrome trace JSON file. Spans other
+    /// threads haven't handed over yet aren't included.
+    pub fn write_trace(&self, path: impl AsRef<Path>) -> Result<(), PmError> {
+        THREAD_TRACE.with(|thread_trace| {
+            if let Some(thread_trace) = thread_trace.borrow_mut().as_mut() {
+                thread_trace.flush(true);
+            }
+        });
+
+        let trace = self.state.shared.blocking_get()?.get_state::<Trace>()?;
+
+        let json = trace.blocking_get()?.chrome_trace();
+
+        std::fs::write(path, json).map_err(|err| PmError::WriteTrace(err.kind()))
+    }
+}
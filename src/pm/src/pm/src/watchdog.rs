@@ -0,0 +1,380 @@
+use crate::{pm::*, PmError};
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+/// A doer entered by the [Pm], as its name and the name of the [crate::Phase].
+pub type DoerEntry = (&'static str, &'static str);
+
+/// What went over its budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverrunKind {
+    /// A single doer's phase.
+    Doer,
+    /// A whole [Pm::update].
+    Tick,
+}
+
+/// A budget the watchdog caught being exceeded.
+#[derive(Debug, Clone)]
+pub struct Overrun {
+    pub kind: OverrunKind,
+    /// The doer running when the overrun was caught, if any.
+    pub doer: Option<DoerEntry>,
+    /// How long it had been running when caught. It may have run for longer.
+    pub elapsed: Duration,
+    pub budget: Duration,
+    /// The last doers entered, oldest first. The last one is the one running,
+    /// or the one that ran last if the tick was between doers.
+    pub trail: Vec<DoerEntry>,
+}
+
+type OverrunFn = Box<dyn Fn(&Overrun) + Send>;
+
+/// Watches a [Pm] from another thread for doers that block the tick. Doers
+/// aren't interrupted, the watchdog only notices them. Every overrun is
+/// counted and handed to [Self::on_overrun], which is the place to log it,
+/// and the last few are kept by the [WatchdogHandle].
+///
+/// Only the doers' phases are watched. [crate::DoerTrait::first] is allowed to take
+/// its time.
+///
+/// Watchdog::new(Duration::from_millis(5)).tick_budget(Duration::from_millis(16)).abort()
+pub struct Watchdog {
+    doer_budget: Duration,
+    tick_budget: Option<Duration>,
+    check_every: Option<Duration>,
+    trail_len: usize,
+    overruns_len: usize,
+    abort: bool,
+    on_overrun: Option<OverrunFn>,
+}
+
+impl Watchdog {
+    /// The default amount of doers kept in the trail.
+    pub const DEFAULT_TRAIL_LEN: usize = 16;
+
+    /// The default amount of overruns kept.
+    pub const DEFAULT_OVERRUNS_LEN: usize = 64;
+
+    /// Watch for any doer phase that runs for longer than the budget.
+    pub fn new(doer_budget: Duration) -> Self {
+        Self {
+            doer_budget,
+            tick_budget: None,
+            check_every: None,
+            trail_len: Self::DEFAULT_TRAIL_LEN,
+            overruns_len: Self::DEFAULT_OVERRUNS_LEN,
+            abort: false,
+            on_overrun: None,
+        }
+    }
+
+    /// Also watch for updates that take longer than the budget as a whole.
+    pub fn tick_budget(mut self, tick_budget: Duration) -> Self {
+        self.tick_budget = Some(tick_budget);
+
+        self
+    }
+
+    /// How often the watchdog thread checks. This defaults to a quarter of the
+    /// smallest budget, so overruns are caught at most that much late.
+    pub fn check_every(mut self, check_every: Duration) -> Self {
+        self.check_every = Some(check_every);
+
+        self
+    }
+
+    /// How many of the last doers entered are kept for reports.
+    pub fn trail(mut self, trail_len: usize) -> Self {
+        self.trail_len = trail_len.max(1);
+
+        self
+    }
+
+    /// How many of the last overruns are kept. Older ones are only counted.
+    pub fn overruns(mut self, overruns_len: usize) -> Self {
+        self.overruns_len = overruns_len.max(1);
+
+        self
+    }
+
+    /// Stop the [Pm] on the first overrun. The blocking doer still has to
+    /// return, after which update returns [PmError::WatchdogAbort] at the end
+    /// of the phase.
+    pub fn abort(mut self) -> Self {
+        self.abort = true;
+
+        self
+    }
+
+    /// Called from the watchdog thread for every overrun.
+    pub fn on_overrun(mut self, on_overrun: impl Fn(&Overrun) + Send + 'static) -> Self {
+        self.on_overrun = Some(Box::new(on_overrun));
+
+        self
+    }
+
+    fn start(self) -> WatchdogHandle {
+        let smallest_budget = match self.tick_budget {
+            Some(tick_budget) => tick_budget.min(self.doer_budget),
+            None => self.doer_budget,
+        };
+
+        let check_every = self
+            .check_every
+            .unwrap_or(smallest_budget / 4)
+            .max(Duration::from_micros(100));
+
+        let shared = Arc::new(WatchdogShared {
+            status: Mutex::new(WatchdogStatus {
+                current: None,
+                doer_reported: false,
+                tick_start: None,
+                tick_reported: false,
+                trail: VecDeque::with_capacity(self.trail_len),
+                overruns: VecDeque::with_capacity(self.overruns_len),
+                overrun_count: 0,
+                first_overrun: None,
+            }),
+            aborted: AtomicBool::new(false),
+            stop: AtomicBool::new(false),
+        });
+
+        let thread_shared = shared.clone();
+        let trail_len = self.trail_len;
+
+        let thread = std::thread::spawn(move || self.watch(&thread_shared, check_every));
+
+        WatchdogHandle {
+            shared,
+            trail_len,
+            thread: Some(thread),
+        }
+    }
+
+    /// The watchdog thread's loop.
+    fn watch(self, shared: &WatchdogShared, check_every: Duration) {
+        while !shared.stop.load(Ordering::Relaxed) {
+            // Parked instead of slept so stopping doesn't wait out the check.
+            std::thread::park_timeout(check_every);
+
+            if shared.stop.load(Ordering::Relaxed) {
+                break;
+            }
+
+            let now = Instant::now();
+            let mut status = shared.status();
+            let mut overruns = Vec::new();
+
+            if let Some((doer, entered)) = status.current {
+                let elapsed = now.duration_since(entered);
+
+                if !status.doer_reported && elapsed > self.doer_budget {
+                    status.doer_reported = true;
+                    overruns.push(status.overrun(
+                        OverrunKind::Doer,
+                        Some(doer),
+                        elapsed,
+                        self.doer_budget,
+                    ));
+                }
+            }
+
+            if let (Some(tick_budget), Some(tick_start)) = (self.tick_budget, status.tick_start) {
+                let elapsed = now.duration_since(tick_start);
+
+                if !status.tick_reported && elapsed > tick_budget {
+                    status.tick_reported = true;
+
+                    let doer = status.current.map(|(doer, _)| doer);
+
+                    overruns.push(status.overrun(OverrunKind::Tick, doer, elapsed, tick_budget));
+                }
+            }
+
+            for overrun in overruns.iter() {
+                status.push_overrun(overrun.clone(), self.overruns_len);
+            }
+
+            drop(status);
+
+            for overrun in overruns.iter() {
+                if let Some(on_overrun) = self.on_overrun.as_ref() {
+                    on_overrun(overrun);
+                }
+
+                if self.abort {
+                    shared.aborted.store(true, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+}
+
+/// What the [Pm] is doing, as seen by the watchdog thread.
+struct WatchdogStatus {
+    /// The doer running and when it was entered.
+    current: Option<(DoerEntry, Instant)>,
+    doer_reported: bool,
+    tick_start: Option<Instant>,
+    tick_reported: bool,
+    trail: VecDeque<DoerEntry>,
+    /// The last overruns, oldest first.
+    overruns: VecDeque<Overrun>,
+    overrun_count: u64,
+    /// Kept for [WatchdogHandle::check_abort] after it leaves overruns.
+    first_overrun: Option<Overrun>,
+}
+
+impl WatchdogStatus {
+    fn push_overrun(&mut self, overrun: Overrun, overruns_len: usize) {
+        if self.first_overrun.is_none() {
+            self.first_overrun = Some(overrun.clone());
+        }
+
+        if self.overruns.len() == overruns_len {
+            self.overruns.pop_front();
+        }
+
+        self.overruns.push_back(overrun);
+        self.overrun_count += 1;
+    }
+
+    fn overrun(
+        &self,
+        kind: OverrunKind,
+        doer: Option<DoerEntry>,
+        elapsed: Duration,
+        budget: Duration,
+    ) -> Overrun {
+        Overrun {
+            kind,
+            doer,
+            elapsed,
+            budget,
+            trail: self.trail.iter().copied().collect(),
+        }
+    }
+}
+
+struct WatchdogShared {
+    status: Mutex<WatchdogStatus>,
+    aborted: AtomicBool,
+    stop: AtomicBool,
+}
+
+impl WatchdogShared {
+    /// A doer panicking mid update shouldn't take the watchdog down with it.
+    fn status(&self) -> MutexGuard<'_, WatchdogStatus> {
+        self.status
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// A running [Watchdog]. Dropping it stops the watchdog thread.
+pub struct WatchdogHandle {
+    shared: Arc<WatchdogShared>,
+    trail_len: usize,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl WatchdogHandle {
+    /// The last overruns caught, oldest first. See [Watchdog::overruns].
+    pub fn overruns(&self) -> Vec<Overrun> {
+        self.shared.status().overruns.iter().cloned().collect()
+    }
+
+    /// How many overruns were caught, including those no longer kept.
+    pub fn overrun_count(&self) -> u64 {
+        self.shared.status().overrun_count
+    }
+
+    /// Set once an overrun is caught by a watchdog made with [Watchdog::abort].
+    pub fn aborted(&self) -> bool {
+        self.shared.aborted.load(Ordering::Relaxed)
+    }
+
+    /// The last doers entered, oldest first.
+    pub fn trail(&self) -> Vec<DoerEntry> {
+        self.shared.status().trail.iter().copied().collect()
+    }
+
+    pub(crate) fn start_tick(&self) {
+        let mut status = self.shared.status();
+
+        status.tick_start = Some(Instant::now());
+        status.tick_reported = false;
+    }
+
+    pub(crate) fn end_tick(&self) {
+        self.shared.status().tick_start = None;
+    }
+
+    pub(crate) fn enter(&self, doer: DoerEntry) {
+        let mut status = self.shared.status();
+
+        if status.trail.len() == self.trail_len {
+            status.trail.pop_front();
+        }
+
+        status.trail.push_back(doer);
+        status.current = Some((doer, Instant::now()));
+        status.doer_reported = false;
+    }
+
+    pub(crate) fn exit(&self) {
+        self.shared.status().current = None;
+    }
+
+    /// Error with the first overrun if the watchdog aborted the [Pm].
+    pub(crate) fn check_abort(&self) -> Result<(), PmError> {
+        if !self.aborted() {
+            return Ok(());
+        }
+
+        match self.shared.status().first_overrun.as_ref() {
+            Some(overrun) => Err(PmError::WatchdogAbort(Box::new(overrun.clone()))),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for WatchdogHandle {
+    fn drop(&mut self) {
+        self.shared.stop.store(true, Ordering::Relaxed);
+
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Pm {
+    /// Start watching this Pm's doers from a new thread.
+    pub fn start_watchdog(&mut self, watchdog: Watchdog) -> Result<(), PmError> {
+        if self.doers.watchdog.is_some() {
+            return Err(PmError::WatchdogExists);
+        }
+
+        self.doers.watchdog = Some(watchdog.start());
+
+        Ok(())
+    }
+
+    /// Stop the watchdog thread, returning the last overruns it caught.
+    pub fn stop_watchdog(&mut self) -> Result<Vec<Overrun>, PmError> {
+        match self.doers.watchdog.take() {
+            Some(watchdog) => Ok(watchdog.overruns()),
+            None => Err(PmError::WatchdogDoesNotExist),
+        }
+    }
+}
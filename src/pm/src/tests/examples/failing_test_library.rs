@@ -0,0 +1,44 @@
+//! Loaded by the library tests. Its second doer's new_state fails after the
+//! first has added its state.
+use pm::*;
+
+#[derive(StateTrait)]
+pub struct AddedBeforeFailing;
+
+pub struct AddsState;
+
+impl DoerTrait for AddsState {
+    fn new_state(state: &StateStore) -> Result<(), PmError>
+    where
+        Self: Sized,
+    {
+        state.local.get()?.add_state(AddedBeforeFailing)
+    }
+
+    fn new(_pm: &Pm) -> Result<Box<dyn DoerTrait>, PmError>
+    where
+        Self: Sized,
+    {
+        Ok(Box::new(Self))
+    }
+}
+
+pub struct FailsNewState;
+
+impl DoerTrait for FailsNewState {
+    fn new_state(_state: &StateStore) -> Result<(), PmError>
+    where
+        Self: Sized,
+    {
+        Err(PmError::AddNewState)
+    }
+
+    fn new(_pm: &Pm) -> Result<Box<dyn DoerTrait>, PmError>
+    where
+        Self: Sized,
+    {
+        Ok(Box::new(Self))
+    }
+}
+
+pm_library!(AddsState, FailsNewState);
@@ -0,0 +1,37 @@
+//! Loaded by the library tests.
+use pm::*;
+
+#[derive(StateTrait)]
+pub struct TestLibraryCount {
+    pub count: u64,
+}
+
+pub struct TestLibraryDoer {
+    count: State<TestLibraryCount>,
+}
+
+impl DoerTrait for TestLibraryDoer {
+    fn new_state(state: &StateStore) -> Result<(), PmError>
+    where
+        Self: Sized,
+    {
+        state.local.get()?.add_state(TestLibraryCount { count: 0 })
+    }
+
+    fn new(pm: &Pm) -> Result<Box<dyn DoerTrait>, PmError>
+    where
+        Self: Sized,
+    {
+        Ok(Box::new(Self {
+            count: pm.state.local.get()?.get_state::<TestLibraryCount>()?,
+        }))
+    }
+
+    fn update(&self) -> Result<(), PmError> {
+        self.count.get()?.count += 1;
+
+        Ok(())
+    }
+}
+
+pm_library!(TestLibraryDoer);
@@ -0,0 +1,98 @@
+use pm::*;
+use pm_common::async_bridge::*;
+use std::time::{Duration, Instant};
+
+#[derive(StateTrait, Default)]
+#[pm(default)]
+struct Response(Option<u64>);
+
+/// Spawns a slow request on first and checks for the response every update.
+#[derive(Doer)]
+struct Requester {
+    #[pm(init = Response::default())]
+    response: State<Response>,
+    runtime: State<AsyncRuntime>,
+}
+
+impl DoerTrait for Requester {
+    derived_doer!();
+
+    fn first(&self, _pm: &Pm) -> Result<(), PmError> {
+        let request = async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            42
+        };
+
+        self.runtime
+            .get()?
+            .spawn_into(&self.response, request, |response, value| {
+                response.0 = Some(value)
+            });
+
+        Ok(())
+    }
+}
+
+fn requester_pm() -> Result<Pm, PmError> {
+    let mut pm = Pm::with_shared_state()?;
+
+    pm.add_doer::<AsyncBridge>()?;
+    pm.add_doer::<Requester>()?;
+    pm.first()?;
+
+    Ok(pm)
+}
+
+#[test]
+fn spawn_into_state() -> Result<(), PmError> {
+    let mut pm = requester_pm()?;
+    let response = pm.state.local.get()?.get_state::<Response>()?;
+    let runtime = pm.state.local.get()?.get_state::<AsyncRuntime>()?;
+
+    assert_eq!(runtime.get()?.pending(), 1);
+
+    let mut ticks = 0;
+
+    while response.get()?.0.is_none() {
+        pm.update()?;
+        ticks += 1;
+
+        assert!(ticks < 1000, "the future never finished");
+    }
+
+    assert_eq!(response.get()?.0, Some(42));
+    assert_eq!(runtime.get()?.pending(), 0);
+
+    Ok(())
+}
+
+#[test]
+fn bounded_poll() -> Result<(), PmError> {
+    let mut pm = requester_pm()?;
+    let runtime = pm.state.local.get()?.get_state::<AsyncRuntime>()?;
+
+    runtime
+        .get()?
+        .spawn(async { tokio::time::sleep(Duration::from_secs(60)).await });
+
+    runtime.get()?.poll_budget = Duration::from_millis(100);
+
+    // The request finishes within the budget, the sleep keeps the poll going
+    // until the budget runs out.
+    let start = Instant::now();
+    pm.update()?;
+
+    assert!(start.elapsed() >= Duration::from_millis(100));
+    assert!(start.elapsed() < Duration::from_secs(1));
+    assert_eq!(runtime.get()?.pending(), 1);
+
+    runtime.get()?.poll_budget = Duration::ZERO;
+
+    let start = Instant::now();
+    pm.update()?;
+
+    assert!(start.elapsed() < Duration::from_millis(50));
+    assert_eq!(runtime.get()?.pending(), 1);
+
+    Ok(())
+}
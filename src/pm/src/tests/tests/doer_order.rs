@@ -0,0 +1,198 @@
+use pm::*;
+use std::any::type_name;
+
+macro_rules! doers {
+    ($($doer:ident),+) => {
+        $(
+            struct $doer;
+
+            impl DoerTrait for $doer {
+                fn new(_pm: &Pm) -> Result<Box<dyn DoerTrait>, PmError> {
+                    Ok(Box::new(Self))
+                }
+            }
+        )+
+    };
+}
+
+doers!(A, B, C, D);
+
+/// Build a pm with the doers A, B, C, D in that order.
+fn abcd() -> Result<Pm, PmError> {
+    let mut pm = Pm::with_shared_state()?;
+
+    pm.add_doer::<A>()?;
+    pm.add_doer::<B>()?;
+    pm.add_doer::<C>()?;
+    pm.add_doer::<D>()?;
+
+    pm.first()?;
+
+    Ok(pm)
+}
+
+/// The active doers' names with the module path stripped.
+fn order(pm: &Pm) -> Vec<&'static str> {
+    pm.doers
+        .active
+        .iter()
+        .map(|doer| doer.name().rsplit("::").next().unwrap())
+        .collect()
+}
+
+/// Queue the message and let the pm handle it.
+fn apply(pm: &mut Pm, message: DoerControlMessage) -> Result<(), PmError> {
+    pm.doers.state.get()?.message_queue.push(message);
+
+    pm.first()
+}
+
+#[test]
+fn move_before() -> Result<(), PmError> {
+    let cases = [
+        (type_name::<D>(), type_name::<B>(), ["A", "D", "B", "C"]),
+        (type_name::<A>(), type_name::<C>(), ["B", "A", "C", "D"]),
+        (type_name::<D>(), type_name::<A>(), ["D", "A", "B", "C"]),
+        (type_name::<A>(), type_name::<B>(), ["A", "B", "C", "D"]),
+        (type_name::<B>(), type_name::<B>(), ["A", "B", "C", "D"]),
+    ];
+
+    for (move_doer, before_doer, expected) in cases {
+        let mut pm = abcd()?;
+
+        apply(
+            &mut pm,
+            DoerControlMessage::MoveBefore(move_doer, before_doer),
+        )?;
+
+        assert_eq!(order(&pm), expected);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn move_after() -> Result<(), PmError> {
+    let cases = [
+        (type_name::<A>(), type_name::<C>(), ["B", "C", "A", "D"]),
+        (type_name::<D>(), type_name::<A>(), ["A", "D", "B", "C"]),
+        (type_name::<A>(), type_name::<D>(), ["B", "C", "D", "A"]),
+        (type_name::<B>(), type_name::<A>(), ["A", "B", "C", "D"]),
+    ];
+
+    for (move_doer, after_doer, expected) in cases {
+        let mut pm = abcd()?;
+
+        apply(
+            &mut pm,
+            DoerControlMessage::MoveAfter(move_doer, after_doer),
+        )?;
+
+        assert_eq!(order(&pm), expected);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn move_to_index() -> Result<(), PmError> {
+    let cases = [
+        (type_name::<A>(), 2, ["B", "C", "A", "D"]),
+        (type_name::<D>(), 0, ["D", "A", "B", "C"]),
+        (type_name::<A>(), 3, ["B", "C", "D", "A"]),
+        (type_name::<C>(), 2, ["A", "B", "C", "D"]),
+    ];
+
+    for (move_doer, index, expected) in cases {
+        let mut pm = abcd()?;
+
+        apply(&mut pm, DoerControlMessage::MoveToIndex(move_doer, index))?;
+
+        assert_eq!(order(&pm), expected);
+    }
+
+    let mut pm = abcd()?;
+
+    assert!(matches!(
+        apply(
+            &mut pm,
+            DoerControlMessage::MoveToIndex(type_name::<A>(), 4)
+        ),
+        Err(PmError::DoerIndexOutOfBounds)
+    ));
+    assert_eq!(order(&pm), ["A", "B", "C", "D"]);
+
+    Ok(())
+}
+
+#[test]
+fn swap() -> Result<(), PmError> {
+    let mut pm = abcd()?;
+
+    apply(
+        &mut pm,
+        DoerControlMessage::Swap(type_name::<A>(), type_name::<C>()),
+    )?;
+
+    assert_eq!(order(&pm), ["C", "B", "A", "D"]);
+
+    Ok(())
+}
+
+#[test]
+fn replace() -> Result<(), PmError> {
+    doers!(E);
+
+    let mut pm = Pm::with_shared_state()?;
+
+    pm.add_doer::<A>()?;
+    pm.add_doer::<B>()?;
+    pm.add_doer::<C>()?;
+    pm.first()?;
+
+    apply(
+        &mut pm,
+        DoerControlMessage::Replace(type_name::<B>(), Box::new(E::new)),
+    )?;
+
+    assert_eq!(order(&pm), ["A", "E", "C"]);
+
+    let doer_state = pm.doers.state.get()?;
+
+    assert_eq!(doer_state.inactive.len(), 1);
+    assert!(matches!(
+        &doer_state.inactive[0],
+        DoerInactive::Removed(doer) if doer.name() == type_name::<B>()
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn unknown_doer() -> Result<(), PmError> {
+    doers!(Missing);
+
+    let missing = type_name::<Missing>();
+    let messages = [
+        DoerControlMessage::MoveBefore(missing, type_name::<A>()),
+        DoerControlMessage::MoveBefore(type_name::<A>(), missing),
+        DoerControlMessage::MoveAfter(missing, type_name::<A>()),
+        DoerControlMessage::MoveAfter(type_name::<A>(), missing),
+        DoerControlMessage::MoveToIndex(missing, 0),
+        DoerControlMessage::Swap(type_name::<A>(), missing),
+        DoerControlMessage::Replace(missing, Box::new(A::new)),
+        DoerControlMessage::Remove(missing),
+    ];
+
+    for message in messages {
+        let mut pm = abcd()?;
+
+        assert!(matches!(
+            apply(&mut pm, message),
+            Err(PmError::DoerDoesNotExist)
+        ));
+        assert_eq!(order(&pm), ["A", "B", "C", "D"]);
+    }
+
+    Ok(())
+}
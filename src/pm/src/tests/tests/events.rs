@@ -0,0 +1,138 @@
+use pm::*;
+
+#[test]
+fn events_live_two_swaps() {
+    let mut events = Events::new();
+    let reader = EventReader::new();
+
+    events.send(1);
+    events.send(2);
+
+    assert_eq!(reader.read(&events).collect::<Vec<_>>(), [&1, &2]);
+    assert_eq!(reader.read(&events).count(), 0);
+
+    events.swap();
+    events.send(3);
+
+    // A reader that hasn't read yet still sees the previous loop's events.
+    let late_reader = EventReader::new();
+
+    assert_eq!(reader.read(&events).collect::<Vec<_>>(), [&3]);
+    assert_eq!(late_reader.read(&events).collect::<Vec<_>>(), [&1, &2, &3]);
+
+    events.swap();
+    events.swap();
+
+    assert!(events.is_empty());
+    assert_eq!(reader.read(&events).count(), 0);
+}
+
+#[test]
+fn missed_events() {
+    let mut events = Events::new();
+    let reader = EventReader::new();
+
+    events.send_batch([1, 2, 3]);
+    events.swap();
+    events.send(4);
+    events.swap();
+    events.send(5);
+
+    assert_eq!(reader.missed(&events), 3);
+    assert_eq!(reader.read(&events).collect::<Vec<_>>(), [&4, &5]);
+    assert_eq!(reader.missed(&events), 0);
+
+    events.send(6);
+    events.clear();
+    events.send(7);
+
+    assert_eq!(events.len(), 1);
+    assert_eq!(reader.read(&events).collect::<Vec<_>>(), [&7]);
+}
+
+#[derive(StateTrait, Default)]
+#[pm(default)]
+struct Received {
+    first: Vec<u64>,
+    second: Vec<u64>,
+}
+
+/// Sends the loop count every loop.
+#[derive(Doer)]
+struct Sender {
+    events: State<Events<u64>>,
+    #[pm(init = Received::default())]
+    _received: State<Received>,
+    sent: std::cell::Cell<u64>,
+}
+
+impl DoerTrait for Sender {
+    derived_doer!();
+
+    fn update(&self) -> Result<(), PmError> {
+        let sent = self.sent.get() + 1;
+
+        self.sent.set(sent);
+        self.events.get()?.send(sent);
+
+        Ok(())
+    }
+}
+
+macro_rules! reader {
+    ($doer:ident, $field:ident) => {
+        #[derive(Doer)]
+        struct $doer {
+            events: State<Events<u64>>,
+            received: State<Received>,
+            reader: EventReader<u64>,
+        }
+
+        impl DoerTrait for $doer {
+            derived_doer!();
+
+            fn update(&self) -> Result<(), PmError> {
+                let events = self.events.get()?;
+
+                self.received
+                    .get()?
+                    .$field
+                    .extend(self.reader.read(&events).copied());
+
+                Ok(())
+            }
+        }
+    };
+}
+
+reader!(FirstReader, first);
+reader!(SecondReader, second);
+
+#[test]
+fn readers_fan_out() -> Result<(), PmError> {
+    let mut pm = Pm::with_shared_state()?;
+
+    // The first reader runs before the sender, so it sees each event one loop
+    // later, from the previous buffer.
+    pm.add_doer::<EventUpdater<u64>>()?;
+    pm.add_doer::<FirstReader>()?;
+    pm.add_doer::<Sender>()?;
+    pm.add_doer::<SecondReader>()?;
+    pm.first()?;
+
+    for _ in 0..4 {
+        pm.update()?;
+    }
+
+    let received = pm.state.local.get()?.get_state::<Received>()?;
+    let received = received.get()?;
+
+    assert_eq!(received.first, [1, 2, 3]);
+    assert_eq!(received.second, [1, 2, 3, 4]);
+
+    let events = pm.state.local.get()?.get_state::<Events<u64>>()?;
+
+    assert_eq!(events.get()?.iter().collect::<Vec<_>>(), [&4]);
+
+    Ok(())
+}
@@ -0,0 +1,129 @@
+use pm::*;
+use std::{
+    env::consts::{DLL_PREFIX, DLL_SUFFIX},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Build one of this crate's example libraries. It gets its own target
+/// directory, since cargo holds the lock on the one running the tests.
+fn build_library(name: &str) -> PathBuf {
+    let target_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("libraries");
+
+    let status = Command::new(env!("CARGO"))
+        .args(["build", "--quiet", "--package", "pm_tests", "--example", name])
+        .arg("--target-dir")
+        .arg(&target_dir)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .status()
+        .expect("cargo should run");
+
+    assert!(status.success(), "building {} failed", name);
+
+    target_dir
+        .join("debug")
+        .join("examples")
+        .join(format!("{}{}{}", DLL_PREFIX, name, DLL_SUFFIX))
+}
+
+fn local_state_names(pm: &Pm) -> Result<Vec<&'static str>, PmError> {
+    Ok(pm.state.local.get()?.state_names())
+}
+
+fn has_state(names: &[&str], name: &str) -> bool {
+    names.iter().any(|state_name| state_name.ends_with(name))
+}
+
+#[test]
+fn load_and_unload_library() -> Result<(), PmError> {
+    let path = build_library("test_library");
+    let mut pm = Pm::with_shared_state()?;
+
+    pm.first()?;
+    pm.load_library(&path)?;
+
+    assert!(matches!(pm.load_library(&path), Err(PmError::LibraryLoaded)));
+    assert!(has_state(&local_state_names(&pm)?, "TestLibraryCount"));
+
+    // The doer is added on the next control message pass.
+    pm.update()?;
+    pm.update()?;
+
+    assert_eq!(pm.doers.active.len(), 1);
+
+    pm.unload_library(&path)?;
+
+    assert!(pm.doers.active.is_empty());
+    assert!(!has_state(&local_state_names(&pm)?, "TestLibraryCount"));
+    assert!(pm.libraries.loaded.is_empty());
+    assert!(matches!(pm.unload_library(&path), Err(PmError::LibraryNotLoaded)));
+
+    // It can come back after being closed.
+    pm.load_library(&path)?;
+
+    assert!(has_state(&local_state_names(&pm)?, "TestLibraryCount"));
+
+    pm.unload_library(&path)?;
+
+    Ok(())
+}
+
+#[test]
+fn unload_before_doers_are_created() -> Result<(), PmError> {
+    let path = build_library("test_library");
+    let mut pm = Pm::with_shared_state()?;
+
+    pm.first()?;
+    pm.load_library(&path)?;
+    pm.doers
+        .state
+        .get()?
+        .message_queue
+        .push(DoerControlMessage::PausePm);
+
+    // Unloading only drops the library's pending doers, the rest of the queue
+    // is left for the next pass.
+    pm.unload_library(&path)?;
+
+    assert!(pm.libraries.loaded.is_empty());
+    assert_eq!(pm.doers.state.get()?.message_queue.len(), 2);
+    assert!(!pm.paused);
+
+    // With no library doers created there is nothing to update.
+    assert!(matches!(pm.update(), Err(PmError::DoerUpdate)));
+    assert!(pm.doers.active.is_empty());
+    assert!(pm.paused);
+
+    Ok(())
+}
+
+#[test]
+fn failing_new_state_rolls_back() -> Result<(), PmError> {
+    let path = build_library("failing_test_library");
+    let mut pm = Pm::with_shared_state()?;
+
+    pm.first()?;
+
+    let before = local_state_names(&pm)?;
+
+    assert!(matches!(pm.load_library(&path), Err(PmError::AddNewState)));
+    assert_eq!(local_state_names(&pm)?, before);
+    assert!(pm.libraries.loaded.is_empty());
+
+    // None of its doers were queued to reach into the closed library.
+    assert!(pm.doers.state.get()?.message_queue.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn missing_library() -> Result<(), PmError> {
+    let mut pm = Pm::with_shared_state()?;
+
+    assert!(matches!(
+        pm.load_library("does/not/exist.so"),
+        Err(PmError::LoadLibrary)
+    ));
+
+    Ok(())
+}
@@ -0,0 +1,31 @@
+use pm::*;
+use pm_common::loop_timing::*;
+use std::time::{Duration, Instant};
+
+#[test]
+fn paused_loops_are_timed() -> Result<(), PmError> {
+    let mut pm = Pm::with_shared_state()?;
+
+    pm.add_doer::<LoopTimingManager>()?;
+    pm.first()?;
+
+    {
+        let timing = pm.state.local.get()?.get_state::<LoopTiming>()?;
+        let mut timing = timing.get()?;
+
+        timing.desired_loop_duration = Duration::from_millis(10);
+        timing.loop_sleep_duration = Duration::from_millis(10);
+    }
+
+    pm.pause();
+
+    let start = Instant::now();
+
+    for _ in 0..5 {
+        pm.update()?;
+    }
+
+    assert!(start.elapsed() >= Duration::from_millis(30));
+
+    Ok(())
+}
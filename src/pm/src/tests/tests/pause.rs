@@ -0,0 +1,223 @@
+use pm::*;
+use std::any::type_name;
+
+#[derive(StateTrait, Default)]
+#[pm(default)]
+struct Runs {
+    first: usize,
+    second: usize,
+    debugger: usize,
+}
+
+#[derive(Doer)]
+struct First {
+    #[pm(init = Runs::default())]
+    runs: State<Runs>,
+}
+
+impl DoerTrait for First {
+    derived_doer!();
+
+    fn update(&self) -> Result<(), PmError> {
+        self.runs.get()?.first += 1;
+
+        Ok(())
+    }
+}
+
+#[derive(Doer)]
+struct Second {
+    runs: State<Runs>,
+}
+
+impl DoerTrait for Second {
+    derived_doer!();
+
+    fn update(&self) -> Result<(), PmError> {
+        self.runs.get()?.second += 1;
+
+        Ok(())
+    }
+}
+
+/// Steps the paused pm once every other paused loop, then resumes it.
+#[derive(Doer)]
+struct Debugger {
+    runs: State<Runs>,
+    #[pm(value = pm.doers.state.clone())]
+    doer_state: State<DoerState>,
+}
+
+impl DoerTrait for Debugger {
+    derived_doer!();
+
+    fn phases(&self) -> &'static [Phase] {
+        &[Phase::Paused]
+    }
+
+    fn paused(&self) -> Result<(), PmError> {
+        let mut runs = self.runs.get()?;
+
+        runs.debugger += 1;
+
+        let message = match runs.debugger {
+            2 => DoerControlMessage::StepPm,
+            4 => DoerControlMessage::ResumePm,
+            _ => return Ok(()),
+        };
+
+        self.doer_state.get()?.message_queue.push(message);
+
+        Ok(())
+    }
+}
+
+fn runs(pm: &Pm) -> Result<State<Runs>, PmError> {
+    pm.state.local.get()?.get_state::<Runs>()
+}
+
+fn push(pm: &Pm, message: DoerControlMessage) -> Result<(), PmError> {
+    pm.doers.state.get()?.message_queue.push(message);
+
+    Ok(())
+}
+
+fn tick(pm: &mut Pm, ticks: usize) -> Result<(), PmError> {
+    for _ in 0..ticks {
+        pm.update()?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn pause_and_resume_doer() -> Result<(), PmError> {
+    let mut pm = Pm::with_shared_state()?;
+
+    pm.add_doer::<First>()?;
+    pm.add_doer::<Second>()?;
+    pm.first()?;
+
+    push(&pm, DoerControlMessage::Pause(type_name::<First>()))?;
+    tick(&mut pm, 3)?;
+
+    let runs = runs(&pm)?;
+
+    assert_eq!(runs.get()?.first, 0);
+    assert_eq!(runs.get()?.second, 3);
+
+    push(&pm, DoerControlMessage::Resume(type_name::<First>()))?;
+    tick(&mut pm, 2)?;
+
+    assert_eq!(runs.get()?.first, 2);
+
+    // The doer kept its instance and its place.
+    assert_eq!(pm.doers.doer_index(type_name::<First>())?, 0);
+    assert!(pm.doers.state.get()?.inactive.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn pause_unknown_doer() -> Result<(), PmError> {
+    let mut pm = Pm::with_shared_state()?;
+
+    pm.add_doer::<First>()?;
+    pm.first()?;
+
+    push(&pm, DoerControlMessage::Pause(type_name::<Second>()))?;
+
+    assert!(matches!(pm.update(), Err(PmError::DoerDoesNotExist)));
+
+    Ok(())
+}
+
+#[test]
+fn paused_doers_skip_their_schedule() -> Result<(), PmError> {
+    let mut pm = Pm::with_shared_state()?;
+
+    pm.add_scheduled_doer::<First>(Schedule::every_ticks(2))?;
+    pm.first()?;
+
+    tick(&mut pm, 1)?;
+    push(&pm, DoerControlMessage::Pause(type_name::<First>()))?;
+    tick(&mut pm, 5)?;
+    push(&pm, DoerControlMessage::Resume(type_name::<First>()))?;
+
+    // The schedule picks up where it left off, so it is one tick into waiting
+    // for its next run instead of being due right away.
+    tick(&mut pm, 1)?;
+
+    let runs = runs(&pm)?;
+
+    assert_eq!(runs.get()?.first, 1);
+
+    tick(&mut pm, 1)?;
+
+    assert_eq!(runs.get()?.first, 2);
+
+    Ok(())
+}
+
+#[test]
+fn pause_and_step_pm() -> Result<(), PmError> {
+    let mut pm = Pm::with_shared_state()?;
+
+    pm.add_doer::<First>()?;
+    pm.first()?;
+
+    pm.pause();
+    tick(&mut pm, 3)?;
+
+    let runs = runs(&pm)?;
+
+    assert_eq!(runs.get()?.first, 0);
+
+    pm.step();
+    pm.step();
+    tick(&mut pm, 5)?;
+
+    assert_eq!(runs.get()?.first, 2);
+
+    pm.resume();
+    tick(&mut pm, 2)?;
+
+    assert_eq!(runs.get()?.first, 4);
+
+    // Stepping only counts while paused.
+    pm.step();
+    pm.pause();
+    tick(&mut pm, 1)?;
+
+    assert_eq!(runs.get()?.first, 4);
+
+    Ok(())
+}
+
+#[test]
+fn paused_phase_controls_pm() -> Result<(), PmError> {
+    let mut pm = Pm::with_shared_state()?;
+
+    pm.add_doer::<First>()?;
+    pm.add_doer::<Debugger>()?;
+    pm.first()?;
+
+    push(&pm, DoerControlMessage::PausePm)?;
+
+    // Paused loops 1 and 2, the step, paused loops 3 and 4, then the resume
+    // is handled at the start of the sixth loop.
+    tick(&mut pm, 6)?;
+
+    let runs = runs(&pm)?;
+
+    assert_eq!(runs.get()?.debugger, 4);
+    assert_eq!(runs.get()?.first, 2);
+    assert!(!pm.paused);
+
+    tick(&mut pm, 1)?;
+
+    assert_eq!(runs.get()?.first, 3);
+    assert_eq!(runs.get()?.debugger, 4);
+
+    Ok(())
+}
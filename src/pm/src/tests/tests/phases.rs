@@ -0,0 +1,187 @@
+use pm::*;
+use std::{cell::RefCell, rc::Rc};
+
+/// Every phase call made during the test, in the order they were made.
+#[derive(StateTrait, Default)]
+#[pm(default)]
+struct Calls(Vec<String>);
+
+fn record(calls: &State<Calls>, call: &str) -> Result<(), PmError> {
+    calls.get()?.0.push(call.to_string());
+
+    Ok(())
+}
+
+macro_rules! phase_doer {
+    ($doer:ident, [$($phase:expr),*]) => {
+        #[derive(Doer)]
+        struct $doer {
+            calls: State<Calls>,
+        }
+
+        impl DoerTrait for $doer {
+            fn new_state(state: &StateStore) -> Result<(), PmError> {
+                // Several doers share the state, so only the first adds it.
+                let _ = state.local.get()?.add_default_state::<Calls>();
+
+                Ok(())
+            }
+
+            fn new(pm: &Pm) -> Result<Box<dyn DoerTrait>, PmError> {
+                Ok(Box::new(<Self as DerivedDoer>::derived_new(pm)?))
+            }
+
+            fn phases(&self) -> &'static [Phase] {
+                &[$($phase),*]
+            }
+
+            fn pre_update(&self) -> Result<(), PmError> {
+                record(&self.calls, concat!(stringify!($doer), " pre_update"))
+            }
+
+            fn update(&self) -> Result<(), PmError> {
+                record(&self.calls, concat!(stringify!($doer), " update"))
+            }
+
+            fn post_update(&self) -> Result<(), PmError> {
+                record(&self.calls, concat!(stringify!($doer), " post_update"))
+            }
+
+            fn custom_phase(&self, phase: &'static str) -> Result<(), PmError> {
+                record(&self.calls, &format!("{} {}", stringify!($doer), phase))
+            }
+        }
+    };
+}
+
+phase_doer!(Flusher, [Phase::PostUpdate]);
+phase_doer!(Worker, [Phase::Update]);
+phase_doer!(
+    Everything,
+    [
+        Phase::PreUpdate,
+        Phase::Update,
+        Phase::PostUpdate,
+        Phase::Custom("render")
+    ]
+);
+
+fn calls(pm: &Pm) -> Result<Vec<String>, PmError> {
+    let calls = pm.state.local.get()?.get_state::<Calls>()?;
+    let calls = std::mem::take(&mut calls.get()?.0);
+
+    Ok(calls)
+}
+
+#[test]
+fn phases_run_in_order() -> Result<(), PmError> {
+    let mut pm = Pm::with_shared_state()?;
+
+    // The flusher is first in the list, but still runs after every update.
+    pm.add_doer::<Flusher>()?;
+    pm.add_doer::<Worker>()?;
+    pm.add_doer::<Everything>()?;
+    pm.first()?;
+
+    pm.update()?;
+
+    assert_eq!(
+        calls(&pm)?,
+        [
+            "Everything pre_update",
+            "Worker update",
+            "Everything update",
+            "Flusher post_update",
+            "Everything post_update",
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn custom_phase() -> Result<(), PmError> {
+    let mut pm = Pm::with_shared_state()?;
+
+    pm.add_doer::<Worker>()?;
+    pm.add_doer::<Everything>()?;
+    pm.first()?;
+
+    pm.add_phase_after(Phase::Custom("render"), Phase::Update)?;
+
+    assert!(matches!(
+        pm.add_phase_before(Phase::Custom("render"), Phase::PreUpdate),
+        Err(PmError::PhaseExists)
+    ));
+    assert!(matches!(
+        pm.add_phase_before(Phase::Custom("audio"), Phase::Custom("physics")),
+        Err(PmError::PhaseDoesNotExist)
+    ));
+
+    pm.update()?;
+
+    assert_eq!(
+        calls(&pm)?,
+        [
+            "Everything pre_update",
+            "Worker update",
+            "Everything update",
+            "Everything render",
+            "Everything post_update",
+        ]
+    );
+
+    Ok(())
+}
+
+struct Failing {
+    runs: Rc<RefCell<usize>>,
+}
+
+thread_local! {
+    static FAILING_RUNS: Rc<RefCell<usize>> = Rc::new(RefCell::new(0));
+}
+
+impl DoerTrait for Failing {
+    fn new(_pm: &Pm) -> Result<Box<dyn DoerTrait>, PmError> {
+        Ok(Box::new(Self {
+            runs: FAILING_RUNS.with(Rc::clone),
+        }))
+    }
+
+    fn phases(&self) -> &'static [Phase] {
+        &[Phase::PreUpdate, Phase::PostUpdate]
+    }
+
+    fn pre_update(&self) -> Result<(), PmError> {
+        *self.runs.borrow_mut() += 1;
+
+        Err(PmError::DoerUpdate)
+    }
+
+    fn post_update(&self) -> Result<(), PmError> {
+        *self.runs.borrow_mut() += 1;
+
+        Ok(())
+    }
+}
+
+#[test]
+fn errored_doers_skip_later_phases() -> Result<(), PmError> {
+    let mut pm = Pm::with_shared_state()?;
+
+    pm.add_doer::<Failing>()?;
+    pm.add_doer::<Failing>()?;
+    pm.add_doer::<Worker>()?;
+    pm.first()?;
+
+    pm.update()?;
+
+    // Both failing doers are removed, and neither reaches post_update.
+    assert_eq!(FAILING_RUNS.with(|runs| *runs.borrow()), 2);
+    assert_eq!(pm.doers.active.len(), 1);
+    assert_eq!(pm.doers.state.get()?.inactive.len(), 2);
+    assert_eq!(calls(&pm)?, ["Worker update"]);
+
+    Ok(())
+}
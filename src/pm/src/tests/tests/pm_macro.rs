@@ -0,0 +1,6 @@
+#[test]
+fn pm_macro_ui() {
+    let t = trybuild::TestCases::new();
+    t.pass("tests/ui/pm_macro/pass_*.rs");
+    t.compile_fail("tests/ui/pm_macro/fail_*.rs");
+}
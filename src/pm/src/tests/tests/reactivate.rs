@@ -0,0 +1,263 @@
+use pm::*;
+use std::{any::type_name, cell::Cell};
+
+#[derive(StateTrait, Default)]
+#[pm(default)]
+struct Runs {
+    flaky_updates: usize,
+    flaky_firsts: usize,
+    fail_first: bool,
+}
+
+/// Fails its first update, then works.
+#[derive(Doer)]
+struct Flaky {
+    #[pm(init = Runs::default())]
+    runs: State<Runs>,
+    failed: Cell<bool>,
+}
+
+impl DoerTrait for Flaky {
+    derived_doer!();
+
+    fn first(&self, _pm: &Pm) -> Result<(), PmError> {
+        let mut runs = self.runs.get()?;
+
+        runs.flaky_firsts += 1;
+
+        if runs.fail_first {
+            return Err(PmError::DoerFirst);
+        }
+
+        Ok(())
+    }
+
+    fn update(&self) -> Result<(), PmError> {
+        if !self.failed.replace(true) {
+            return Err(PmError::DoerUpdate);
+        }
+
+        self.runs.get()?.flaky_updates += 1;
+
+        Ok(())
+    }
+}
+
+macro_rules! doers {
+    ($($doer:ident),+) => {
+        $(
+            struct $doer;
+
+            impl DoerTrait for $doer {
+                fn new(_pm: &Pm) -> Result<Box<dyn DoerTrait>, PmError> {
+                    Ok(Box::new(Self))
+                }
+            }
+        )+
+    };
+}
+
+doers!(A, B);
+
+fn push(pm: &Pm, message: DoerControlMessage) -> Result<(), PmError> {
+    pm.doers.state.get()?.message_queue.push(message);
+
+    Ok(())
+}
+
+fn order(pm: &Pm) -> Vec<&'static str> {
+    pm.doers
+        .active
+        .iter()
+        .map(|doer| doer.name().rsplit("::").next().unwrap())
+        .collect()
+}
+
+#[test]
+fn reactivate_errored_doer() -> Result<(), PmError> {
+    let mut pm = Pm::with_shared_state()?;
+
+    pm.add_doer::<A>()?;
+    pm.add_doer::<Flaky>()?;
+    pm.add_doer::<B>()?;
+    pm.first()?;
+
+    pm.update()?;
+
+    assert_eq!(order(&pm), ["A", "B"]);
+
+    push(
+        &pm,
+        DoerControlMessage::Reactivate(type_name::<Flaky>(), DoerPosition::After(type_name::<A>())),
+    )?;
+    pm.update()?;
+
+    // The same instance is back, so it remembers it already failed once.
+    assert_eq!(order(&pm), ["A", "Flaky", "B"]);
+    assert!(pm.doers.state.get()?.inactive.is_empty());
+
+    let runs = pm.state.local.get()?.get_state::<Runs>()?;
+
+    assert_eq!(runs.get()?.flaky_updates, 1);
+    assert_eq!(runs.get()?.flaky_firsts, 1);
+
+    Ok(())
+}
+
+#[test]
+fn reactivate_first() -> Result<(), PmError> {
+    let mut pm = Pm::with_shared_state()?;
+
+    pm.add_doer::<Flaky>()?;
+    pm.add_doer::<A>()?;
+    pm.first()?;
+    pm.update()?;
+
+    let runs = pm.state.local.get()?.get_state::<Runs>()?;
+
+    push(
+        &pm,
+        DoerControlMessage::ReactivateFirst(type_name::<Flaky>(), DoerPosition::Start),
+    )?;
+    pm.update()?;
+
+    assert_eq!(order(&pm), ["Flaky", "A"]);
+    assert_eq!(runs.get()?.flaky_firsts, 2);
+
+    // A failing first sends the doer straight back.
+    push(&pm, DoerControlMessage::Remove(type_name::<Flaky>()))?;
+    pm.update()?;
+
+    runs.get()?.fail_first = true;
+
+    push(
+        &pm,
+        DoerControlMessage::ReactivateFirst(type_name::<Flaky>(), DoerPosition::End),
+    )?;
+    pm.update()?;
+
+    assert_eq!(order(&pm), ["A"]);
+    assert!(matches!(
+        pm.doers.state.get()?.inactive.last(),
+        Some(DoerInactive::FirstErr(_, PmError::DoerFirst))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn reactivate_errors() -> Result<(), PmError> {
+    let mut pm = Pm::with_shared_state()?;
+
+    pm.add_doer::<A>()?;
+    pm.add_doer::<B>()?;
+    pm.first()?;
+
+    push(
+        &pm,
+        DoerControlMessage::Reactivate(type_name::<B>(), DoerPosition::End),
+    )?;
+
+    assert!(matches!(
+        pm.update(),
+        Err(PmError::InactiveDoerDoesNotExist)
+    ));
+
+    push(&pm, DoerControlMessage::Remove(type_name::<B>()))?;
+    pm.update()?;
+
+    // A bad position leaves the doer in the inactive list.
+    for position in [
+        DoerPosition::Index(5),
+        DoerPosition::Before(type_name::<Flaky>()),
+    ] {
+        push(
+            &pm,
+            DoerControlMessage::Reactivate(type_name::<B>(), position),
+        )?;
+
+        assert!(pm.update().is_err());
+        assert_eq!(pm.doers.state.get()?.inactive.len(), 1);
+    }
+
+    push(
+        &pm,
+        DoerControlMessage::Reactivate(type_name::<B>(), DoerPosition::Index(0)),
+    )?;
+    pm.update()?;
+
+    assert_eq!(order(&pm), ["B", "A"]);
+
+    Ok(())
+}
+
+#[test]
+fn inactive_is_bounded() -> Result<(), PmError> {
+    let mut pm = Pm::with_shared_state()?;
+
+    pm.add_doer::<A>()?;
+    pm.first()?;
+
+    pm.doers.state.get()?.max_inactive = 3;
+
+    for _ in 0..5 {
+        pm.add_doer::<B>()?;
+        pm.update()?;
+        push(&pm, DoerControlMessage::Remove(type_name::<B>()))?;
+        pm.update()?;
+    }
+
+    let mut doer_state = pm.doers.state.get()?;
+
+    assert_eq!(doer_state.inactive.len(), 3);
+
+    doer_state.max_inactive = 1;
+    doer_state.trim_inactive();
+
+    assert_eq!(doer_state.inactive.len(), 1);
+
+    Ok(())
+}
+
+/// Pauses the pm on its way out.
+#[derive(Doer)]
+struct PauseOnRemove {
+    #[pm(value = pm.doers.state.clone())]
+    doer_state: State<DoerState>,
+}
+
+impl DoerTrait for PauseOnRemove {
+    derived_doer!();
+
+    fn remove(&self) -> Result<(), PmError> {
+        self.doer_state
+            .get()?
+            .message_queue
+            .push(DoerControlMessage::PausePm);
+
+        Ok(())
+    }
+}
+
+#[test]
+fn remove_can_queue_messages() -> Result<(), PmError> {
+    let mut pm = Pm::with_shared_state()?;
+
+    pm.add_doer::<A>()?;
+    pm.add_doer::<PauseOnRemove>()?;
+    pm.first()?;
+
+    push(&pm, DoerControlMessage::Remove(type_name::<PauseOnRemove>()))?;
+    pm.update()?;
+
+    assert!(matches!(
+        pm.doers.state.get()?.inactive.as_slice(),
+        [DoerInactive::Removed(_)]
+    ));
+
+    pm.update()?;
+
+    assert!(pm.paused);
+
+    Ok(())
+}
@@ -0,0 +1,204 @@
+use pm::*;
+use std::path::PathBuf;
+
+/// Stands in for the outside world, like a socket. Not recorded.
+#[derive(StateTrait)]
+struct Source {
+    next: u64,
+    step: u64,
+}
+
+/// What the sensor read this loop.
+#[derive(StateTrait, Default)]
+#[pm(default)]
+struct Reading(u64);
+
+impl InputState for Reading {
+    fn record(&self, buffer: &mut Vec<u8>) -> Result<(), PmError> {
+        buffer.extend_from_slice(&self.0.to_le_bytes());
+
+        Ok(())
+    }
+
+    fn replay(&mut self, recorded: &[u8]) -> Result<(), PmError> {
+        let bytes = recorded
+            .try_into()
+            .map_err(|_| PmError::RecordingMismatch)?;
+
+        self.0 = u64::from_le_bytes(bytes);
+
+        Ok(())
+    }
+}
+
+#[derive(StateTrait, Default)]
+#[pm(default)]
+struct Totals(Vec<u64>);
+
+#[derive(Doer)]
+struct Sensor {
+    source: State<Source>,
+    #[pm(init = Reading::default())]
+    reading: State<Reading>,
+}
+
+impl DoerTrait for Sensor {
+    derived_doer!();
+
+    fn phases(&self) -> &'static [Phase] {
+        &[Phase::PreUpdate]
+    }
+
+    fn pre_update(&self) -> Result<(), PmError> {
+        let mut source = self.source.get()?;
+
+        source.next += source.step;
+        self.reading.get()?.0 = source.next;
+
+        Ok(())
+    }
+}
+
+#[derive(Doer)]
+struct Integrator {
+    reading: State<Reading>,
+    #[pm(init = Totals::default())]
+    totals: State<Totals>,
+}
+
+impl DoerTrait for Integrator {
+    derived_doer!();
+
+    fn update(&self) -> Result<(), PmError> {
+        let reading = self.reading.get()?.0;
+        let mut totals = self.totals.get()?;
+        let total = totals.0.last().copied().unwrap_or(0) + reading;
+
+        totals.0.push(total);
+
+        Ok(())
+    }
+}
+
+fn recording() -> Recording {
+    Recording::new().input::<Reading>().producer::<Sensor>()
+}
+
+/// A pm whose sensor reads a different sequence for every step.
+fn pm(step: u64) -> Result<Pm, PmError> {
+    let mut pm = Pm::with_shared_state()?;
+
+    pm.state.local.get()?.add_state(Source { next: 0, step })?;
+    pm.add_doer::<Sensor>()?;
+    pm.add_doer::<Integrator>()?;
+    pm.first()?;
+
+    Ok(pm)
+}
+
+fn totals(pm: &Pm) -> Result<Vec<u64>, PmError> {
+    let totals = pm.state.local.get()?.get_state::<Totals>()?;
+    let totals = totals.get()?.0.clone();
+
+    Ok(totals)
+}
+
+fn recording_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("pm_{}_{}.rec", name, std::process::id()))
+}
+
+#[test]
+fn replay_reproduces_run() -> Result<(), PmError> {
+    let path = recording_path("replay_reproduces_run");
+
+    let mut recorded = pm(3)?;
+
+    recorded.start_recording(&path, &recording())?;
+
+    for _ in 0..5 {
+        recorded.update()?;
+    }
+
+    recorded.stop_recording()?;
+
+    assert_eq!(totals(&recorded)?, [3, 9, 18, 30, 45]);
+
+    // Left alone, this sensor would read something else entirely.
+    let mut replayed = pm(100)?;
+
+    replayed.start_replay(&path, &recording())?;
+
+    for _ in 0..5 {
+        replayed.update()?;
+    }
+
+    assert_eq!(totals(&replayed)?, totals(&recorded)?);
+    assert!(matches!(replayed.update(), Err(PmError::ReplayFinished)));
+
+    std::fs::remove_file(path).map_err(|err| PmError::RecordingIo(err.kind()))?;
+
+    Ok(())
+}
+
+#[test]
+fn replay_checks_inputs() -> Result<(), PmError> {
+    let path = recording_path("replay_checks_inputs");
+
+    let mut recorded = pm(1)?;
+
+    recorded.start_recording(&path, &Recording::new())?;
+    recorded.update()?;
+    recorded.stop_recording()?;
+
+    let mut replayed = pm(1)?;
+
+    assert!(matches!(
+        replayed.start_replay(&path, &recording()),
+        Err(PmError::RecordingMismatch)
+    ));
+    assert!(matches!(
+        replayed.stop_recording(),
+        Err(PmError::RecordingDoesNotExist)
+    ));
+    assert!(matches!(
+        replayed.start_recording(&path, &recording().after(Phase::Custom("missing"))),
+        Err(PmError::PhaseDoesNotExist)
+    ));
+
+    std::fs::remove_file(path).map_err(|err| PmError::RecordingIo(err.kind()))?;
+
+    Ok(())
+}
+
+#[test]
+fn replay_rejects_truncated_input() -> Result<(), PmError> {
+    let path = recording_path("replay_rejects_truncated_input");
+
+    let mut recorded = pm(1)?;
+
+    recorded.start_recording(&path, &recording())?;
+    recorded.update()?;
+    recorded.stop_recording()?;
+
+    // A second tick claiming far more bytes than the file has left.
+    let mut file = std::fs::read(&path).map_err(|err| PmError::RecordingIo(err.kind()))?;
+
+    file.extend_from_slice(&1u64.to_le_bytes());
+    file.extend_from_slice(&u64::MAX.to_le_bytes());
+    file.extend_from_slice(&[0; 4]);
+    std::fs::write(&path, file).map_err(|err| PmError::RecordingIo(err.kind()))?;
+
+    let mut replayed = pm(1)?;
+
+    replayed.start_replay(&path, &recording())?;
+    replayed.update()?;
+
+    assert!(matches!(
+        replayed.update(),
+        Err(PmError::RecordingIo(std::io::ErrorKind::UnexpectedEof))
+    ));
+
+    std::fs::remove_file(path).map_err(|err| PmError::RecordingIo(err.kind()))?;
+
+    Ok(())
+}
@@ -0,0 +1,298 @@
+use pm::*;
+use std::{any::type_name, time::Duration};
+
+/// How many times each doer ran, and whether the gated doer may run.
+#[derive(StateTrait, Default)]
+#[pm(default)]
+struct Runs {
+    counted: usize,
+    gated: usize,
+    open: bool,
+}
+
+#[derive(Doer)]
+struct Counted {
+    #[pm(init = Runs::default())]
+    runs: State<Runs>,
+}
+
+impl DoerTrait for Counted {
+    derived_doer!();
+
+    fn update(&self) -> Result<(), PmError> {
+        self.runs.get()?.counted += 1;
+
+        Ok(())
+    }
+}
+
+#[derive(Doer)]
+struct Gated {
+    runs: State<Runs>,
+}
+
+impl DoerTrait for Gated {
+    derived_doer!();
+
+    fn update(&self) -> Result<(), PmError> {
+        self.runs.get()?.gated += 1;
+
+        Ok(())
+    }
+}
+
+fn runs(pm: &Pm) -> Result<State<Runs>, PmError> {
+    pm.state.local.get()?.get_state::<Runs>()
+}
+
+fn tick(pm: &mut Pm, ticks: usize) -> Result<(), PmError> {
+    for _ in 0..ticks {
+        pm.update()?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn every_ticks() -> Result<(), PmError> {
+    let mut pm = Pm::with_shared_state()?;
+
+    pm.add_scheduled_doer::<Counted>(Schedule::every_ticks(3))?;
+    pm.first()?;
+
+    // Runs on ticks 1, 4 and 7.
+    tick(&mut pm, 7)?;
+
+    assert_eq!(runs(&pm)?.get()?.counted, 3);
+
+    Ok(())
+}
+
+#[test]
+fn every_duration() -> Result<(), PmError> {
+    let mut pm = Pm::with_shared_state()?;
+
+    pm.add_scheduled_doer::<Counted>(Schedule::every(Duration::from_millis(20)))?;
+    pm.first()?;
+
+    tick(&mut pm, 5)?;
+
+    assert_eq!(runs(&pm)?.get()?.counted, 1);
+
+    std::thread::sleep(Duration::from_millis(25));
+    tick(&mut pm, 5)?;
+
+    assert_eq!(runs(&pm)?.get()?.counted, 2);
+
+    Ok(())
+}
+
+#[test]
+fn run_if() -> Result<(), PmError> {
+    let mut pm = Pm::with_shared_state()?;
+
+    pm.add_doer::<Counted>()?;
+    pm.add_scheduled_doer::<Gated>(Schedule::every_tick().run_if::<Runs>(|runs| runs.open))?;
+    pm.first()?;
+
+    tick(&mut pm, 3)?;
+
+    let runs = runs(&pm)?;
+
+    assert_eq!(runs.get()?.counted, 3);
+    assert_eq!(runs.get()?.gated, 0);
+
+    runs.get()?.open = true;
+    tick(&mut pm, 2)?;
+
+    assert_eq!(runs.get()?.gated, 2);
+
+    Ok(())
+}
+
+#[test]
+fn rate_waits_for_condition() -> Result<(), PmError> {
+    let mut pm = Pm::with_shared_state()?;
+
+    pm.add_doer::<Counted>()?;
+    pm.add_scheduled_doer::<Gated>(Schedule::every_ticks(10).run_if::<Runs>(|runs| runs.open))?;
+    pm.first()?;
+
+    tick(&mut pm, 4)?;
+
+    let runs = runs(&pm)?;
+
+    // The first run was held back by the condition, so it happens right away.
+    runs.get()?.open = true;
+    tick(&mut pm, 1)?;
+
+    assert_eq!(runs.get()?.gated, 1);
+
+    tick(&mut pm, 9)?;
+
+    assert_eq!(runs.get()?.gated, 1);
+
+    Ok(())
+}
+
+#[test]
+fn unschedule() -> Result<(), PmError> {
+    let mut pm = Pm::with_shared_state()?;
+
+    pm.add_scheduled_doer::<Counted>(Schedule::every_ticks(100))?;
+    pm.first()?;
+
+    tick(&mut pm, 2)?;
+
+    pm.doers
+        .state
+        .get()?
+        .message_queue
+        .push(DoerControlMessage::Unschedule(type_name::<Counted>()));
+
+    tick(&mut pm, 2)?;
+
+    assert_eq!(runs(&pm)?.get()?.counted, 3);
+    assert!(pm.doers.schedules.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn schedule_unknown_doer() -> Result<(), PmError> {
+    let mut pm = Pm::with_shared_state()?;
+
+    pm.add_doer::<Counted>()?;
+    pm.first()?;
+
+    pm.doers
+        .state
+        .get()?
+        .message_queue
+        .push(DoerControlMessage::Schedule(
+            type_name::<Gated>(),
+            Schedule::every_ticks(2),
+        ));
+
+    assert!(matches!(pm.update(), Err(PmError::DoerDoesNotExist)));
+
+    Ok(())
+}
+
+#[test]
+fn removed_doers_drop_their_schedule() -> Result<(), PmError> {
+    let mut pm = Pm::with_shared_state()?;
+
+    pm.add_doer::<Counted>()?;
+    pm.add_scheduled_doer::<Gated>(Schedule::every_ticks(2))?;
+    pm.first()?;
+
+    assert_eq!(pm.doers.schedules.len(), 1);
+
+    pm.doers
+        .state
+        .get()?
+        .message_queue
+        .push(DoerControlMessage::Remove(type_name::<Gated>()));
+
+    tick(&mut pm, 1)?;
+
+    assert!(pm.doers.schedules.is_empty());
+
+    Ok(())
+}
+
+/// Whether the watched doer may run, kept apart from [Runs] so it can be held
+/// borrowed while the other doers keep counting.
+#[derive(StateTrait, Default)]
+#[pm(default)]
+struct Gate {
+    open: bool,
+}
+
+#[derive(Doer)]
+struct Watched {
+    runs: State<Runs>,
+    #[pm(init = Gate::default())]
+    _gate: State<Gate>,
+}
+
+impl DoerTrait for Watched {
+    derived_doer!();
+
+    fn update(&self) -> Result<(), PmError> {
+        self.runs.get()?.gated += 1;
+
+        Ok(())
+    }
+}
+
+#[test]
+fn failing_condition_skips_the_doer() -> Result<(), PmError> {
+    let mut pm = Pm::with_shared_state()?;
+
+    pm.add_doer::<Counted>()?;
+    pm.add_scheduled_doer::<Watched>(Schedule::every_tick().run_if::<Gate>(|gate| gate.open))?;
+    pm.first()?;
+
+    let gate = pm.state.local.get()?.get_state::<Gate>()?;
+    let mut held = gate.get()?;
+
+    held.open = true;
+
+    // The condition can't borrow the gate, so the doer sits out instead of
+    // failing the update.
+    tick(&mut pm, 2)?;
+
+    assert_eq!(runs(&pm)?.get()?.counted, 2);
+    assert_eq!(runs(&pm)?.get()?.gated, 0);
+
+    drop(held);
+    tick(&mut pm, 1)?;
+
+    assert_eq!(runs(&pm)?.get()?.gated, 1);
+
+    Ok(())
+}
+
+#[derive(Doer)]
+struct Renamed {
+    runs: State<Runs>,
+}
+
+impl DoerTrait for Renamed {
+    derived_doer!();
+
+    fn name(&self) -> &'static str {
+        "renamed"
+    }
+
+    fn update(&self) -> Result<(), PmError> {
+        self.runs.get()?.gated += 1;
+
+        Ok(())
+    }
+}
+
+#[test]
+fn scheduled_by_doer_name() -> Result<(), PmError> {
+    let mut pm = Pm::with_shared_state()?;
+
+    pm.add_doer::<Counted>()?;
+    pm.add_scheduled_doer::<Renamed>(Schedule::every_ticks(100))?;
+    pm.first()?;
+
+    assert!(pm.doers.schedules.contains_key("renamed"));
+
+    pm.doers
+        .state
+        .get()?
+        .message_queue
+        .push(DoerControlMessage::Unschedule("renamed"));
+
+    tick(&mut pm, 3)?;
+
+    assert_eq!(runs(&pm)?.get()?.gated, 3);
+
+    Ok(())
+}
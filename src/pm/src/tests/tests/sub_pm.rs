@@ -0,0 +1,127 @@
+use pm::*;
+
+/// Counted up by the child, read by the parent.
+#[derive(StateTrait, Default)]
+#[pm(default)]
+struct Physics {
+    steps: u64,
+}
+
+/// Only in the child's store.
+#[derive(StateTrait, Default)]
+#[pm(default)]
+struct PhysicsInternal;
+
+#[derive(Doer)]
+struct PhysicsStep {
+    #[pm(init = Physics::default())]
+    physics: State<Physics>,
+    #[pm(init = PhysicsInternal)]
+    _internal: State<PhysicsInternal>,
+}
+
+impl DoerTrait for PhysicsStep {
+    derived_doer!();
+
+    fn update(&self) -> Result<(), PmError> {
+        self.physics.get()?.steps += 1;
+
+        Ok(())
+    }
+}
+
+struct PhysicsPm;
+
+impl SubPmTrait for PhysicsPm {
+    fn build(parent: &StateStore) -> Result<Pm, PmError> {
+        let child = Pm::new(parent.shared.clone())?;
+
+        child.add_doer::<PhysicsStep>()?;
+
+        Ok(child)
+    }
+
+    fn expose(child: &Pm, parent: &StateStore) -> Result<(), PmError> {
+        expose_state::<Physics>(child, parent)
+    }
+
+    fn updates_per_update() -> usize {
+        4
+    }
+}
+
+type PhysicsSubPm = SubPm<PhysicsPm>;
+
+#[derive(StateTrait, Default)]
+#[pm(default)]
+struct Seen(Vec<u64>);
+
+/// A parent doer reading the exposed child state. It is added before the
+/// SubPm, but can still get the state in its new.
+#[derive(Doer)]
+struct Renderer {
+    physics: State<Physics>,
+    #[pm(init = Seen::default())]
+    seen: State<Seen>,
+}
+
+impl DoerTrait for Renderer {
+    derived_doer!();
+
+    fn update(&self) -> Result<(), PmError> {
+        let steps = self.physics.get()?.steps;
+
+        self.seen.get()?.0.push(steps);
+
+        Ok(())
+    }
+}
+
+#[test]
+fn sub_pm_runs_child() -> Result<(), PmError> {
+    let mut pm = pm!(Renderer, PhysicsSubPm);
+
+    pm.first()?;
+
+    for _ in 0..3 {
+        pm.update()?;
+    }
+
+    let local = pm.state.local.get()?;
+
+    // The renderer runs first, so it sees the child one parent update behind.
+    assert_eq!(local.get_state::<Seen>()?.get()?.0, [0, 4, 8]);
+    assert_eq!(local.get_state::<Physics>()?.get()?.steps, 12);
+
+    // Only exposed state reaches the parent's store.
+    assert!(!local.state_exists::<PhysicsInternal>());
+
+    Ok(())
+}
+
+#[test]
+fn child_runs_on_its_own() -> Result<(), PmError> {
+    let pm = Pm::with_shared_state()?;
+    let mut child = PhysicsPm::build(&pm.state)?;
+
+    child.first()?;
+    child.update()?;
+
+    let physics = child.state.local.get()?.get_state::<Physics>()?;
+
+    assert_eq!(physics.get()?.steps, 1);
+
+    // Exposing the same state twice is an error, same as adding it twice.
+    PhysicsPm::expose(&child, &pm.state)?;
+
+    assert!(matches!(
+        PhysicsPm::expose(&child, &pm.state),
+        Err(PmError::StateExists)
+    ));
+
+    pm.state.local.get()?.get_state::<Physics>()?.get()?.steps = 10;
+
+    assert_eq!(physics.get()?.steps, 10);
+
+    Ok(())
+}
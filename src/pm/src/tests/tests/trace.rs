@@ -0,0 +1,150 @@
+use pm::*;
+
+#[derive(StateTrait, Default)]
+#[pm(default)]
+struct Counter(u64);
+
+#[derive(Doer)]
+struct Count {
+    #[pm(init = Counter::default())]
+    counter: State<Counter>,
+}
+
+impl DoerTrait for Count {
+    derived_doer!();
+
+    fn update(&self) -> Result<(), PmError> {
+        self.counter.get()?.0 += 1;
+
+        Ok(())
+    }
+}
+
+/// Every span in the trace as (name, category) pairs.
+fn spans(json: &str) -> Vec<(String, String)> {
+    json.split("{\"name\":\"")
+        .skip(1)
+        .filter_map(|event| {
+            let (name, rest) = event.split_once('"')?;
+            let category = rest.strip_prefix(",\"cat\":\"")?.split('"').next()?;
+
+            Some((name.to_string(), category.to_string()))
+        })
+        .collect()
+}
+
+fn has_span(spans: &[(String, String)], name: &str, category: &str) -> bool {
+    spans
+        .iter()
+        .any(|(span_name, span_category)| span_name.ends_with(name) && span_category == category)
+}
+
+#[test]
+fn doer_spans() -> Result<(), PmError> {
+    let mut pm = Pm::with_shared_state()?;
+
+    pm.enable_tracing()?;
+    pm.add_doer::<Count>()?;
+    pm.first()?;
+    pm.update()?;
+    pm.update()?;
+
+    pm.doers
+        .state
+        .get()?
+        .message_queue
+        .push(DoerControlMessage::Remove(std::any::type_name::<Count>()));
+
+    pm.first()?;
+
+    let path = std::env::temp_dir().join("pm_trace_doer_spans.json");
+
+    pm.write_trace(&path)?;
+
+    let json = std::fs::read_to_string(&path).unwrap();
+    let spans = spans(&json);
+
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(json.starts_with("{\"traceEvents\":["));
+    assert!(json.ends_with("]}"));
+    assert!(has_span(&spans, "Count", "first"));
+    assert!(has_span(&spans, "Count", "update"));
+    assert!(has_span(&spans, "Count", "remove"));
+    assert!(has_span(&spans, "Pm::update", "pm"));
+
+    // State borrows are off by default.
+    assert!(!spans.iter().any(|(_, category)| category == "state"));
+
+    Ok(())
+}
+
+#[test]
+fn state_borrows() -> Result<(), PmError> {
+    let mut pm = Pm::with_shared_state()?;
+
+    let mut trace = Trace::new();
+    trace.state_borrows = true;
+
+    pm.state.shared.blocking_get()?.add_state(trace)?;
+    pm.enable_tracing()?;
+    pm.add_doer::<Count>()?;
+    pm.first()?;
+    pm.update()?;
+
+    let trace = pm.state.shared.blocking_get()?.get_state::<Trace>()?;
+    let spans = spans(&trace.blocking_get()?.chrome_trace());
+
+    assert!(has_span(&spans, "Counter", "state"));
+
+    Ok(())
+}
+
+#[test]
+fn threads() -> Result<(), PmError> {
+    let pm = Pm::with_shared_state()?;
+
+    pm.enable_tracing()?;
+
+    let handles: Vec<_> = (0..2)
+        .map(|thread| {
+            let shared_state = pm.state.shared.clone();
+
+            std::thread::Builder::new()
+                .name(format!("worker {}", thread))
+                .spawn(move || -> Result<(), PmError> {
+                    // Tracing is picked up from the shared store.
+                    let mut pm = Pm::new(shared_state)?;
+
+                    pm.add_doer::<Count>()?;
+                    pm.first()?;
+                    pm.update()
+                })
+                .unwrap()
+        })
+        .collect();
+
+    for handle in handles {
+        handle.join().unwrap()?;
+    }
+
+    let trace = pm.state.shared.blocking_get()?.get_state::<Trace>()?;
+    let json = trace.blocking_get()?.chrome_trace();
+
+    assert!(json.contains("\"args\":{\"name\":\"worker 0\"}"));
+    assert!(json.contains("\"args\":{\"name\":\"worker 1\"}"));
+
+    // Every thread's update spans are on their own tid.
+    let mut tids: Vec<_> = json
+        .split("{\"name\":\"")
+        .filter(|event| event.contains("\"cat\":\"update\""))
+        .filter_map(|event| event.split("\"tid\":").nth(1)?.split('}').next())
+        .collect();
+
+    tids.sort();
+    tids.dedup();
+
+    assert_eq!(tids.len(), 2);
+
+    Ok(())
+}
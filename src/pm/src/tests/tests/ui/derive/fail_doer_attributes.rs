@@ -0,0 +1,16 @@
+use pm::*;
+
+#[derive(Doer)]
+pub struct BadInit {
+    #[pm(init = 5)]
+    count: u32,
+    #[pm(init = 5, value = 6)]
+    other: u32,
+    #[pm(inti = 5)]
+    typo: u32,
+}
+
+#[derive(Doer)]
+pub struct Tuple(State<u32>);
+
+fn main() {}
@@ -0,0 +1,11 @@
+use pm::{SharedState as Shared, State as S, *};
+
+#[derive(Doer)]
+pub struct Aliased {
+    count: S<u32>,
+    names: Shared<Vec<String>>,
+    // Nested handles are fine, they come from their Default.
+    maybe: Option<S<u32>>,
+}
+
+fn main() {}
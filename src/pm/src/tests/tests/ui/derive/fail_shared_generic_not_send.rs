@@ -0,0 +1,13 @@
+use pm::*;
+use std::rc::Rc;
+
+#[derive(SharedStateTrait)]
+pub struct Holder<T> {
+    pub value: T,
+}
+
+fn shared<T: SharedStateTrait>() {}
+
+fn main() {
+    shared::<Holder<Rc<u32>>>();
+}
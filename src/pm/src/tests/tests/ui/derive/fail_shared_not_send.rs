@@ -0,0 +1,10 @@
+use pm::*;
+use std::rc::Rc;
+
+#[derive(SharedStateTrait)]
+pub struct Counts {
+    pub counts: Vec<Rc<u32>>,
+    pub local: Option<State<u32>>,
+}
+
+fn main() {}
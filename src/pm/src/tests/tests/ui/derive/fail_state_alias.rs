@@ -0,0 +1,8 @@
+use pm::{State as S, *};
+
+#[derive(StateTrait)]
+pub struct Aliased {
+    pub other: Vec<S<u32>>,
+}
+
+fn main() {}
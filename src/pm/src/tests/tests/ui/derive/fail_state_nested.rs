@@ -0,0 +1,8 @@
+use pm::*;
+
+#[derive(StateTrait)]
+pub struct Nested {
+    pub other: State<u32>,
+}
+
+fn main() {}
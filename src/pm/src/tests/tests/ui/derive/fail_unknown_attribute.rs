@@ -0,0 +1,9 @@
+use pm::*;
+
+#[derive(StateTrait)]
+#[pm(defaults)]
+pub struct Typo {
+    pub count: u32,
+}
+
+fn main() {}
@@ -0,0 +1,67 @@
+use pm::*;
+use std::time::Instant;
+
+#[derive(StateTrait)]
+pub struct Counter {
+    pub count: u32,
+}
+
+#[derive(SharedStateTrait)]
+pub struct SharedCounter {
+    pub count: u32,
+}
+
+#[derive(Doer)]
+pub struct Counting {
+    #[pm(init = Counter { count: 0 })]
+    counter: State<Counter>,
+    #[pm(init = SharedCounter { count: 0 })]
+    shared_counter: SharedState<SharedCounter>,
+    doer_state: State<DoerState>,
+    #[pm(value = Instant::now())]
+    start: Instant,
+    updates: std::cell::Cell<u32>,
+}
+
+impl DoerTrait for Counting {
+    derived_doer!();
+
+    fn update(&self) -> Result<(), PmError> {
+        self.counter.get()?.count += 1;
+        self.shared_counter.get()?.count += 1;
+        self.updates.set(self.updates.get() + 1);
+
+        let _ = self.start.elapsed();
+        let _ = self.doer_state.get()?;
+
+        Ok(())
+    }
+}
+
+#[derive(Doer)]
+pub struct NeedsCounter {
+    _counter: State<Counter>,
+}
+
+impl DoerTrait for NeedsCounter {
+    derived_doer!();
+}
+
+fn main() -> Result<(), PmError> {
+    let mut pm = pm!(Counting);
+
+    pm.first()?;
+    pm.update()?;
+
+    let counter = pm.state.local.get()?.get_state::<Counter>()?;
+    assert_eq!(counter.get()?.count, 1);
+
+    let empty = Pm::with_shared_state()?;
+
+    assert!(matches!(
+        NeedsCounter::new(&empty),
+        Err(PmError::MissingState { .. })
+    ));
+
+    Ok(())
+}
@@ -0,0 +1,38 @@
+use pm::*;
+
+#[derive(Default, StateTrait)]
+#[pm(default)]
+pub struct Buffer<T> {
+    pub items: Vec<T>,
+}
+
+#[derive(Default, SharedStateTrait)]
+#[pm(default)]
+pub struct SharedBuffer<T> {
+    pub items: Vec<T>,
+}
+
+#[derive(SharedStateTrait)]
+pub struct NoDefault {
+    pub other: SharedState<Vec<u8>>,
+}
+
+fn main() -> Result<(), PmError> {
+    let pm = Pm::with_shared_state()?;
+
+    pm.state.local.get()?.add_default_state::<Buffer<u32>>()?;
+    pm.state
+        .shared
+        .blocking_get()?
+        .add_default_state::<SharedBuffer<u32>>()?;
+
+    assert!(matches!(
+        pm.state
+            .shared
+            .blocking_get()?
+            .add_default_state::<NoDefault>(),
+        Err(PmError::NoDefaultState)
+    ));
+
+    Ok(())
+}
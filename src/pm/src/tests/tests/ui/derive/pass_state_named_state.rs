@@ -0,0 +1,29 @@
+use pm::*;
+
+/// Another crate's types that happen to share a name with a thread-local handle.
+mod other {
+    pub struct State(pub u32);
+    pub struct Rc;
+}
+
+#[derive(StateTrait)]
+pub struct Local {
+    pub other: other::State,
+    pub rcs: Vec<other::Rc>,
+}
+
+#[derive(SharedStateTrait)]
+pub struct Shared {
+    pub other: Option<other::State>,
+}
+
+fn main() -> Result<(), PmError> {
+    let pm = Pm::with_shared_state()?;
+
+    pm.state.local.get()?.add_state(Local {
+        other: other::State(1),
+        rcs: Vec::new(),
+    })?;
+
+    Ok(())
+}
@@ -0,0 +1,14 @@
+use pm::{SharedStateTrait, StateTrait};
+
+/// Only the derives are imported, so the field checks can't lean on a glob.
+#[derive(StateTrait)]
+pub struct Local {
+    pub count: u32,
+}
+
+#[derive(SharedStateTrait)]
+pub struct Shared {
+    pub names: Vec<String>,
+}
+
+fn main() {}
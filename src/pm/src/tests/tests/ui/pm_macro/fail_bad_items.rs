@@ -0,0 +1,20 @@
+use pm::*;
+
+pub struct A {}
+
+impl DoerTrait for A {
+    fn new(_pm: &Pm) -> Result<Box<dyn DoerTrait>, PmError>
+    where
+        Self: Sized,
+    {
+        Ok(Box::new(Self {}))
+    }
+}
+
+fn main() -> Result<(), PmError> {
+    let _pm = pm!(A, order { A behind A });
+
+    let _pm = pm!(A, 5);
+
+    Ok(())
+}
@@ -0,0 +1,81 @@
+use pm::*;
+
+#[derive(StateTrait)]
+pub struct Hidden {
+    pub value: u32,
+}
+
+#[derive(Doer)]
+pub struct A {}
+
+impl DoerTrait for A {
+    derived_doer!();
+}
+
+#[derive(Doer)]
+pub struct B {
+    #[pm(init = Hidden { value: 1 })]
+    _hidden: State<Hidden>,
+}
+
+impl DoerTrait for B {
+    derived_doer!();
+}
+
+/// Removes the state B added, only possible since it is created right after B.
+pub struct C {}
+
+impl DoerTrait for C {
+    fn new(pm: &Pm) -> Result<Box<dyn DoerTrait>, PmError>
+    where
+        Self: Sized,
+    {
+        pm.state.local.get()?.remove_state::<Hidden>()?;
+
+        Ok(Box::new(Self {}))
+    }
+}
+
+pub struct Failing {}
+
+impl DoerTrait for Failing {
+    fn new(_pm: &Pm) -> Result<Box<dyn DoerTrait>, PmError>
+    where
+        Self: Sized,
+    {
+        Ok(Box::new(Self {}))
+    }
+
+    fn update(&self) -> Result<(), PmError> {
+        Err(PmError::DoerUpdate)
+    }
+}
+
+fn names(pm: &Pm) -> Vec<&'static str> {
+    pm.doers.active.iter().map(|doer| doer.name()).collect()
+}
+
+fn main() -> Result<(), PmError> {
+    let mut pm = pm!(A, group { B, C }, order { C before A });
+    pm.first()?;
+
+    assert_eq!(
+        names(&pm),
+        vec![
+            std::any::type_name::<C>(),
+            std::any::type_name::<A>(),
+            std::any::type_name::<B>(),
+        ]
+    );
+    assert!(!pm.state.local.get()?.state_exists::<Hidden>());
+
+    let shared_state = pm.state.shared.clone();
+    let mut pm = pm!(shared_state; A, B,);
+    pm.first()?;
+
+    assert_eq!(names(&pm).len(), 2);
+
+    assert!(matches!(pm!(run Failing), Err(PmError::DoerUpdate)));
+
+    Ok(())
+}
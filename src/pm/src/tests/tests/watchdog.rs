@@ -0,0 +1,145 @@
+use pm::*;
+use std::{
+    any::type_name,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+struct Fast;
+
+impl DoerTrait for Fast {
+    fn new(_pm: &Pm) -> Result<Box<dyn DoerTrait>, PmError> {
+        Ok(Box::new(Self))
+    }
+}
+
+/// Blocks the tick, which is what the watchdog is for.
+struct Slow;
+
+impl DoerTrait for Slow {
+    fn new(_pm: &Pm) -> Result<Box<dyn DoerTrait>, PmError> {
+        Ok(Box::new(Self))
+    }
+
+    fn update(&self) -> Result<(), PmError> {
+        std::thread::sleep(Duration::from_millis(50));
+
+        Ok(())
+    }
+}
+
+fn fast_then_slow() -> Result<Pm, PmError> {
+    let mut pm = Pm::with_shared_state()?;
+
+    pm.add_doer::<Fast>()?;
+    pm.add_doer::<Slow>()?;
+    pm.first()?;
+
+    Ok(pm)
+}
+
+#[test]
+fn records_overruns() -> Result<(), PmError> {
+    let mut pm = fast_then_slow()?;
+    let reported = Arc::new(Mutex::new(Vec::new()));
+    let on_overrun_reported = reported.clone();
+
+    pm.start_watchdog(
+        Watchdog::new(Duration::from_millis(10))
+            .tick_budget(Duration::from_millis(20))
+            .check_every(Duration::from_millis(1))
+            .on_overrun(move |overrun| on_overrun_reported.lock().unwrap().push(overrun.kind)),
+    )?;
+
+    pm.update()?;
+
+    let overruns = pm.stop_watchdog()?;
+
+    assert_eq!(overruns.len(), 2);
+    assert_eq!(
+        *reported.lock().unwrap(),
+        [OverrunKind::Doer, OverrunKind::Tick]
+    );
+
+    for overrun in overruns.iter() {
+        assert_eq!(overrun.doer, Some((type_name::<Slow>(), "update")));
+        assert!(overrun.elapsed > overrun.budget);
+        assert_eq!(
+            overrun.trail,
+            [
+                (type_name::<Fast>(), "update"),
+                (type_name::<Slow>(), "update")
+            ]
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn overruns_are_bounded() -> Result<(), PmError> {
+    let mut pm = fast_then_slow()?;
+
+    pm.start_watchdog(
+        Watchdog::new(Duration::from_millis(10))
+            .check_every(Duration::from_millis(1))
+            .overruns(2),
+    )?;
+
+    for _ in 0..3 {
+        pm.update()?;
+    }
+
+    let watchdog = pm.doers.watchdog.as_ref().unwrap();
+
+    assert_eq!(watchdog.overrun_count(), 3);
+    assert_eq!(watchdog.overruns().len(), 2);
+
+    Ok(())
+}
+
+#[test]
+fn within_budget() -> Result<(), PmError> {
+    let mut pm = Pm::with_shared_state()?;
+
+    pm.add_doer::<Fast>()?;
+    pm.first()?;
+    pm.start_watchdog(Watchdog::new(Duration::from_secs(10)).trail(1))?;
+
+    for _ in 0..3 {
+        pm.update()?;
+    }
+
+    let watchdog = pm.doers.watchdog.as_ref().unwrap();
+
+    assert!(watchdog.overruns().is_empty());
+    assert_eq!(watchdog.trail(), [(type_name::<Fast>(), "update")]);
+
+    Ok(())
+}
+
+#[test]
+fn abort() -> Result<(), PmError> {
+    let mut pm = fast_then_slow()?;
+
+    pm.start_watchdog(
+        Watchdog::new(Duration::from_millis(10))
+            .check_every(Duration::from_millis(1))
+            .abort(),
+    )?;
+
+    match pm.update() {
+        Err(PmError::WatchdogAbort(overrun)) => {
+            assert_eq!(overrun.kind, OverrunKind::Doer);
+            assert_eq!(overrun.doer, Some((type_name::<Slow>(), "update")));
+        }
+        result => panic!("expected the watchdog to abort, got {:?}", result),
+    }
+
+    assert!(matches!(
+        pm.start_watchdog(Watchdog::new(Duration::from_millis(10))),
+        Err(PmError::WatchdogExists)
+    ));
+
+    Ok(())
+}